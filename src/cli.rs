@@ -0,0 +1,524 @@
+use std::{
+    collections::HashSet,
+    env,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+
+use crate::lib::{events, facts::Facts, jobs::Settings, runner, state};
+
+#[derive(Clone, Debug, Parser)]
+#[command(about, version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// check first, then ask once whether to apply what would change
+    #[arg(long)]
+    pub ask: bool,
+
+    /// only report what jobs would change, without changing anything
+    #[arg(long, alias = "dry-run")]
+    pub check: bool,
+
+    /// whether to color output, where "auto" is only for a terminal, and only without NO_COLOR
+    #[arg(long, value_enum, default_value_t = Color::Auto, value_name = "WHEN")]
+    pub color: Color,
+
+    /// ask before each job that removes or overwrites things, e.g. with `force = true`
+    #[arg(long)]
+    pub confirm: bool,
+
+    /// read this config file, or main.toml in this directory, or stdin for -, rather than the usual places
+    #[arg(long, env = "TUNING_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// show how jobs change (or would change, with --check) the contents of files
+    #[arg(long)]
+    pub diff: bool,
+
+    /// like --check, then summarize which jobs would change, by job type
+    #[arg(long)]
+    pub drift: bool,
+
+    /// append every job status change to this file as JSON lines, or "-" for stdout
+    #[arg(long, value_name = "PATH")]
+    pub events: Option<PathBuf>,
+
+    /// set a fact for templates, e.g. --fact is_work_machine=true, where the value is TOML or a string
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_fact)]
+    pub fact: Vec<(String, serde_json::Value)>,
+
+    /// stop starting new jobs as soon as any job fails
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// the most jobs with a tag or type that can run at the same time, e.g. --limit network=1
+    #[arg(long, value_delimiter = ',', value_name = "NAME=N", value_parser = parse_limit)]
+    pub limit: Vec<(String, usize)>,
+
+    /// how much to log to stderr, e.g. "warn" for less, or "debug" for more [default: info]
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+
+    /// only run jobs with these names (and the jobs they need), e.g. --only vimrc
+    #[arg(long, value_delimiter = ',', value_name = "NAME")]
+    pub only: Vec<String>,
+
+    /// start jobs in the order they are configured, rather than as soon as they can run
+    #[arg(long)]
+    pub ordered: bool,
+
+    /// print the report at the end as one JSON document, with nothing else on stdout
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = Output::Human)]
+    pub output: Output,
+
+    /// only print jobs that changed, failed, or were skipped, with a count of the rest
+    #[arg(long, short = 'q')]
+    pub quiet: bool,
+
+    /// only run jobs that did not succeed last time, e.g. that failed or were blocked
+    #[arg(long, alias = "resume")]
+    pub retry_failed: bool,
+
+    /// skip jobs with these names (so the jobs that need them cannot run), e.g. --skip rustup,nvim
+    #[arg(long, value_delimiter = ',', value_name = "NAME")]
+    pub skip: Vec<String>,
+
+    /// do not run jobs that needed no change last time, if neither they nor their files changed,
+    /// except for jobs that manage more than files (e.g. packages)
+    #[arg(long)]
+    pub skip_unchanged: bool,
+
+    /// skip jobs with any of these tags, e.g. --skip-tags gui,slow
+    #[arg(long, value_delimiter = ',')]
+    pub skip_tags: Vec<String>,
+
+    /// print job output as it arrives, prefixed by job name, rather than when each job finishes
+    #[arg(long)]
+    pub stream: bool,
+
+    /// only run jobs with any of these tags (and the jobs they need), e.g. --tags shell
+    #[arg(long, value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// assume the jobs configured before this one are done, e.g. to resume a long first run
+    #[arg(long, value_name = "NAME")]
+    pub start_at_job: Option<String>,
+
+    /// stop starting new jobs after this long, and time out jobs that are still running, e.g. 30m
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// how many jobs can run at the same time [default: number of CPUs]
+    #[arg(long, short = 'j')]
+    pub threads: Option<usize>,
+
+    /// log more, e.g. -v for debug (which includes rendered jobs and why jobs are skipped)
+    #[arg(long, short = 'v', action = ArgAction::Count, conflicts_with = "log_level")]
+    pub verbose: u8,
+
+    /// run again whenever a file in the config directory changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// answer yes to every question, from --ask or --confirm, for unattended runs
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+}
+
+// instead of running the jobs
+#[derive(Clone, Debug, Subcommand)]
+pub enum Command {
+    /// append a job to the config, e.g. `tuning add link --src ~/dotfiles/zshrc --path ~/.zshrc`
+    Add {
+        /// what the job is called [default: from the job, e.g. "link .zshrc"]
+        #[arg(long, global = true)]
+        name: Option<String>,
+        /// jobs that must succeed first
+        #[arg(long, global = true, value_delimiter = ',', value_name = "NAME")]
+        needs: Vec<String>,
+        /// e.g. --tags shell, to run it with `tuning --tags shell`
+        #[arg(long, global = true, value_delimiter = ',')]
+        tags: Vec<String>,
+        #[command(subcommand)]
+        job: NewJob,
+    },
+    /// check this machine for problems that would stop jobs from working, exiting 1 if there are any
+    Doctor,
+    /// print everything about one job, and whether it would run now, without running any jobs
+    Explain {
+        #[arg(value_name = "NAME")]
+        job: String,
+    },
+    /// print the values that are available to templates on this machine
+    Facts {
+        #[arg(long, value_enum, default_value_t = Format::Toml)]
+        format: Format,
+    },
+    /// rewrite the config with sorted keys and consistent quotes and indentation, keeping comments
+    Fmt {
+        /// only print what would change, exiting 1 if anything would
+        #[arg(long)]
+        check: bool,
+    },
+    /// print earlier runs, newest first, with the jobs that changed, failed, or were blocked
+    History {
+        /// only this job's status in each run
+        #[arg(long, value_name = "NAME")]
+        job: Option<String>,
+    },
+    /// write a starter config, with a commented example of each type of job
+    Init {
+        /// overwrite the config if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// print every job's name, type, tags and needs, in config order, without running any jobs
+    List {
+        /// show each job under the jobs that it needs
+        #[arg(long)]
+        tree: bool,
+    },
+    /// print how jobs need each other, e.g. for `dot -Tsvg`, without running any jobs
+    Graph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// run `tuning --yes --quiet` periodically, via systemd, launchd, or Task Scheduler
+    Schedule {
+        /// how long between runs, e.g. 1h
+        #[arg(long, value_name = "DURATION", default_value = "1d", value_parser = humantime::parse_duration)]
+        every: Duration,
+        /// stop running periodically
+        #[arg(long, conflicts_with = "every")]
+        remove: bool,
+    },
+    /// list every problem with the config, without running any jobs, exiting 1 if there are any
+    Validate,
+    /// print the config after templates are rendered, without running any jobs
+    Render {
+        /// only this job, after expanding `with_items`, `include`, and so on
+        #[arg(long, value_name = "NAME")]
+        job: Option<String>,
+    },
+}
+
+// for `tuning add`, with the fields that most jobs of each kind need
+#[derive(Clone, Debug, Subcommand)]
+pub enum NewJob {
+    /// a "cargo" job, to install a crate
+    Cargo {
+        #[arg(value_name = "CRATE")]
+        krate: String,
+    },
+    /// a "command" job, e.g. `tuning add command --creates ~/src/nvim -- git clone ...`
+    Command {
+        /// only run it if this path does not exist yet
+        #[arg(long, value_name = "PATH")]
+        creates: Option<PathBuf>,
+        command: String,
+        argv: Vec<String>,
+    },
+    /// a "file" job, to link `path` to `src`
+    Link {
+        /// replace whatever is at `path`
+        #[arg(long)]
+        force: bool,
+        #[arg(long, value_name = "PATH")]
+        path: PathBuf,
+        #[arg(long, value_name = "PATH")]
+        src: PathBuf,
+    },
+    /// a "script" job
+    Script {
+        /// only run it if this path does not exist yet
+        #[arg(long, value_name = "PATH")]
+        creates: Option<PathBuf>,
+        script: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Color {
+    Always,
+    Auto,
+    Never,
+}
+impl Color {
+    // `no_color` is the NO_COLOR environment variable, see https://no-color.org/
+    fn is_colored(self, no_color: Option<String>, is_terminal: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Auto => is_terminal && no_color.unwrap_or_default().is_empty(),
+            Self::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Output {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl Cli {
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level.unwrap_or(match self.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        })
+    }
+
+    // so that logs and pipes do not get ANSI codes
+    pub fn is_colored(&self) -> bool {
+        self.color
+            .is_colored(env::var("NO_COLOR").ok(), io::stdout().is_terminal())
+    }
+
+    // whether to check, then ask, before applying
+    pub fn is_ask(&self, settings: &Settings) -> bool {
+        (self.ask || settings.ask) && !self.yes && !self.check && !self.drift
+    }
+
+    // whether people are reading stdout, rather than the events stream or a JSON report
+    pub fn is_human(&self) -> bool {
+        self.output == Output::Human && self.events.as_deref() != Some(Path::new(events::STDOUT))
+    }
+
+    // `settings` from the config apply unless overridden here
+    pub fn runner_options(&self, settings: &Settings, facts: &Facts) -> runner::Options {
+        runner::Options {
+            check: self.check || self.drift,
+            confirm: (self.confirm || settings.confirm) && !self.yes,
+            // the run starts now
+            deadline: self
+                .timeout
+                .or(settings.timeout)
+                .map(|t| Instant::now() + t),
+            diff: self.diff,
+            events: self.events.clone(),
+            fail_fast: self.fail_fast,
+            history: Some(state::durations_path(&facts.cache_dir)),
+            limits: settings
+                .limits
+                .clone()
+                .into_iter()
+                .chain(self.limit.iter().cloned())
+                .collect(),
+            logs: Some(
+                facts
+                    .cache_dir
+                    .join(env!("CARGO_PKG_NAME"))
+                    .join("logs")
+                    .join(events::run_id()),
+            ),
+            json_report: self.output == Output::Json,
+            only: self.only.clone(),
+            ordered: self.ordered || settings.ordered,
+            quiet: self.quiet || settings.quiet,
+            skip: self.skip.clone(),
+            start_at: self.start_at_job.clone(),
+            skip_tags: self.skip_tags.clone(),
+            // see `main()`, which reads these from the state files
+            succeeded: HashSet::new(),
+            unchanged: HashSet::new(),
+            stream: self.stream,
+            tags: self.tags.clone(),
+            threads: self.threads.or(settings.threads),
+        }
+    }
+}
+
+// e.g. "is_work_machine=true", "count=2", or "role=laptop"
+fn parse_fact(s: &str) -> Result<(String, serde_json::Value), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, not {:?}", s))?;
+    // strings do not need quotes
+    let value = toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(String::from(value)));
+    let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    Ok((String::from(name), value))
+}
+
+// e.g. "network=1"
+fn parse_limit(s: &str) -> Result<(String, usize), String> {
+    let (name, max) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=N, not {:?}", s))?;
+    let max = max.parse().map_err(|e| format!("{}: {}", max, e))?;
+    Ok((String::from(name), max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tags() {
+        let cli = Cli::parse_from(["tuning", "--tags", "shell,git", "--skip-tags=gui"]);
+        let got = cli.runner_options(&Settings::default(), &Facts::default());
+        assert_eq!(got.tags, vec![String::from("shell"), String::from("git")]);
+        assert_eq!(got.skip_tags, vec![String::from("gui")]);
+    }
+
+    #[test]
+    fn color_only_for_terminals_without_no_color() {
+        assert!(Color::Auto.is_colored(None, true));
+        assert!(Color::Auto.is_colored(Some(String::new()), true));
+        assert!(!Color::Auto.is_colored(Some(String::from("1")), true));
+        assert!(!Color::Auto.is_colored(None, false));
+        assert!(Color::Always.is_colored(Some(String::from("1")), false));
+        assert!(!Color::Never.is_colored(None, true));
+    }
+
+    #[test]
+    fn yes_overrides_ask() {
+        let settings = Settings {
+            ask: true,
+            ..Default::default()
+        };
+        assert!(Cli::parse_from(["tuning"]).is_ask(&settings));
+        assert!(Cli::parse_from(["tuning", "--ask"]).is_ask(&Settings::default()));
+        assert!(!Cli::parse_from(["tuning", "--ask", "-y"]).is_ask(&Settings::default()));
+        assert!(!Cli::parse_from(["tuning", "--check"]).is_ask(&settings));
+    }
+
+    #[test]
+    fn parse_facts() {
+        let cli = Cli::parse_from([
+            "tuning",
+            "--fact",
+            "is_work_machine=true",
+            "--fact=count=2",
+            "--fact",
+            "role=laptop, mostly",
+        ]);
+        assert_eq!(
+            cli.fact,
+            vec![
+                (String::from("is_work_machine"), serde_json::json!(true)),
+                (String::from("count"), serde_json::json!(2)),
+                (String::from("role"), serde_json::json!("laptop, mostly")),
+            ]
+        );
+        assert!(Cli::try_parse_from(["tuning", "--fact", "is_work_machine"]).is_err());
+    }
+
+    #[test]
+    fn parse_log_level() {
+        assert_eq!(Cli::parse_from(["tuning"]).log_level(), LevelFilter::Info);
+        assert_eq!(
+            Cli::parse_from(["tuning", "-v"]).log_level(),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            Cli::parse_from(["tuning", "-vv"]).log_level(),
+            LevelFilter::Trace
+        );
+        assert_eq!(
+            Cli::parse_from(["tuning", "--log-level", "warn"]).log_level(),
+            LevelFilter::Warn
+        );
+        assert!(Cli::try_parse_from(["tuning", "-v", "--log-level", "warn"]).is_err());
+    }
+
+    #[test]
+    fn parse_only_and_skip() {
+        let cli = Cli::parse_from(["tuning", "--only", "a,b", "--skip=c"]);
+        let got = cli.runner_options(&Settings::default(), &Facts::default());
+        assert_eq!(got.only, vec![String::from("a"), String::from("b")]);
+        assert_eq!(got.skip, vec![String::from("c")]);
+    }
+
+    #[test]
+    fn parse_add_with_metadata_after_the_job() {
+        let cli = Cli::parse_from([
+            "tuning", "add", "command", "--name", "clone", "git", "clone", "--tags", "a,b",
+        ]);
+        match cli.command {
+            Some(Command::Add {
+                name,
+                tags,
+                job: NewJob::Command { command, argv, .. },
+                ..
+            }) => {
+                assert_eq!(name.as_deref(), Some("clone"));
+                assert_eq!(tags, vec![String::from("a"), String::from("b")]);
+                assert_eq!(command, "git");
+                assert_eq!(argv, vec![String::from("clone")]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn limits_override_settings() {
+        let mut settings = Settings::default();
+        settings.limits.insert(String::from("network"), 1);
+        settings.limits.insert(String::from("compile"), 2);
+        let cli = Cli::parse_from(["tuning", "--limit", "compile=4,gui=1"]);
+        let got = cli.runner_options(&settings, &Facts::default()).limits;
+        assert_eq!(got.get("network"), Some(&1));
+        assert_eq!(got.get("compile"), Some(&4));
+        assert_eq!(got.get("gui"), Some(&1));
+
+        assert!(Cli::try_parse_from(["tuning", "--limit", "network"]).is_err());
+    }
+
+    #[test]
+    fn parse_facts_command() {
+        let cli = Cli::parse_from(["tuning", "facts", "--format", "json"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Facts {
+                format: Format::Json
+            })
+        ));
+        let cli = Cli::parse_from(["tuning", "facts"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Facts {
+                format: Format::Toml
+            })
+        ));
+    }
+
+    #[test]
+    fn threads_override_settings() {
+        let settings = Settings {
+            threads: Some(4),
+            ..Default::default()
+        };
+        let cli = Cli::parse_from(["tuning"]);
+        assert_eq!(
+            cli.runner_options(&settings, &Facts::default()).threads,
+            Some(4)
+        );
+        let cli = Cli::parse_from(["tuning", "-j", "8"]);
+        assert_eq!(
+            cli.runner_options(&settings, &Facts::default()).threads,
+            Some(8)
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+use super::fmt;
+
+// `text` with `job` as another `[[jobs]]` at the end, formatted like `tuning fmt`,
+// but without changing the rest of `text`
+pub fn append(text: &str, job: Table) -> Result<String, fmt::Error> {
+    let mut jobs = ArrayOfTables::new();
+    jobs.push(job);
+    let mut doc = DocumentMut::new();
+    doc.insert("jobs", Item::ArrayOfTables(jobs));
+    let job = fmt::format(&doc.to_string())?;
+    let text = text.trim_end();
+    if text.is_empty() {
+        return Ok(job);
+    }
+    Ok(format!("{}\n\n{}", text, job))
+}
+
+// e.g. "{{ home_dir }}/.zshrc", so that the config also works for other people
+pub fn portable_path(path: &Path, home_dir: &Path) -> String {
+    match path.strip_prefix(home_dir) {
+        Ok(rest) if rest.as_os_str().is_empty() => String::from("{{ home_dir }}"),
+        Ok(rest) => format!(
+            "{{{{ home_dir }}}}/{}",
+            rest.iter()
+                .map(|c| c.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        ),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml_edit::value;
+
+    use super::*;
+
+    #[test]
+    fn append_a_formatted_job() -> Result<(), fmt::Error> {
+        let mut job = Table::new();
+        job.insert("state", value("link"));
+        job.insert("type", value("file"));
+        job.insert("name", value("link .zshrc"));
+
+        let text = "# mine\n[[jobs]]\nname = 'a'\ntype = 'command'\ncommand = 'a'\n\n\n";
+        assert_eq!(
+            append(text, job.clone())?,
+            "# mine\n[[jobs]]\nname = 'a'\ntype = 'command'\ncommand = 'a'\n\n\
+             [[jobs]]\nname = \"link .zshrc\"\ntype = \"file\"\nstate = \"link\"\n"
+        );
+        assert_eq!(
+            append("", job)?,
+            "[[jobs]]\nname = \"link .zshrc\"\ntype = \"file\"\nstate = \"link\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn portable_path_under_home_dir() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            portable_path(Path::new("/home/me/dotfiles/zshrc"), home),
+            "{{ home_dir }}/dotfiles/zshrc"
+        );
+        assert_eq!(portable_path(home, home), "{{ home_dir }}");
+        assert_eq!(portable_path(Path::new("/etc/hosts"), home), "/etc/hosts");
+    }
+}
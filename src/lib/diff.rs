@@ -0,0 +1,130 @@
+use std::path::Path;
+
+// lines of unchanged context around each change
+const CONTEXT: usize = 3;
+// beyond this many line comparisons, everything is shown as removed then added
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// like `diff -u`, or an empty string if nothing changed
+pub fn unified(path: &Path, before: &str, after: &str) -> String {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let ops = ops(&a, &b);
+    if ops.iter().all(|(op, _, _)| *op == Op::Equal) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", path.display(), path.display());
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _, _))| *op != Op::Equal)
+        .map(|(i, _)| i)
+        .collect();
+    let mut first = 0;
+    while first < changes.len() {
+        // changes that are close enough to share their context
+        let mut last = first;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[first].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(ops.len());
+        out.push_str(&hunk(&ops[start..end], &a, &b));
+        first = last + 1;
+    }
+    out
+}
+
+fn hunk(ops: &[(Op, usize, usize)], a: &[&str], b: &[&str]) -> String {
+    let (_, a_start, b_start) = ops[0];
+    let a_count = ops.iter().filter(|(op, _, _)| *op != Op::Insert).count();
+    let b_count = ops.iter().filter(|(op, _, _)| *op != Op::Delete).count();
+    // line numbers start at 1, except for an empty range, which is after the line before it
+    let mut out = format!(
+        "@@ -{},{} +{},{} @@\n",
+        if a_count > 0 { a_start + 1 } else { a_start },
+        a_count,
+        if b_count > 0 { b_start + 1 } else { b_start },
+        b_count
+    );
+    for (op, i, j) in ops {
+        match op {
+            Op::Equal => out.push_str(&format!(" {}\n", a[*i])),
+            Op::Delete => out.push_str(&format!("-{}\n", a[*i])),
+            Op::Insert => out.push_str(&format!("+{}\n", b[*j])),
+        }
+    }
+    out
+}
+
+// each step from `a` to `b`, with the index into each (of the next line, if not used)
+fn ops(a: &[&str], b: &[&str]) -> Vec<(Op, usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+    let mut ops = Vec::new();
+    if n * m > MAX_CELLS {
+        ops.extend((0..n).map(|i| (Op::Delete, i, 0)));
+        ops.extend((0..m).map(|j| (Op::Insert, n, j)));
+        return ops;
+    }
+    // lengths of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            ops.push((Op::Equal, i, j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // removed lines first, like `diff -u`
+            ops.push((Op::Delete, i, j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_shows_changes_with_context() {
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let after = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            unified(Path::new("f"), before, after),
+            "--- f\n+++ f\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+    }
+
+    #[test]
+    fn unified_from_nothing() {
+        assert_eq!(
+            unified(Path::new("f"), "", "a\nb\n"),
+            "--- f\n+++ f\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+        assert_eq!(unified(Path::new("f"), "a\n", "a\n"), "");
+    }
+}
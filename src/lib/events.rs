@@ -0,0 +1,622 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, OpenOptions},
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use colored::*;
+use serde::Serialize;
+use thiserror::Error as ThisError;
+
+use super::{jobs, runner};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to open {} for events: {}", path.display(), source)]
+    OpenPath { path: PathBuf, source: io::Error },
+}
+
+// where the JSON-lines stream goes instead of a file
+pub const STDOUT: &str = "-";
+// how many jobs to list in the summary
+const SLOWEST_COUNT: usize = 5;
+// for the prefix of each streamed line, chosen by job name
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Red,
+];
+
+// one line of the stream, for each time a job changes Status
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Event {
+    pub name: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // only once the job has finished executing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+}
+impl Event {
+    pub fn new(name: &str, result: &jobs::Result, duration: Option<Duration>) -> Self {
+        let (status, message) = jobs::result_status(result);
+        let message = Some(message).filter(|m| !m.is_empty());
+        let (message, error) = match result {
+            Err(_) => (None, message),
+            Ok(_) => (message, None),
+        };
+        Self {
+            name: String::from(name),
+            status: String::from(status),
+            message,
+            error,
+            duration_ms: duration.map(|d| d.as_millis()),
+        }
+    }
+}
+
+// the whole run at once, e.g. for `--output json`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub status: &'static str,
+    pub exit_code: u8,
+    // how many jobs ended with each status
+    pub totals: BTreeMap<String, usize>,
+    // sorted by name
+    pub jobs: Vec<Event>,
+}
+impl Report {
+    pub fn new(report: &runner::RunReport) -> Self {
+        let mut jobs: Vec<Event> = report
+            .results
+            .iter()
+            .map(|(name, result)| Event::new(name, result, report.durations.get(name).copied()))
+            .collect();
+        jobs.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut totals = BTreeMap::<String, usize>::new();
+        for job in &jobs {
+            *totals.entry(job.status.clone()).or_default() += 1;
+        }
+        let status = report.status();
+        Self {
+            status: status.name(),
+            exit_code: status.exit_code(),
+            totals,
+            jobs,
+        }
+    }
+}
+
+// observes each job as it runs, e.g. to write the JSON-lines stream,
+// so that other tools can follow along without parsing what is printed
+pub trait Hook: Send + Sync {
+    // each time a job changes Status, including when it starts and finishes
+    fn status(&self, name: &str, result: &jobs::Result, duration: Option<Duration>);
+    // just before a job executes
+    fn started(&self, _name: &str) {}
+    // once a job has executed, with everything it wrote to stdout and stderr
+    fn finished(&self, _name: &str, _result: &jobs::Result, _duration: Duration, _output: &[u8]) {}
+}
+
+// writes one `Event` per line
+struct JsonLines {
+    sink: Mutex<Box<dyn Write + Send>>,
+}
+impl Hook for JsonLines {
+    fn status(&self, name: &str, result: &jobs::Result, duration: Option<Duration>) {
+        let event = Event::new(name, result, duration);
+        let mut sink = self.sink.lock().unwrap();
+        // the stream is best-effort, and should never fail the run
+        serde_json::to_writer(&mut *sink, &event).ok();
+        writeln!(sink).ok();
+        sink.flush().ok();
+    }
+}
+
+// reports job Status transitions to people, and to any hooks
+pub struct Events {
+    // how long each finished job took
+    durations: Mutex<Vec<(String, Duration)>>,
+    hooks: Vec<Box<dyn Hook>>,
+    // show how jobs change the contents of files
+    diff: bool,
+    // where to write a log file for each finished job
+    logs: Option<PathBuf>,
+    // one question at a time, see `confirm()`
+    prompt: Mutex<()>,
+    // only print jobs that did something, or that need attention
+    quiet: bool,
+    // whether job output is printed as it arrives, rather than once each job finishes
+    stream: bool,
+    // false when the JSON-lines stream or a JSON report is written to stdout
+    human: bool,
+    // how many NoChange jobs were not printed, see `quiet`
+    unchanged: AtomicUsize,
+}
+impl Events {
+    // appends to `path`, so that several runs can share one log
+    pub fn open(options: &runner::Options) -> Result<Self, Error> {
+        let path = options.events.as_deref();
+        let sink: Option<Box<dyn Write + Send>> = match path {
+            None => None,
+            Some(p) if p == Path::new(STDOUT) => Some(Box::new(io::stdout())),
+            Some(p) => Some(Box::new(
+                OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(p)
+                    .map_err(|e| Error::OpenPath {
+                        path: p.to_path_buf(),
+                        source: e,
+                    })?,
+            )),
+        };
+        let mut events = Self {
+            durations: Mutex::new(Vec::new()),
+            diff: options.diff,
+            hooks: Vec::new(),
+            logs: options.logs.clone(),
+            prompt: Mutex::new(()),
+            quiet: options.quiet,
+            stream: options.stream,
+            human: !options.json_report && path.is_none_or(|p| p != Path::new(STDOUT)),
+            unchanged: AtomicUsize::new(0),
+        };
+        if let Some(s) = sink {
+            events.add_hook(Box::new(JsonLines {
+                sink: Mutex::new(s),
+            }));
+        }
+        Ok(events)
+    }
+
+    // observe every job from now on, as well as the existing hooks
+    pub fn add_hook(&mut self, hook: Box<dyn Hook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_diff(&self) -> bool {
+        self.diff
+    }
+
+    // whether people are reading stdout,
+    // i.e. whether to print anything else there
+    pub fn is_human(&self) -> bool {
+        self.human
+    }
+
+    // asks whether to execute a destructive job, on stderr if stdout is for the JSON-lines stream,
+    // and the answer is no without a terminal to answer from
+    pub fn confirm(&self, name: &str) -> bool {
+        let _lock = self.prompt.lock().unwrap();
+        let question = format!("{} {}? [y/N] ", "confirm:".yellow().bold(), name);
+        ask_terminal(&question, self.human)
+    }
+
+    // whether to print this result for people, see `quiet`
+    pub fn is_shown(&self, result: &jobs::Result) -> bool {
+        !self.quiet
+            || !matches!(
+                result,
+                Ok(jobs::Status::InProgress) | Ok(jobs::Status::NoChange(_))
+            )
+    }
+
+    // e.g. "[job name] ", when streaming job output
+    pub fn prefix(&self, name: &str) -> Option<String> {
+        if !self.stream {
+            return None;
+        }
+        let index = name.bytes().map(usize::from).sum::<usize>() % PREFIX_COLORS.len();
+        Some(format!(
+            "{} ",
+            format!("[{}]", name).color(PREFIX_COLORS[index])
+        ))
+    }
+
+    pub fn job(&self, name: &str, result: &jobs::Result, duration: Option<Duration>) {
+        if let Ok(jobs::Status::InProgress) = result {
+            self.hooks.iter().for_each(|h| h.started(name));
+        }
+        if let Some(d) = duration {
+            self.durations.lock().unwrap().push((String::from(name), d));
+        }
+        if self.human && !self.is_shown(result) {
+            if matches!(result, Ok(jobs::Status::NoChange(_))) {
+                self.unchanged.fetch_add(1, Ordering::Relaxed);
+            }
+        } else if self.human {
+            match duration {
+                Some(d) => println!(
+                    "job: {}: {} {}",
+                    name,
+                    jobs::result_display(result),
+                    format!("({})", display_duration(d)).dimmed()
+                ),
+                None => println!("job: {}: {}", name, jobs::result_display(result)),
+            }
+        }
+        self.emit(name, result, duration);
+    }
+
+    // everything a job wrote to stdout and stderr, all at once unless already streamed,
+    // on stderr if stdout is for the JSON-lines stream
+    pub fn finished(&self, name: &str, result: &jobs::Result, duration: Duration, output: &[u8]) {
+        if !self.stream && !output.is_empty() && self.is_shown(result) {
+            if self.human {
+                io::stdout().lock().write_all(output).ok();
+            } else {
+                io::stderr().lock().write_all(output).ok();
+            }
+        }
+        let log = self.write_log(name, result, duration, output);
+        self.hooks
+            .iter()
+            .for_each(|h| h.finished(name, result, duration, output));
+        self.job(name, result, Some(duration));
+        if let (Some(path), Err(_), true) = (log, result, self.human) {
+            println!("    {}", format!("log: {}", path.display()).dimmed());
+        }
+    }
+
+    // logs are best-effort, and should never fail the run
+    fn write_log(
+        &self,
+        name: &str,
+        result: &jobs::Result,
+        duration: Duration,
+        output: &[u8],
+    ) -> Option<PathBuf> {
+        let dir = self.logs.as_ref()?;
+        fs::create_dir_all(dir).ok()?;
+        let path = dir.join(log_file_name(name));
+        let (status, message) = jobs::result_status(result);
+        let mut log = format!(
+            "job: {}\nstatus: {}\nmessage: {}\nduration: {}\n\n",
+            name,
+            status,
+            message,
+            display_duration(duration)
+        )
+        .into_bytes();
+        log.extend_from_slice(output);
+        fs::write(&path, log).ok()?;
+        Some(path)
+    }
+
+    // how many NoChange jobs were not printed
+    pub fn unchanged(&self) -> usize {
+        self.unchanged.load(Ordering::Relaxed)
+    }
+
+    // how long each finished job took
+    pub fn durations(&self) -> Vec<(String, Duration)> {
+        self.durations.lock().unwrap().clone()
+    }
+
+    // once all jobs have finished
+    pub fn summary(&self, report: &runner::RunReport) {
+        if !self.human {
+            return;
+        }
+        if self.quiet {
+            println!("unchanged: {} jobs", self.unchanged());
+            return;
+        }
+        if report.durations.is_empty() {
+            return;
+        }
+        println!("slowest jobs:");
+        for (name, d) in slowest(&report.durations, SLOWEST_COUNT) {
+            println!("    {:>8}  {}", display_duration(*d), name);
+        }
+    }
+
+    // only to the hooks, for transitions that people do not need to see
+    pub fn emit(&self, name: &str, result: &jobs::Result, duration: Option<Duration>) {
+        self.hooks
+            .iter()
+            .for_each(|h| h.status(name, result, duration));
+    }
+}
+
+// after a check, whether to go ahead with the changes, like `Events::confirm()`
+pub fn confirm_apply(human: bool) -> bool {
+    let question = format!("{} apply these changes? [y/N] ", "ask:".yellow().bold());
+    ask_terminal(&question, human)
+}
+
+// on stderr if stdout is not for people, and the answer is no without a terminal
+fn ask_terminal(question: &str, human: bool) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    if human {
+        ask(question, io::stdin().lock(), io::stdout().lock())
+    } else {
+        ask(question, io::stdin().lock(), io::stderr().lock())
+    }
+}
+
+// writes `question`, then reads one line of `input`
+pub fn ask(question: &str, mut input: impl BufRead, mut output: impl Write) -> bool {
+    write!(output, "{}", question).ok();
+    output.flush().ok();
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// after a check, which jobs would change, grouped by job type (from `kinds`),
+// and how many jobs of each type could not be checked
+pub fn drift(results: &HashMap<String, jobs::Result>, kinds: &HashMap<String, &str>) -> String {
+    let mut drifted = BTreeMap::<&str, Vec<String>>::new();
+    let mut unchecked = BTreeMap::<&str, usize>::new();
+    for (name, result) in results {
+        let kind = kinds.get(name).copied().unwrap_or("handler");
+        match result {
+            Ok(jobs::Status::WouldChange(from, to)) => drifted
+                .entry(kind)
+                .or_default()
+                .push(format!("{}: {} => {}", name, from, to)),
+            Ok(jobs::Status::Unchecked(_)) => *unchecked.entry(kind).or_default() += 1,
+            _ => {}
+        }
+    }
+    let mut lines = vec![String::from("drift:")];
+    if drifted.is_empty() {
+        lines.push(String::from("    none"));
+    }
+    for (kind, mut changes) in drifted {
+        changes.sort();
+        lines.push(format!("    {}:", kind));
+        lines.extend(changes.into_iter().map(|c| format!("        {}", c)));
+    }
+    if !unchecked.is_empty() {
+        let counts: Vec<String> = unchecked
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        lines.push(format!("not checked: {}", counts.join(", ")));
+    }
+    lines.join("\n")
+}
+
+// unique per run, and sorts in the order that runs started,
+// e.g. "2020-05-03T101500Z"
+pub fn run_id() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now())
+        .to_string()
+        .replace(':', "")
+}
+
+// job names can contain anything, but file names cannot
+fn log_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.log", name)
+}
+
+// e.g. "0.3s" or "12.0s"
+fn display_duration(d: Duration) -> String {
+    format!("{:.1}s", d.as_secs_f64())
+}
+
+fn slowest(durations: &HashMap<String, Duration>, count: usize) -> Vec<(&String, &Duration)> {
+    let mut sorted: Vec<_> = durations.iter().collect();
+    // longest first, with ties in name order so that output is stable
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    sorted.truncate(count);
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_for_each_kind_of_result() {
+        let got = Event::new("a", &Ok(jobs::Status::InProgress), None);
+        assert_eq!(
+            serde_json::to_string(&got).unwrap(),
+            r#"{"name":"a","status":"inprogress"}"#
+        );
+
+        let got = Event::new(
+            "b",
+            &Ok(jobs::Status::Changed(String::from("x"), String::from("y"))),
+            Some(Duration::from_millis(1500)),
+        );
+        assert_eq!(
+            serde_json::to_string(&got).unwrap(),
+            r#"{"name":"b","status":"changed","message":"y","duration_ms":1500}"#
+        );
+
+        let got = Event::new(
+            "c",
+            &Err(jobs::Error::SomethingBad),
+            Some(Duration::from_millis(2)),
+        );
+        assert_eq!(got.status, "failed");
+        assert_eq!(got.message, None);
+        assert!(got.error.is_some());
+    }
+
+    #[test]
+    fn report_totals_and_sorted_jobs() {
+        let mut report = runner::RunReport::default();
+        report
+            .results
+            .insert(String::from("b"), Ok(jobs::Status::Done));
+        report.results.insert(
+            String::from("a"),
+            Ok(jobs::Status::Changed(String::new(), String::from("x"))),
+        );
+        report
+            .results
+            .insert(String::from("c"), Ok(jobs::Status::Done));
+        report
+            .durations
+            .insert(String::from("a"), Duration::from_millis(5));
+
+        let got = Report::new(&report);
+        assert_eq!(got.status, "changed");
+        assert_eq!(got.exit_code, 2);
+        let names: Vec<&str> = got.jobs.iter().map(|j| j.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(got.jobs[0].duration_ms, Some(5));
+        assert_eq!(got.totals.get("changed"), Some(&1));
+        assert_eq!(got.totals.get("done"), Some(&2));
+    }
+
+    #[test]
+    fn slowest_first() {
+        let mut durations = HashMap::<String, Duration>::new();
+        for (name, ms) in [("a", 100), ("b", 3000), ("c", 100), ("d", 700)].iter() {
+            durations.insert(String::from(*name), Duration::from_millis(*ms));
+        }
+        let got: Vec<&str> = slowest(&durations, 3)
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(got, vec!["b", "d", "a"]);
+        assert_eq!(display_duration(Duration::from_millis(3040)), "3.0s");
+    }
+
+    #[test]
+    fn prefix_only_when_streaming() {
+        let events = Events::open(&runner::Options::default()).unwrap();
+        assert_eq!(events.prefix("a"), None);
+
+        let events = Events::open(&runner::Options {
+            stream: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let got = events.prefix("a").expect("prefix");
+        assert!(got.contains("[a]"));
+        assert_eq!(events.prefix("a"), Some(got));
+    }
+
+    #[test]
+    fn writes_a_log_for_each_finished_job() {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let events = Events::open(&runner::Options {
+            logs: Some(temp.to_path_buf()),
+            stream: true,
+            ..Default::default()
+        })
+        .unwrap();
+        events.finished(
+            "echo hi > ~/a",
+            &Ok(jobs::Status::Done),
+            Duration::from_millis(100),
+            b"hi\n",
+        );
+        let got = fs::read_to_string(temp.join("echo_hi_____a.log")).expect("log file");
+        assert_eq!(
+            got,
+            "job: echo hi > ~/a\nstatus: done\nmessage: \nduration: 0.1s\n\nhi\n"
+        );
+    }
+
+    #[test]
+    fn quiet_counts_unchanged_jobs() {
+        let events = Events::open(&runner::Options {
+            quiet: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!events.is_shown(&Ok(jobs::Status::InProgress)));
+        assert!(events.is_shown(&Ok(jobs::Status::Skipped)));
+        assert!(events.is_shown(&Err(jobs::Error::SomethingBad)));
+        for name in ["a", "b"] {
+            events.job(
+                name,
+                &Ok(jobs::Status::NoChange(String::from(name))),
+                Some(Duration::from_millis(100)),
+            );
+        }
+        events.job("c", &Ok(jobs::Status::Done), None);
+        assert_eq!(events.unchanged(), 2);
+
+        let events = Events::open(&runner::Options::default()).unwrap();
+        assert!(events.is_shown(&Ok(jobs::Status::NoChange(String::from("a")))));
+    }
+
+    #[test]
+    fn ask_defaults_to_no() {
+        let mut output = Vec::<u8>::new();
+        assert!(ask("rm -rf a? ", &b"y\n"[..], &mut output));
+        assert_eq!(output, b"rm -rf a? ");
+        assert!(ask("", &b" YES \n"[..], io::sink()));
+        assert!(!ask("", &b"\n"[..], io::sink()));
+        assert!(!ask("", &b""[..], io::sink()));
+        assert!(!ask("", &b"nope\n"[..], io::sink()));
+    }
+
+    #[test]
+    fn drift_grouped_by_kind() {
+        let mut results = HashMap::<String, jobs::Result>::new();
+        let mut kinds = HashMap::<String, &str>::new();
+        for (name, kind, from, to) in [
+            ("b", "file", "absent", "file"),
+            ("a", "file", "absent", "symlink"),
+            ("c", "command", "not run", "run: c"),
+        ] {
+            results.insert(
+                String::from(name),
+                Ok(jobs::Status::WouldChange(
+                    String::from(from),
+                    String::from(to),
+                )),
+            );
+            kinds.insert(String::from(name), kind);
+        }
+        for name in ["d", "e"] {
+            results.insert(
+                String::from(name),
+                Ok(jobs::Status::Unchecked(String::from("not yet available"))),
+            );
+            kinds.insert(String::from(name), "wait_for");
+        }
+        results.insert(
+            String::from("f"),
+            Ok(jobs::Status::NoChange(String::from("f"))),
+        );
+        kinds.insert(String::from("f"), "file");
+
+        assert_eq!(
+            drift(&results, &kinds),
+            "drift:
+    command:
+        c: not run => run: c
+    file:
+        a: absent => symlink
+        b: absent => file
+not checked: 2 wait_for"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashSet, env};
+
+use tera::Context;
+
+use super::jobs::{self, Execute};
+
+// why a job would run or not, without running anything,
+// except for guards like `only_if`, which are only known when it runs
+pub fn verdict(jobs: &[impl Execute], name: &str, context: &Context) -> Result<(), String> {
+    verdict_within(jobs, name, context, &mut HashSet::new())
+}
+
+fn verdict_within(
+    jobs: &[impl Execute],
+    name: &str,
+    context: &Context,
+    seen: &mut HashSet<String>,
+) -> Result<(), String> {
+    let job = match jobs.iter().find(|j| j.name() == name) {
+        Some(j) => j,
+        None => return Err(format!("{:?} is not a job", name)),
+    };
+    // only the jobs on the way here, as several jobs can need the same job
+    if !seen.insert(String::from(name)) {
+        return Err(String::from("it needs itself, via its needs"));
+    }
+    let result = verdict_job(jobs, job, context, seen);
+    seen.remove(name);
+    result
+}
+
+fn verdict_job<J: Execute>(
+    jobs: &[J],
+    job: &J,
+    context: &Context,
+    seen: &mut HashSet<String>,
+) -> Result<(), String> {
+    if !jobs::is_os_supported(&job.os()) {
+        return Err(format!("it is not for {}", env::consts::OS));
+    }
+    for need in job.needs() {
+        if !jobs.iter().any(|j| j.name() == need) {
+            return Err(format!("it needs {:?}, which is not a job", need));
+        }
+        if let Err(reason) = verdict_within(jobs, &need, context, seen) {
+            return Err(format!(
+                "it needs {:?}, which would not run: {}",
+                need, reason
+            ));
+        }
+    }
+    match job.when(context) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(String::from("\"when\" is false")),
+        Err(e) => Err(format!("\"when\" failed: {}", e)),
+    }
+}
+
+// every job that `name` needs, directly or not, in an order they could run in
+pub fn chain(jobs: &[impl Execute], name: &str) -> Vec<String> {
+    let mut chain = Vec::new();
+    chain_within(jobs, name, &mut HashSet::new(), &mut chain);
+    // the job itself is last
+    chain.pop();
+    chain
+}
+
+fn chain_within(
+    jobs: &[impl Execute],
+    name: &str,
+    seen: &mut HashSet<String>,
+    chain: &mut Vec<String>,
+) {
+    if !seen.insert(String::from(name)) {
+        return;
+    }
+    if let Some(job) = jobs.iter().find(|j| j.name() == name) {
+        for need in job.needs() {
+            chain_within(jobs, &need, seen, chain);
+        }
+    }
+    chain.push(String::from(name));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::lib::jobs::Main;
+
+    const CONFIG: &str = r#"
+        [[jobs]]
+        name = "a"
+        type = "command"
+        command = "a"
+
+        [[jobs]]
+        name = "b"
+        type = "command"
+        command = "b"
+        needs = ["a"]
+        when = false
+
+        [[jobs]]
+        name = "c"
+        type = "command"
+        command = "c"
+        needs = ["b", "a"]
+
+        [[jobs]]
+        name = "d"
+        type = "command"
+        command = "d"
+        needs = ["nope"]
+        "#;
+
+    #[test]
+    fn chain_of_needs_in_run_order() {
+        let m = Main::try_from(CONFIG).expect("config");
+        assert_eq!(chain(&m.jobs, "c"), vec!["a", "b"]);
+        assert!(chain(&m.jobs, "a").is_empty());
+    }
+
+    #[test]
+    fn verdict_explains_skips() {
+        let m = Main::try_from(CONFIG).expect("config");
+        let context = Context::new();
+        assert_eq!(verdict(&m.jobs, "a", &context), Ok(()));
+        assert_eq!(
+            verdict(&m.jobs, "b", &context),
+            Err(String::from("\"when\" is false"))
+        );
+        assert_eq!(
+            verdict(&m.jobs, "c", &context),
+            Err(String::from(
+                "it needs \"b\", which would not run: \"when\" is false"
+            ))
+        );
+        assert_eq!(
+            verdict(&m.jobs, "d", &context),
+            Err(String::from("it needs \"nope\", which is not a job"))
+        );
+    }
+}
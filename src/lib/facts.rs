@@ -1,8 +1,15 @@
-use std::{env::consts::OS, path::PathBuf};
+use std::{collections::BTreeMap, env::consts::OS, path::PathBuf, sync::Mutex};
 
-use serde::Serialize;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
+lazy_static! {
+    // e.g. from `--fact`, so that every `gather()` agrees, including within "group" jobs
+    static ref OVERRIDES: Mutex<BTreeMap<String, serde_json::Value>> = Mutex::new(BTreeMap::new());
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, ThisError)]
 pub enum Error {
     #[error("unable to find cache_dir")]
@@ -11,9 +18,14 @@ pub enum Error {
     ConfigDir,
     #[error("unable to find home_dir")]
     HomeDir,
+    #[error("unable to set fact {}: {}", name, source)]
+    InvalidFact {
+        name: String,
+        source: serde_json::Error,
+    },
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Facts {
     pub cache_dir: PathBuf,
     pub config_dir: PathBuf,
@@ -21,17 +33,38 @@ pub struct Facts {
     pub is_os_linux: bool,
     pub is_os_macos: bool,
     pub is_os_windows: bool,
+    // any other facts, e.g. `--fact is_work_machine=true`
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, serde_json::Value>,
 }
 impl Facts {
     pub fn gather() -> Result {
-        Ok(Self {
+        let facts = Self {
             cache_dir: dirs::cache_dir().ok_or(Error::CacheDir)?,
             config_dir: dirs::config_dir().ok_or(Error::ConfigDir)?,
             home_dir: dirs::home_dir().ok_or(Error::HomeDir)?,
             is_os_linux: OS == "linux",
             is_os_macos: OS == "macos",
             is_os_windows: OS == "windows",
-        })
+            custom: BTreeMap::new(),
+        };
+        let overrides = OVERRIDES.lock().expect("facts overrides lock").clone();
+        facts.with_overrides(overrides)
+    }
+
+    // replaces facts with the same names, and adds the rest
+    fn with_overrides(self, overrides: BTreeMap<String, serde_json::Value>) -> Result {
+        let mut facts = self;
+        for (name, value) in overrides {
+            let mut map = match serde_json::to_value(&facts) {
+                Ok(serde_json::Value::Object(m)) => m,
+                _ => unreachable!("facts are always an object"),
+            };
+            map.insert(name.clone(), value);
+            facts = serde_json::from_value(serde_json::Value::Object(map))
+                .map_err(|source| Error::InvalidFact { name, source })?;
+        }
+        Ok(facts)
     }
 }
 impl Default for Facts {
@@ -43,8 +76,40 @@ impl Default for Facts {
             is_os_linux: false,
             is_os_macos: false,
             is_os_windows: false,
+            custom: BTreeMap::new(),
         }
     }
 }
 
 pub type Result = std::result::Result<Facts, Error>;
+
+// for every later `Facts::gather()`
+pub fn set_overrides(overrides: BTreeMap<String, serde_json::Value>) {
+    *OVERRIDES.lock().expect("facts overrides lock") = overrides;
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn with_overrides_replaces_and_adds_facts() -> std::result::Result<(), Error> {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(String::from("is_os_macos"), json!(true));
+        overrides.insert(String::from("is_work_machine"), json!(true));
+        let facts = Facts::default().with_overrides(overrides)?;
+        assert!(facts.is_os_macos);
+        assert_eq!(facts.custom.get("is_work_machine"), Some(&json!(true)));
+        assert_eq!(facts.custom.get("is_os_macos"), None);
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert(String::from("is_os_macos"), json!("yes"));
+        assert!(matches!(
+            Facts::default().with_overrides(overrides),
+            Err(Error::InvalidFact { .. })
+        ));
+        Ok(())
+    }
+}
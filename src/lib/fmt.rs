@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+
+use thiserror::Error as ThisError;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Key, RawString, Table, Value};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(
+        "unable to parse as TOML, e.g. `{{% %}}` tags outside of strings: {}",
+        source
+    )]
+    Parse {
+        #[from]
+        source: toml_edit::TomlError,
+    },
+}
+
+// these come first, as they say what each job is
+const FIRST_KEYS: [&str; 2] = ["name", "type"];
+// for each value of an array that spans several lines
+const INDENT: &str = "    ";
+
+// the config with sorted keys, the same quotes for every string, no indentation,
+// and a blank line before each table, but with comments where they were,
+// except for strings with template tags, which may need their quotes as they are
+pub fn format(text: &str) -> Result<String, Error> {
+    let mut doc: DocumentMut = text.parse()?;
+    format_table(doc.as_table_mut());
+    let trailing = reindent(raw_str(Some(doc.trailing())));
+    doc.set_trailing(trailing);
+    let formatted = doc.to_string();
+    Ok(format!(
+        "{}\n",
+        formatted.trim_start_matches('\n').trim_end()
+    ))
+}
+
+fn format_table(table: &mut Table) {
+    table.sort_values_by(|a, _, b, _| compare_keys(a, b));
+    if !table.is_dotted() && !table.is_implicit() {
+        let decor = table.decor_mut();
+        let prefix = reindent(raw_str(decor.prefix()));
+        let suffix = comment(raw_str(decor.suffix()));
+        decor.set_prefix(format!("\n{}", prefix.trim_start_matches('\n')));
+        decor.set_suffix(suffix);
+    }
+    for (mut key, item) in table.iter_mut() {
+        let decor = key.leaf_decor_mut();
+        match item {
+            Item::Value(v) => {
+                let prefix = reindent(raw_str(decor.prefix()));
+                decor.set_prefix(prefix);
+                decor.set_suffix(" ");
+                format_value(v);
+                let suffix = comment(raw_str(v.decor().suffix()));
+                v.decor_mut().set_prefix(" ");
+                v.decor_mut().set_suffix(suffix);
+            }
+            // the key is in the header, e.g. `[[jobs]]`
+            Item::Table(t) => {
+                decor.clear();
+                format_table(t);
+            }
+            Item::ArrayOfTables(a) => {
+                decor.clear();
+                a.iter_mut().for_each(format_table);
+            }
+            Item::None => {}
+        }
+    }
+}
+
+// only the value itself, as its surroundings depend on where it is
+fn format_value(value: &mut Value) {
+    match value {
+        Value::String(s) if !s.value().contains("{{") && !s.value().contains("{%") => s.fmt(),
+        Value::String(_) => {}
+        Value::Integer(i) => i.fmt(),
+        Value::Float(f) => f.fmt(),
+        Value::Boolean(b) => b.fmt(),
+        Value::Datetime(d) => d.fmt(),
+        Value::Array(a) => format_array(a),
+        Value::InlineTable(t) => format_inline_table(t),
+    }
+}
+
+// on one line, or one value per line if it was on several lines,
+// unless there are comments between the values, which are left where they are
+fn format_array(array: &mut Array) {
+    array.iter_mut().for_each(format_value);
+    let mut between: Vec<&str> = array
+        .iter()
+        .flat_map(|v| vec![raw_str(v.decor().prefix()), raw_str(v.decor().suffix())])
+        .collect();
+    between.push(raw_str(Some(array.trailing())));
+    if between.iter().any(|s| s.contains('#')) {
+        return;
+    }
+    if between.iter().any(|s| s.contains('\n')) {
+        for value in array.iter_mut() {
+            value.decor_mut().set_prefix(format!("\n{}", INDENT));
+            value.decor_mut().set_suffix("");
+        }
+        array.set_trailing_comma(true);
+        array.set_trailing("\n");
+    } else {
+        array.fmt();
+    }
+}
+
+fn format_inline_table(table: &mut InlineTable) {
+    table.sort_values_by(|a, _, b, _| compare_keys(a, b));
+    for (_, value) in table.iter_mut() {
+        format_value(value);
+    }
+    table.fmt();
+}
+
+fn compare_keys(a: &Key, b: &Key) -> Ordering {
+    let rank = |k: &Key| {
+        FIRST_KEYS
+            .iter()
+            .position(|f| *f == k.get())
+            .unwrap_or(FIRST_KEYS.len())
+    };
+    rank(a).cmp(&rank(b)).then_with(|| a.get().cmp(b.get()))
+}
+
+// whitespace and comments, without indentation, and with at most one blank line in a row
+fn reindent(raw: &str) -> String {
+    let mut lines: Vec<&str> = raw.split('\n').collect();
+    // `split()` always returns at least one line, which is the indentation before a key
+    let last = lines.pop().unwrap_or_default().trim();
+    let mut reindented = String::new();
+    let mut was_blank = false;
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() && was_blank {
+            continue;
+        }
+        was_blank = line.is_empty();
+        reindented.push_str(line);
+        reindented.push('\n');
+    }
+    reindented.push_str(last);
+    reindented
+}
+
+// a comment after a value, e.g. " # why", or nothing if there is none
+fn comment(raw: &str) -> String {
+    match raw.trim() {
+        "" => String::new(),
+        c => format!(" {}", c),
+    }
+}
+
+fn raw_str(raw: Option<&RawString>) -> &str {
+    raw.and_then(|r| r.as_str()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_sorts_keys_and_keeps_comments() -> Result<(), Error> {
+        let input = r#"
+# my config
+  [[jobs]]
+    command = 'git'   # needs git
+    # which repository
+    argv = [ "clone",
+      'https://example.com/{{ name }}.git' ]
+    type = "command"
+    name = 'clone'
+[[jobs]]
+name="b"
+type="file"
+environment = { b = "2", a = '1' }
+"#;
+        let want = r#"# my config
+[[jobs]]
+name = "clone"
+type = "command"
+# which repository
+argv = [
+    "clone",
+    'https://example.com/{{ name }}.git',
+]
+command = "git" # needs git
+
+[[jobs]]
+name = "b"
+type = "file"
+environment = { a = "1", b = "2" }
+"#;
+        assert_eq!(format(input)?, want);
+        assert_eq!(format(want)?, want);
+        Ok(())
+    }
+
+    #[test]
+    fn format_errs_on_tags_outside_strings() {
+        assert!(format("{% if is_os_linux %}\n[[jobs]]\n{% endif %}\n").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error as ThisError;
+
+// a commented example of each type of job, for people who are new to tuning
+const EXAMPLE: &str = include_str!("init.toml");
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("{} already exists, so not overwriting it without --force", path.display())]
+    PathExists { path: PathBuf },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+// writes the example config to `path`, creating its directory if necessary
+pub fn write(path: &Path, force: bool) -> Result<(), Error> {
+    if path.exists() && !force {
+        return Err(Error::PathExists {
+            path: path.to_path_buf(),
+        });
+    }
+    let write_path_error = |source| Error::WritePath {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(write_path_error)?;
+    }
+    fs::write(path, EXAMPLE).map_err(write_path_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, convert::TryFrom};
+
+    use super::*;
+    use crate::lib::{
+        facts::Facts,
+        jobs::{Execute, Main},
+        template,
+    };
+
+    #[test]
+    fn example_renders() {
+        let rendered = template::render(EXAMPLE, &Facts::default()).expect("render");
+        let m = Main::try_from(rendered.as_str()).expect("parse");
+        assert_eq!(m.jobs.len(), 1);
+    }
+
+    #[test]
+    fn example_has_a_valid_job_of_each_type() {
+        // "#[[jobs]]" and so on, but not "# prose"
+        let uncommented: String = EXAMPLE
+            .lines()
+            .map(|line| match line.strip_prefix('#') {
+                Some(l) if !l.is_empty() && !l.starts_with(' ') => l,
+                _ => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let rendered = template::render(uncommented, &Facts::default()).expect("render");
+        let m = Main::try_from(rendered.as_str()).expect("parse");
+        let kinds: HashSet<&str> = m.jobs.iter().map(|j| j.kind()).collect();
+        // every variant of `Spec`
+        assert_eq!(kinds.len(), 38);
+    }
+
+    #[test]
+    fn write_does_not_overwrite_without_force() {
+        let dir = mktemp::Temp::new_dir().expect("temp dir");
+        let path = dir.join("tuning").join("main.toml");
+
+        write(&path, false).expect("write");
+        assert_eq!(fs::read_to_string(&path).expect("read"), EXAMPLE);
+
+        fs::write(&path, "mine").expect("write");
+        assert!(matches!(write(&path, false), Err(Error::PathExists { .. })));
+        assert_eq!(fs::read_to_string(&path).expect("read"), "mine");
+
+        write(&path, true).expect("write");
+        assert_eq!(fs::read_to_string(&path).expect("read"), EXAMPLE);
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+const DEFAULT_PRIORITY: u32 = 50;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("{} is not an alternative for {}, set link to install it", path.display(), alternative)]
+    NotInstalled { alternative: String, path: PathBuf },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Alternatives {
+    pub alternative: String,
+    pub link: Option<PathBuf>,
+    pub path: PathBuf,
+    pub priority: Option<u32>,
+}
+impl Alternatives {
+    pub fn execute(&self) -> Result {
+        let cmd = alternatives_command();
+        let path = self.path.to_string_lossy();
+        let data = command::capture(cmd, &["--display", &self.alternative])?;
+        // an unknown alternative exits non-zero, which is fine if we install it
+        let display = if data.success() {
+            parse_display(data.stdout_str())
+        } else {
+            Display::default()
+        };
+
+        if display.current.as_deref() == Some(path.as_ref()) {
+            return Ok(Status::NoChange(format!(
+                "{} -> {}",
+                self.alternative, path
+            )));
+        }
+
+        let changed = Status::Changed(
+            format!(
+                "{} -> {}",
+                self.alternative,
+                display.current.as_deref().unwrap_or("absent")
+            ),
+            format!("{} -> {}", self.alternative, path),
+        );
+        let install = if display.paths.iter().any(|p| p == path.as_ref()) {
+            None
+        } else {
+            Some(self.link.as_ref().ok_or_else(|| Error::NotInstalled {
+                alternative: self.alternative.clone(),
+                path: self.path.clone(),
+            })?)
+        };
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        if let Some(link) = install {
+            command::capture_success_as_root(
+                cmd,
+                &[
+                    "--install",
+                    &link.to_string_lossy(),
+                    &self.alternative,
+                    &path,
+                    &self.priority.unwrap_or(DEFAULT_PRIORITY).to_string(),
+                ],
+            )?;
+        }
+        command::capture_success_as_root(cmd, &["--set", &self.alternative, &path])?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "{} --set {} {}",
+            alternatives_command(),
+            self.alternative,
+            self.path.display()
+        )
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// Debian has update-alternatives, Fedora and friends call it alternatives
+fn alternatives_command() -> &'static str {
+    if which("update-alternatives").is_err() && which("alternatives").is_ok() {
+        "alternatives"
+    } else {
+        "update-alternatives"
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Display {
+    current: Option<String>,
+    paths: Vec<String>,
+}
+
+// e.g.
+// editor - manual mode
+//   link currently points to /usr/bin/nvim
+// /bin/nano - priority 40
+// /usr/bin/nvim - priority 30
+fn parse_display<S>(output: S) -> Display
+where
+    S: AsRef<str>,
+{
+    let mut display = Display::default();
+    for line in output.as_ref().lines() {
+        let l = line.trim();
+        if let Some(current) = l.strip_prefix("link currently points to ") {
+            display.current = Some(String::from(current.trim()));
+        } else if let Some((path, details)) = l.split_once(" - ") {
+            if details.contains("priority") {
+                display.paths.push(String::from(path.trim()));
+            }
+        }
+    }
+    display
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_alternative_and_path() {
+        let alternatives = Alternatives {
+            alternative: String::from("editor"),
+            path: PathBuf::from("/usr/bin/nvim"),
+            ..Default::default()
+        };
+        let got = alternatives.name();
+        assert!(got.ends_with("alternatives --set editor /usr/bin/nvim"));
+    }
+
+    #[test]
+    fn parse_display_from_debian() {
+        let input = "editor - manual mode\n  link best version is /bin/nano\n  link currently points to /usr/bin/nvim\n  link editor is /usr/bin/editor\n/bin/nano - priority 40\n/usr/bin/nvim - priority 30\n  slave editor.1.gz: /usr/share/man/man1/nvim.1.gz\n";
+        let got = parse_display(input);
+        let want = Display {
+            current: Some(String::from("/usr/bin/nvim")),
+            paths: vec![String::from("/bin/nano"), String::from("/usr/bin/nvim")],
+        };
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_display_from_fedora() {
+        let input = "java - status is auto.\n link currently points to /usr/lib/jvm/java-17/bin/java\n/usr/lib/jvm/java-17/bin/java - family java-17 priority 17\n";
+        let got = parse_display(input);
+        let want = Display {
+            current: Some(String::from("/usr/lib/jvm/java-17/bin/java")),
+            paths: vec![String::from("/usr/lib/jvm/java-17/bin/java")],
+        };
+        assert_eq!(got, want);
+    }
+}
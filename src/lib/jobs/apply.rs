@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{
+    super::{
+        events::Events,
+        facts::{self, Facts},
+        runner, template,
+    },
+    command, Status,
+};
+
+const DEFAULT_CONFIG_FILE: &str = "main.toml";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to find cache_dir")]
+    CacheDir,
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("unable to prepare template context: {}", source)]
+    Context { source: tera::Error },
+    #[error("failed jobs: {}", names.join(", "))]
+    Failed { names: Vec<String> },
+    #[error(transparent)]
+    Facts {
+        #[from]
+        source: facts::Error,
+    },
+    #[error("apply requires path or repo")]
+    NothingToDo,
+    #[error(transparent)]
+    Runner {
+        #[from]
+        source: runner::Error,
+    },
+    // boxed, as template errors can contain job errors, which can contain this
+    #[error(transparent)]
+    Template { source: Box<template::Error> },
+}
+
+// runs another config as a whole, e.g. a shared config underneath a personal one,
+// with `path` relative to the root of `repo` when cloning
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Apply {
+    pub branch: Option<String>,
+    pub path: Option<PathBuf>,
+    pub repo: Option<String>,
+}
+impl Apply {
+    pub fn execute(&self) -> Result {
+        let config = match &self.repo {
+            Some(r) => {
+                // fetching may move the checkout on, so only a real run does that
+                if command::is_check() {
+                    return Ok(Status::Unchecked(format!("{} not fetched", r)));
+                }
+                let dir = clone_dir(r)?;
+                sync_repo(r, self.branch.as_deref(), &dir)?;
+                dir.join(
+                    self.path
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE)),
+                )
+            }
+            None => self.path.clone().ok_or(Error::NothingToDo)?,
+        };
+
+        let facts = Facts::gather()?;
+        let main = template::render_file(&config, &facts).map_err(|e| Error::Template {
+            source: Box::new(e),
+        })?;
+        let context = template::context(&facts).map_err(|e| Error::Context { source: e })?;
+        let options = runner::Options {
+            check: command::is_check(),
+            ..Default::default()
+        };
+        let events = Arc::new(Events::open(&options).map_err(runner::Error::from)?);
+        let report =
+            runner::run_with_handlers(main.jobs, main.handlers, context, &options, &events)?;
+        events.summary(&report);
+        summarize(report.results)
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = vec![String::from("apply")];
+        if let Some(r) = &self.repo {
+            parts.push(r.clone());
+            if let Some(b) = &self.branch {
+                parts.push(format!("({})", b));
+            }
+        }
+        if let Some(p) = &self.path {
+            parts.push(p.display().to_string());
+        }
+        parts.join(" ")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn clone_dir(repo: &str) -> std::result::Result<PathBuf, Error> {
+    let dirname: String = repo
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Ok(dirs::cache_dir()
+        .ok_or(Error::CacheDir)?
+        .join(env!("CARGO_PKG_NAME"))
+        .join("apply")
+        .join(dirname))
+}
+
+// the sub-run already printed each of its jobs,
+// so this just rolls them up into a single status
+fn summarize(results: HashMap<String, super::Result>) -> Result {
+    let mut failed: Vec<String> = results
+        .iter()
+        .filter(|(_, r)| r.is_err())
+        .map(|(n, _)| n.clone())
+        .collect();
+    if !failed.is_empty() {
+        failed.sort();
+        return Err(Error::Failed { names: failed });
+    }
+
+    let mut changed: Vec<String> = results
+        .iter()
+        .filter(|(_, r)| matches!(r, Ok(Status::Changed(_, _)) | Ok(Status::WouldChange(_, _))))
+        .map(|(n, _)| n.clone())
+        .collect();
+    if !changed.is_empty() {
+        changed.sort();
+        return Ok(Status::Changed(
+            format!("{} jobs", results.len()),
+            format!("changed: {}", changed.join(", ")),
+        ));
+    }
+    if results.values().any(|r| matches!(r, Ok(Status::Done))) {
+        return Ok(Status::Done);
+    }
+    let mut unchecked: Vec<String> = results
+        .iter()
+        .filter(|(_, r)| matches!(r, Ok(Status::Unchecked(_))))
+        .map(|(n, _)| n.clone())
+        .collect();
+    if !unchecked.is_empty() {
+        unchecked.sort();
+        return Ok(Status::Unchecked(format!(
+            "not checked: {}",
+            unchecked.join(", ")
+        )));
+    }
+    Ok(Status::NoChange(format!("{} jobs", results.len())))
+}
+
+fn sync_repo(repo: &str, branch: Option<&str>, dir: &Path) -> std::result::Result<(), Error> {
+    if dir.join(".git").is_dir() {
+        command::capture_success_in(dir, "git", &["pull", "--ff-only"])?;
+        return Ok(());
+    }
+    let mut args = vec![String::from("clone"), String::from("--depth=1")];
+    if let Some(b) = branch {
+        args.push(format!("--branch={}", b));
+    }
+    args.push(String::from(repo));
+    args.push(dir.display().to_string());
+    command::capture_success("git", &args)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_repo_and_path() {
+        let apply = Apply {
+            branch: Some(String::from("main")),
+            path: Some(PathBuf::from("team/main.toml")),
+            repo: Some(String::from("https://example.com/team/dotfiles.git")),
+        };
+        let got = apply.name();
+        let want = "apply https://example.com/team/dotfiles.git (main) team/main.toml";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn summarize_results() {
+        let mut results = HashMap::<String, super::super::Result>::new();
+        results.insert(String::from("a"), Ok(Status::NoChange(String::new())));
+        results.insert(String::from("b"), Ok(Status::Skipped));
+        assert_eq!(
+            summarize(results).ok(),
+            Some(Status::NoChange(String::from("2 jobs")))
+        );
+
+        let mut results = HashMap::<String, super::super::Result>::new();
+        results.insert(
+            String::from("a"),
+            Ok(Status::Changed(String::new(), String::new())),
+        );
+        results.insert(String::from("b"), Ok(Status::Done));
+        assert_eq!(
+            summarize(results).ok(),
+            Some(Status::Changed(
+                String::from("2 jobs"),
+                String::from("changed: a")
+            ))
+        );
+
+        let mut results = HashMap::<String, super::super::Result>::new();
+        results.insert(String::from("a"), Err(super::super::Error::SomethingBad));
+        results.insert(String::from("b"), Ok(Status::Blocked));
+        match summarize(results) {
+            Err(Error::Failed { names }) => assert_eq!(names, vec![String::from("a")]),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("assertion failed: {}", msg)]
+    Failed { msg: String },
+    #[error("assert requires that, command or path")]
+    NothingToDo,
+}
+
+// `that` is usually a template expression, e.g. `that = {{ is_os_linux }}`,
+// which has already been rendered to a boolean by the time we see it
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Assert {
+    pub argv: Option<Vec<String>>,
+    pub command: Option<String>,
+    pub msg: Option<String>,
+    pub path: Option<PathBuf>,
+    pub that: Option<bool>,
+}
+impl Assert {
+    pub fn execute(&self) -> Result {
+        if self.that.is_none() && self.command.is_none() && self.path.is_none() {
+            return Err(Error::NothingToDo);
+        }
+
+        if self.that == Some(false) {
+            return Err(self.failed("`that` is false"));
+        }
+        if let Some(p) = &self.path {
+            if !p.exists() {
+                return Err(self.failed(&format!("{} does not exist", p.display())));
+            }
+        }
+        if let Some(c) = &self.command {
+            let args = self.argv.clone().unwrap_or_default();
+            if !command::capture(c, &args)?.success() {
+                return Err(self.failed(&format!("`{}` exited non-zero", self.command_line())));
+            }
+        }
+
+        Ok(Status::NoChange(String::from("assertion holds")))
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = Vec::<String>::new();
+        if let Some(t) = self.that {
+            parts.push(format!("{}", t));
+        }
+        if let Some(p) = &self.path {
+            parts.push(format!("[ -e {} ]", p.display()));
+        }
+        if self.command.is_some() {
+            parts.push(self.command_line());
+        }
+        format!("assert {}", parts.join(" && "))
+    }
+
+    fn command_line(&self) -> String {
+        let mut parts = vec![self.command.clone().unwrap_or_default()];
+        parts.extend(self.argv.clone().unwrap_or_default());
+        parts.join(" ")
+    }
+
+    // prefer the configured message, as it should explain what to do next
+    fn failed(&self, reason: &str) -> Error {
+        Error::Failed {
+            msg: self.msg.clone().unwrap_or_else(|| String::from(reason)),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_all_conditions() {
+        let assert = Assert {
+            argv: Some(vec![String::from("--version")]),
+            command: Some(String::from("git")),
+            path: Some(PathBuf::from("/etc/hosts")),
+            that: Some(true),
+            ..Default::default()
+        };
+        let got = assert.name();
+        let want = "assert true && [ -e /etc/hosts ] && git --version";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn execute_fails_with_msg() {
+        let assert = Assert {
+            msg: Some(String::from("please install the thing first")),
+            that: Some(false),
+            ..Default::default()
+        };
+        match assert.execute() {
+            Err(Error::Failed { msg }) => assert_eq!(msg, "please install the thing first"),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn execute_checks_path() {
+        let assert = Assert {
+            path: Some(PathBuf::from("/this/does/not/exist")),
+            ..Default::default()
+        };
+        assert!(assert.execute().is_err());
+
+        let assert = Assert {
+            path: Some(std::env::temp_dir()),
+            that: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(assert.execute(), Ok(Status::NoChange(_))));
+    }
+
+    #[test]
+    fn execute_requires_a_condition() {
+        let assert = Assert::default();
+        assert!(matches!(assert.execute(), Err(Error::NothingToDo)));
+    }
+}
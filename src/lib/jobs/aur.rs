@@ -0,0 +1,143 @@
+use std::io;
+
+use mktemp::Temp;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error(transparent)]
+    TempPath { source: io::Error },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AurHelper {
+    Makepkg,
+    Paru,
+    Yay,
+}
+impl AurHelper {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Makepkg => "makepkg",
+            Self::Paru => "paru",
+            Self::Yay => "yay",
+        }
+    }
+
+    fn detect() -> Self {
+        if which("paru").is_ok() {
+            Self::Paru
+        } else if which("yay").is_ok() {
+            Self::Yay
+        } else {
+            Self::Makepkg
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Aur {
+    pub helper: Option<AurHelper>,
+    pub package: String,
+}
+impl Aur {
+    pub fn execute(&self) -> Result {
+        if let Some(installed) = installed(&self.package)? {
+            return Ok(Status::NoChange(installed));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(
+                String::from("absent"),
+                self.package.clone(),
+            ));
+        }
+
+        match self.helper() {
+            AurHelper::Makepkg => {
+                let temp = Temp::new_dir().map_err(|e| Error::TempPath { source: e })?;
+                let url = format!("https://aur.archlinux.org/{}.git", self.package);
+                let dir = temp.join(&self.package);
+                command::capture_success(
+                    "git",
+                    &["clone", "--depth=1", &url, &dir.to_string_lossy()],
+                )?;
+                command::capture_success_in(&dir, "makepkg", &["-si", "--noconfirm"])?;
+            }
+            helper => {
+                command::capture_success(
+                    helper.command(),
+                    &["-S", "--needed", "--noconfirm", &self.package],
+                )?;
+            }
+        }
+
+        Ok(Status::Changed(
+            String::from("absent"),
+            installed(&self.package)?.unwrap_or_else(|| self.package.clone()),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        match self.helper() {
+            AurHelper::Makepkg => format!("makepkg -si {}", self.package),
+            helper => format!("{} -S {}", helper.command(), self.package),
+        }
+    }
+
+    fn helper(&self) -> AurHelper {
+        self.helper.unwrap_or_else(AurHelper::detect)
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// `pacman -Q foo` prints e.g. "foo 1.2.3-1", or exits non-zero
+fn installed<S>(package: S) -> std::result::Result<Option<String>, Error>
+where
+    S: AsRef<str>,
+{
+    let data = command::capture("pacman", &["-Q", package.as_ref()])?;
+    if data.success() {
+        Ok(Some(String::from(data.stdout_str().trim())))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_makepkg() {
+        let aur = Aur {
+            helper: Some(AurHelper::Makepkg),
+            package: String::from("foo"),
+        };
+        let got = aur.name();
+        let want = "makepkg -si foo";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_paru() {
+        let aur = Aur {
+            helper: Some(AurHelper::Paru),
+            package: String::from("foo"),
+        };
+        let got = aur.name();
+        let want = "paru -S foo";
+        assert_eq!(got, want);
+    }
+}
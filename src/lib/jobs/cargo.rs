@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    path::PathBuf,
+};
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Cargo {
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub features: Option<Vec<String>>,
+    pub locked: Option<bool>,
+    pub version: Option<String>,
+}
+impl Cargo {
+    pub fn execute(&self) -> Result {
+        let previously = installed_version(&self.krate)?;
+        if let Some(p) = &previously {
+            if self.is_satisfied_by(p)? {
+                return Ok(Status::NoChange(format!("{} v{}", self.krate, p)));
+            }
+        }
+        let previously = previously.map(|p| format!("{} v{}", self.krate, p));
+        if command::is_check() {
+            let wanted = match &self.version {
+                Some(v) => format!("{} v{}", self.krate, v),
+                None => self.krate.clone(),
+            };
+            return Ok(Status::Changed(
+                previously.unwrap_or_else(|| String::from("absent")),
+                wanted,
+            ));
+        }
+
+        // `cargo install` reinstalls by itself when the version or features differ
+        command::capture_success("cargo", &self.args())?;
+
+        let version = installed_version(&self.krate)?.unwrap_or_default();
+        Ok(Status::Changed(
+            previously.unwrap_or_else(|| String::from("absent")),
+            format!("{} v{}", self.krate, version),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = vec![String::from("cargo")];
+        parts.extend(self.args());
+        parts.join(" ")
+    }
+
+    // with the features we want, and the version we want, or the latest version if not pinned
+    fn is_satisfied_by(&self, installed: &str) -> std::result::Result<bool, Error> {
+        let wanted: BTreeSet<String> = self.features.iter().flatten().cloned().collect();
+        if installed_features(&self.krate).is_some_and(|f| f != wanted) {
+            return Ok(false);
+        }
+        match &self.version {
+            Some(v) => Ok(is_match(v, installed)),
+            None => Ok(latest_version(&self.krate)?.is_none_or(|l| !is_newer(&l, installed))),
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![String::from("install")];
+        if self.locked.unwrap_or(false) {
+            args.push(String::from("--locked"));
+        }
+        if let Some(f) = &self.features {
+            if !f.is_empty() {
+                args.push(String::from("--features"));
+                args.push(f.join(","));
+            }
+        }
+        if let Some(v) = &self.version {
+            args.push(String::from("--version"));
+            args.push(v.clone());
+        }
+        args.push(self.krate.clone());
+        args
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// what cargo records about each crate that it installs, in $CARGO_HOME/.crates2.json
+#[derive(Debug, Default, Deserialize)]
+struct Installs {
+    // e.g. "ripgrep 12.0.1 (registry+https://github.com/rust-lang/crates.io-index)"
+    installs: BTreeMap<String, Install>,
+}
+#[derive(Debug, Default, Deserialize)]
+struct Install {
+    #[serde(default)]
+    features: BTreeSet<String>,
+}
+
+// like `cargo install --version`: an exact version, unless it has an operator, e.g. "~1.2",
+// or is incomplete, e.g. "1.2", when any version that it matches will do
+fn is_match(wanted: &str, installed: &str) -> bool {
+    let installed = match Version::parse(installed) {
+        Ok(v) => v,
+        Err(_) => return wanted == installed,
+    };
+    match Version::parse(wanted.trim()) {
+        Ok(v) => v == installed,
+        Err(_) => VersionReq::parse(wanted).is_ok_and(|r| r.matches(&installed)),
+    }
+}
+
+fn is_newer(latest: &str, installed: &str) -> bool {
+    match (Version::parse(latest), Version::parse(installed)) {
+        (Ok(l), Ok(i)) => l > i,
+        _ => false,
+    }
+}
+
+// None when cargo has no record of the crate, e.g. when it predates `.crates2.json`
+fn installed_features(krate: &str) -> Option<BTreeSet<String>> {
+    let home = match env::var_os("CARGO_HOME") {
+        Some(h) => PathBuf::from(h),
+        None => dirs::home_dir()?.join(".cargo"),
+    };
+    let text = fs::read_to_string(home.join(".crates2.json")).ok()?;
+    parse_installed_features(&text, krate)
+}
+
+fn parse_installed_features(text: &str, krate: &str) -> Option<BTreeSet<String>> {
+    let installs: Installs = serde_json::from_str(text).ok()?;
+    installs
+        .installs
+        .into_iter()
+        .find(|(key, _)| key.split_whitespace().next() == Some(krate))
+        .map(|(_, install)| install.features)
+}
+
+// None when the registry cannot be searched, e.g. when offline,
+// in which case the installed version will have to do
+fn latest_version(krate: &str) -> std::result::Result<Option<String>, Error> {
+    let output = command::capture("cargo", &["search", "--limit", "10", krate])?;
+    if !output.success() {
+        return Ok(None);
+    }
+    Ok(parse_search(output.stdout_str(), krate))
+}
+
+// parses output from `cargo search`, e.g.:
+// ripgrep = "14.1.1"    # ripgrep is a line-oriented search tool ...
+fn parse_search<S>(output: S, krate: &str) -> Option<String>
+where
+    S: AsRef<str>,
+{
+    output.as_ref().lines().find_map(|line| {
+        let (name, rest) = line.split_once(" = \"")?;
+        let (version, _) = rest.split_once('"')?;
+        (name == krate).then(|| String::from(version))
+    })
+}
+
+fn installed_version<S>(krate: S) -> std::result::Result<Option<String>, Error>
+where
+    S: AsRef<str>,
+{
+    let output = command::capture_success("cargo", &["install", "--list"])?.stdout_str();
+    Ok(parse_install_list(output).remove(krate.as_ref()))
+}
+
+// parses output from `cargo install --list`, e.g.:
+// ripgrep v12.0.1:
+//     rg
+// tuning v0.1.8 (/home/user/tuning):
+//     tuning
+fn parse_install_list<S>(output: S) -> HashMap<String, String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut words = line.trim_end_matches(':').split_whitespace();
+            let krate = words.next()?;
+            let version = words.next()?.trim_start_matches('v');
+            Some((String::from(krate), String::from(version)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_crate() {
+        let cargo = Cargo {
+            krate: String::from("ripgrep"),
+            ..Default::default()
+        };
+        let got = cargo.name();
+        let want = "cargo install ripgrep";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_all_options() {
+        let cargo = Cargo {
+            krate: String::from("ripgrep"),
+            features: Some(vec![String::from("pcre2"), String::from("simd")]),
+            locked: Some(true),
+            version: Some(String::from("12.0.1")),
+        };
+        let got = cargo.name();
+        let want = "cargo install --locked --features pcre2,simd --version 12.0.1 ripgrep";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_install_list_with_crates() {
+        let input = "ripgrep v12.0.1:\n    rg\ntuning v0.1.8 (/home/user/tuning):\n    tuning\n";
+        let got = parse_install_list(input);
+        assert_eq!(got.len(), 2);
+        assert_eq!(got.get("ripgrep"), Some(&String::from("12.0.1")));
+        assert_eq!(got.get("tuning"), Some(&String::from("0.1.8")));
+    }
+
+    #[test]
+    fn is_match_with_exact_versions_and_requirements() {
+        assert!(is_match("12.0.1", "12.0.1"));
+        assert!(!is_match("12.0.1", "12.0.2"));
+        assert!(is_match("12.0", "12.0.2"));
+        assert!(is_match("~1.2", "1.2.3"));
+        assert!(!is_match("~1.2", "1.3.0"));
+        assert!(is_match(">=1, <2", "1.9.0"));
+        assert!(!is_match("not a version", "1.0.0"));
+    }
+
+    #[test]
+    fn is_newer_compares_semantic_versions() {
+        assert!(is_newer("1.10.0", "1.9.0"));
+        assert!(!is_newer("1.9.0", "1.9.0"));
+        assert!(!is_newer("1.0.0-beta", "1.0.0"));
+        assert!(!is_newer("?", "1.0.0"));
+    }
+
+    #[test]
+    fn parse_installed_features_by_crate() {
+        let input = r#"{"installs": {
+            "ripgrep 12.0.1 (registry+https://github.com/rust-lang/crates.io-index)": {
+                "version_req": null, "bins": ["rg"], "features": ["pcre2"],
+                "all_features": false, "no_default_features": false
+            },
+            "tuning 0.1.8 (path+file:///home/user/tuning)": {"bins": ["tuning"]}
+        }}"#;
+        assert_eq!(
+            parse_installed_features(input, "ripgrep"),
+            Some(BTreeSet::from([String::from("pcre2")]))
+        );
+        assert_eq!(
+            parse_installed_features(input, "tuning"),
+            Some(BTreeSet::new())
+        );
+        assert_eq!(parse_installed_features(input, "ripgrep-all"), None);
+        assert_eq!(parse_installed_features("", "ripgrep"), None);
+    }
+
+    #[test]
+    fn parse_search_finds_exact_name() {
+        let input = "ripgrep_all = \"0.10.6\"    # rga: ripgrep, but also search in PDFs\nripgrep = \"14.1.1\"        # ripgrep is a line-oriented search tool\n... and 200 crates more\n";
+        assert_eq!(parse_search(input, "ripgrep"), Some(String::from("14.1.1")));
+        assert_eq!(parse_search(input, "rg"), None);
+    }
+
+    #[test]
+    fn parse_install_list_when_empty() {
+        let got = parse_install_list("");
+        assert!(got.is_empty());
+    }
+}
@@ -1,17 +1,211 @@
-use std::{env, io, path::PathBuf, sync::Mutex, thread};
+use std::{
+    cell::{Cell, RefCell},
+    env,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use subprocess::{Exec, PopenError, Redirection};
+use subprocess::CaptureData;
 use thiserror::Error as ThisError;
+use which::which;
 
 use super::Status;
 
 lazy_static! {
+    static ref IS_ROOT: bool = is_root();
     static ref MUTEX: Mutex<()> = Mutex::new(());
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+// how long to wait for the rest of the output, once a command exits
+const OUTPUT_GRACE: Duration = Duration::from_millis(500);
+
+thread_local! {
+    // set when the job on this thread has a timeout,
+    // so that we can kill the process instead of leaving it running
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    // set when the job on this thread should only report what it would change
+    static CHECK: Cell<bool> = const { Cell::new(false) };
+    // set when the job on this thread should show how it changes the contents of files
+    static DIFF: Cell<bool> = const { Cell::new(false) };
+    // set when the job on this thread has `become`, e.g. ["sudo", "--"]
+    static BECOME: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    // set when the job on this thread has `register`, to collect stdout
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+    // set when the job on this thread redirects stdout and stderr
+    static OUTPUT: RefCell<Option<Sink>> = const { RefCell::new(None) };
+    // set when the job on this thread has `environment`
+    static ENVIRONMENT: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn start_capture() {
+    CAPTURED.with(|c| *c.borrow_mut() = Some(String::new()));
+}
+
+// returns everything written to stdout since `start_capture()`
+pub fn finish_capture() -> String {
+    CAPTURED.with(|c| c.borrow_mut().take()).unwrap_or_default()
+}
+
+// where commands write stdout and stderr
+#[derive(Clone)]
+enum Sink {
+    // to print all at once, when the job finishes
+    Buffer(Arc<Mutex<Vec<u8>>>),
+    // each line as soon as it arrives, after `prefix`, all on stderr if `stderr`,
+    // and also to `buffer`
+    Stream {
+        buffer: Arc<Mutex<Vec<u8>>>,
+        prefix: String,
+        stderr: bool,
+    },
+}
+
+pub fn start_output() {
+    OUTPUT.with(|o| *o.borrow_mut() = Some(Sink::Buffer(Arc::default())));
+}
+
+// instead of `start_output()`, e.g. with "[job name] " as `prefix`
+pub fn start_streaming(prefix: String, stderr: bool) {
+    OUTPUT.with(|o| {
+        *o.borrow_mut() = Some(Sink::Stream {
+            buffer: Arc::default(),
+            prefix,
+            stderr,
+        })
+    });
+}
+
+// returns everything written to stdout or stderr since `start_output()`,
+// or since `start_streaming()`, even though that was already printed
+pub fn finish_output() -> Vec<u8> {
+    match OUTPUT.with(|o| o.borrow_mut().take()) {
+        Some(Sink::Buffer(b)) | Some(Sink::Stream { buffer: b, .. }) => {
+            std::mem::take(&mut *b.lock().unwrap())
+        }
+        None => Vec::new(),
+    }
+}
+
+pub fn set_deadline(deadline: Option<Instant>) {
+    DEADLINE.with(|d| d.set(deadline));
+}
+
+pub fn is_past_deadline() -> bool {
+    DEADLINE
+        .with(|d| d.get())
+        .is_some_and(|d| Instant::now() >= d)
+}
+
+pub fn set_check(check: bool) {
+    CHECK.with(|c| c.set(check));
+}
+
+pub fn is_check() -> bool {
+    CHECK.with(|c| c.get())
+}
+
+pub fn set_diff(diff: bool) {
+    DIFF.with(|d| d.set(diff));
+}
+
+pub fn is_diff() -> bool {
+    DIFF.with(|d| d.get())
+}
+
+// alongside the output of any commands that the job on this thread runs, e.g. a diff
+pub fn write_output(text: &str) {
+    Output::new(false).write_all(text.as_bytes()).ok();
+}
+
+// finds a tool to run commands as another user (root by default)
+pub fn become_prefix(user: Option<&str>) -> std::result::Result<Vec<String>, Error> {
+    if user.is_none() && *IS_ROOT {
+        return Ok(Vec::new());
+    }
+    let tool = ["sudo", "doas"]
+        .iter()
+        .find(|t| which(t).is_ok())
+        .ok_or(Error::BecomeUnavailable)?;
+    let mut prefix = vec![String::from(*tool)];
+    if let Some(u) = user {
+        prefix.push(String::from("-u"));
+        prefix.push(String::from(u));
+    }
+    prefix.push(String::from("--"));
+    Ok(prefix)
+}
+
+pub fn set_become(prefix: Option<Vec<String>>) {
+    BECOME.with(|b| *b.borrow_mut() = prefix);
+}
+
+pub fn is_become() -> bool {
+    BECOME.with(|b| b.borrow().is_some())
+}
+
+// variables for commands run by the job on this thread, over the inherited environment
+pub fn set_environment(vars: Vec<(String, String)>) {
+    ENVIRONMENT.with(|e| *e.borrow_mut() = vars);
+}
+
+// like `set_environment()`, but over the variables already set, e.g. by a group
+pub fn extend_environment(vars: Vec<(String, String)>) {
+    ENVIRONMENT.with(|e| e.borrow_mut().extend(vars));
+}
+
+// what a job sets on this thread, i.e. `become`, check mode, and `environment`,
+// and puts back what was there before (e.g. for the rest of a group) when dropped
+pub struct Scope {
+    r#become: Option<Vec<String>>,
+    check: bool,
+    environment: Vec<(String, String)>,
+}
+impl Scope {
+    pub fn enter() -> Self {
+        Self {
+            r#become: BECOME.with(|b| b.borrow().clone()),
+            check: is_check(),
+            environment: environment(),
+        }
+    }
+}
+impl Drop for Scope {
+    fn drop(&mut self) {
+        set_become(self.r#become.take());
+        set_check(self.check);
+        set_environment(std::mem::take(&mut self.environment));
+    }
+}
+
+fn environment() -> Vec<(String, String)> {
+    ENVIRONMENT.with(|e| e.borrow().clone())
+}
+
+// prepends the `become` prefix, if any
+fn escalate<S>(cmd: &str, args: &[S]) -> (String, Vec<String>)
+where
+    S: AsRef<str>,
+{
+    let mut argv = BECOME.with(|b| b.borrow().clone()).unwrap_or_default();
+    let vars = environment();
+    if !argv.is_empty() && !vars.is_empty() {
+        // sudo and doas reset the environment, so `env` sets it again
+        argv.push(String::from("env"));
+        argv.extend(vars.iter().map(|(k, v)| format!("{}={}", k, v)));
+    }
+    argv.push(String::from(cmd));
+    argv.extend(args.iter().map(|a| String::from(a.as_ref())));
+    let cmd = argv.remove(0);
+    (cmd, argv)
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase", tag = "type")]
 pub struct Command {
     pub argv: Option<Vec<String>>,
@@ -20,65 +214,97 @@ pub struct Command {
     pub creates: Option<PathBuf>,
     pub removes: Option<PathBuf>,
 }
-impl Default for Command {
-    fn default() -> Self {
-        Command {
-            argv: None,
-            chdir: None,
-            command: String::new(),
-            creates: None,
-            removes: None,
-        }
-    }
-}
 impl Command {
     pub fn execute(&self) -> Result {
-        match &self.creates {
-            Some(p) => {
-                if p.exists() {
-                    return Ok(Status::NoChange(format!("{:?} already created", p)));
-                }
+        if let Some(p) = &self.creates {
+            if p.exists() {
+                return Ok(Status::NoChange(format!("{:?} already created", p)));
             }
-            None => {}
         }
-        match &self.removes {
-            Some(p) => {
-                if !p.exists() {
-                    return Ok(Status::NoChange(format!("{:?} already removed", p)));
-                }
+        if let Some(p) = &self.removes {
+            if !p.exists() {
+                return Ok(Status::NoChange(format!("{:?} already removed", p)));
             }
-            None => {}
         }
 
-        // we want exactly one "command" to use stdout at a time,
-        // at least until we decide how sharing stdout should work
-        let _lock = MUTEX.lock().unwrap();
+        if is_check() {
+            let mut argv = vec![self.command.clone()];
+            argv.extend(self.argv.clone().unwrap_or_default());
+            return Ok(Status::Changed(
+                String::from("not run"),
+                format!("run: {}", argv.join(" ")),
+            ));
+        }
+
+        // sudo and doas may prompt for a password on the terminal,
+        // which only works for one command at a time
+        let _lock = is_become().then(|| MUTEX.lock().unwrap());
 
-        let args = match &self.argv {
-            Some(a) => a.clone(),
-            None => Vec::<String>::new(),
-        };
+        let (cmd, args) = escalate(&self.command, &self.argv.clone().unwrap_or_default());
         let cwd = match &self.chdir {
             Some(c) => c.clone(),
             None => env::current_dir().unwrap(),
         };
-        let mut p = Exec::cmd(&self.command)
-            .args(&args)
-            .cwd(&cwd)
-            .stdout(Redirection::Pipe)
-            .stderr(Redirection::Pipe)
-            .popen()
-            .map_err(|e| Error::CommandBegin {
-                cmd: self.command.clone(),
-                source: e,
-            })?;
-        let (mut stderr, mut stdout) = (p.stderr.take().unwrap(), p.stdout.take().unwrap());
-        thread::spawn(move || io::copy(&mut stderr, &mut io::stderr()));
-        thread::spawn(move || io::copy(&mut stdout, &mut io::stdout()));
-        let status = p.wait().map_err(|e| Error::CommandWait {
+        let deadline = DEADLINE.with(|d| d.get());
+        let mut p = spawn(
+            process::Command::new(&cmd)
+                .args(&args)
+                .envs(environment())
+                .current_dir(&cwd),
+            deadline,
+        )
+        .map_err(|e| Error::CommandBegin {
             cmd: self.command.clone(),
             source: e,
         })?;
+        let (mut stderr, mut stdout) = (p.stderr.take().unwrap(), p.stdout.take().unwrap());
+        let (done_tx, done_rx) = mpsc::channel();
+        let stderr_done_tx = done_tx.clone();
+        let mut stderr_output = Output::new(true);
+        thread::spawn(move || {
+            io::copy(&mut stderr, &mut stderr_output).ok();
+            stderr_done_tx.send(()).ok();
+        });
+        let capturing = CAPTURED.with(|c| c.borrow().is_some());
+        let stdout_output = Output::new(false);
+        let stdout_handle = thread::spawn(move || {
+            let output = tee_stdout(&mut stdout, stdout_output, capturing);
+            done_tx.send(()).ok();
+            output
+        });
+        let wait_err = |e| Error::CommandWait {
+            cmd: self.command.clone(),
+            source: e,
+        };
+        let status = match wait_until(p, deadline).map_err(wait_err)? {
+            Some(s) => s,
+            None => {
+                return Err(Error::TimedOut {
+                    cmd: self.command.clone(),
+                })
+            }
+        };
+        // background processes may hold stdout or stderr open,
+        // so only wait a moment for the rest of the output
+        let grace = Instant::now() + OUTPUT_GRACE;
+        for _ in 0..2 {
+            if done_rx
+                .recv_timeout(grace.saturating_duration_since(Instant::now()))
+                .is_err()
+            {
+                break;
+            }
+        }
+        // only wait for all of stdout when we need it
+        if capturing {
+            if let Ok(output) = stdout_handle.join() {
+                CAPTURED.with(|c| {
+                    if let Some(captured) = c.borrow_mut().as_mut() {
+                        captured.push_str(&String::from_utf8_lossy(&output));
+                    }
+                });
+            }
+        }
         if status.success() {
             Ok(Status::Done)
         } else {
@@ -107,14 +333,314 @@ impl Command {
     }
 }
 
+// writes to the `Sink` from `start_output()` or `start_streaming()`, if any,
+// otherwise to our stdout or stderr
+struct Output {
+    // an incomplete line, when streaming
+    line: Vec<u8>,
+    sink: Option<Sink>,
+    stderr: bool,
+}
+impl Output {
+    fn new(stderr: bool) -> Self {
+        Self {
+            line: Vec::new(),
+            sink: OUTPUT.with(|o| o.borrow().clone()),
+            stderr,
+        }
+    }
+
+    fn write_line(&self, prefix: &str, line: &[u8], stderr: bool) {
+        let mut prefixed = prefix.as_bytes().to_vec();
+        prefixed.extend_from_slice(line);
+        // whole lines at once, so that concurrent jobs do not interleave within a line
+        if stderr {
+            io::stderr().lock().write_all(&prefixed).ok();
+        } else {
+            io::stdout().lock().write_all(&prefixed).ok();
+        }
+    }
+}
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.sink {
+            Some(Sink::Buffer(b)) => {
+                b.lock().unwrap().extend_from_slice(buf);
+            }
+            Some(Sink::Stream {
+                buffer,
+                prefix,
+                stderr,
+            }) => {
+                buffer.lock().unwrap().extend_from_slice(buf);
+                self.line.extend_from_slice(buf);
+                while let Some(i) = self.line.iter().position(|b| *b == b'\n') {
+                    let rest = self.line.split_off(i + 1);
+                    self.write_line(prefix, &self.line, *stderr || self.stderr);
+                    self.line = rest;
+                }
+            }
+            None if self.stderr => return io::stderr().write(buf),
+            None => return io::stdout().write(buf),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.sink {
+            Some(_) => Ok(()),
+            None if self.stderr => io::stderr().flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+impl Drop for Output {
+    fn drop(&mut self) {
+        // the last line may not end with a newline
+        if let Some(Sink::Stream { prefix, stderr, .. }) = &self.sink {
+            if !self.line.is_empty() {
+                let mut line = std::mem::take(&mut self.line);
+                line.push(b'\n');
+                self.write_line(prefix, &line, *stderr || self.stderr);
+            }
+        }
+    }
+}
+
+// copies to `output`, and also returns a copy if capturing
+fn tee_stdout<R>(stdout: &mut R, mut output: Output, capturing: bool) -> Vec<u8>
+where
+    R: Read,
+{
+    let mut captured = Vec::<u8>::new();
+    let mut chunk = [0u8; 8192];
+    while let Ok(n) = stdout.read(&mut chunk) {
+        if n == 0 {
+            break;
+        }
+        output.write_all(&chunk[..n]).ok();
+        if capturing {
+            captured.extend_from_slice(&chunk[..n]);
+        }
+    }
+    captured
+}
+
+// runs a helper command to completion, e.g. to query existing state
+pub fn capture<S>(cmd: &str, args: &[S]) -> std::result::Result<CaptureData, Error>
+where
+    S: AsRef<str>,
+{
+    capture_exec(helper_exec(cmd, args), cmd)
+}
+
+// runs a helper command to completion, treating non-zero exit as an error
+pub fn capture_success<S>(cmd: &str, args: &[S]) -> std::result::Result<CaptureData, Error>
+where
+    S: AsRef<str>,
+{
+    require_success(capture(cmd, args)?, cmd)
+}
+
+// like `capture_success()`, but within the given working directory
+pub fn capture_success_in<P, S>(
+    cwd: P,
+    cmd: &str,
+    args: &[S],
+) -> std::result::Result<CaptureData, Error>
+where
+    P: AsRef<Path>,
+    S: AsRef<str>,
+{
+    let mut exec = helper_exec(cmd, args);
+    exec.current_dir(cwd);
+    let data = capture_exec(exec, cmd)?;
+    require_success(data, cmd)
+}
+
+// like `capture()`, but as root, via `sudo` when necessary
+pub fn capture_as_root<S>(cmd: &str, args: &[S]) -> std::result::Result<CaptureData, Error>
+where
+    S: AsRef<str>,
+{
+    // `become` already runs everything via sudo or doas
+    if *IS_ROOT || cfg!(windows) || is_become() {
+        return capture(cmd, args);
+    }
+    let mut sudo_args = vec![String::from("--"), String::from(cmd)];
+    sudo_args.extend(args.iter().map(|a| String::from(a.as_ref())));
+    capture("sudo", &sudo_args)
+}
+
+// like `capture_success()`, but as root, via `sudo` when necessary
+pub fn capture_success_as_root<S>(cmd: &str, args: &[S]) -> std::result::Result<CaptureData, Error>
+where
+    S: AsRef<str>,
+{
+    require_success(capture_as_root(cmd, args)?, cmd)
+}
+
+// not via `capture()`, which would use `become`
+fn is_root() -> bool {
+    let mut exec = process::Command::new("id");
+    exec.arg("-u");
+    match capture_exec(exec, "id") {
+        Ok(data) => data.success() && data.stdout_str().trim() == "0",
+        Err(_) => false,
+    }
+}
+
+fn helper_exec<S>(cmd: &str, args: &[S]) -> process::Command
+where
+    S: AsRef<str>,
+{
+    let (cmd, args) = escalate(cmd, args);
+    let mut exec = process::Command::new(cmd);
+    exec.args(&args).envs(environment());
+    exec
+}
+
+// like `process::Command::output()`, but killing the process at the deadline (if any)
+fn capture_exec(mut exec: process::Command, cmd: &str) -> std::result::Result<CaptureData, Error> {
+    let deadline = DEADLINE.with(|d| d.get());
+    let mut p = spawn(&mut exec, deadline).map_err(|e| Error::CommandBegin {
+        cmd: String::from(cmd),
+        source: e,
+    })?;
+    let stdout = read_pipe(p.stdout.take());
+    let stderr = read_pipe(p.stderr.take());
+    let status = wait_until(p, deadline).map_err(|e| Error::CommandWait {
+        cmd: String::from(cmd),
+        source: e,
+    })?;
+    match status {
+        Some(s) => {
+            // background processes may hold the pipes open, as in `Command::execute()`
+            let grace = Instant::now() + OUTPUT_GRACE;
+            let read = |rx: mpsc::Receiver<Vec<u8>>| {
+                rx.recv_timeout(grace.saturating_duration_since(Instant::now()))
+                    .unwrap_or_default()
+            };
+            Ok(CaptureData {
+                stdout: read(stdout),
+                stderr: read(stderr),
+                exit_status: exit_status(s),
+            })
+        }
+        None => Err(Error::TimedOut {
+            cmd: String::from(cmd),
+        }),
+    }
+}
+
+// with a deadline, the process gets its own process group,
+// so that whatever it starts (e.g. via `sh -c`) can be killed along with it;
+// otherwise it stays in ours, so that sudo can prompt on the terminal, and Ctrl+C reaches it
+fn spawn(exec: &mut process::Command, deadline: Option<Instant>) -> io::Result<process::Child> {
+    exec.stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(not(windows))]
+    if deadline.is_some() {
+        use std::os::unix::process::CommandExt;
+
+        exec.process_group(0);
+    }
+    #[cfg(windows)]
+    let _ = deadline;
+    exec.spawn()
+}
+
+// None if the process was still running at the deadline, so it was killed,
+// along with everything that it started
+fn wait_until(
+    mut p: process::Child,
+    deadline: Option<Instant>,
+) -> io::Result<Option<process::ExitStatus>> {
+    let deadline = match deadline {
+        Some(d) => d,
+        None => return p.wait().map(Some),
+    };
+    let pid = p.id();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(p.wait()).ok());
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(status) => status.map(Some),
+        Err(_) => {
+            kill_tree(pid);
+            rx.recv().ok();
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn kill_tree(pid: u32) {
+    // a negative pid means the whole process group that `spawn()` made
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(windows)]
+fn kill_tree(pid: u32) {
+    process::Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .output()
+        .ok();
+}
+
+fn exit_status(status: process::ExitStatus) -> subprocess::ExitStatus {
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return subprocess::ExitStatus::Signaled(signal as u8);
+        }
+    }
+    match status.code() {
+        Some(code) => subprocess::ExitStatus::Exited(code as u32),
+        None => subprocess::ExitStatus::Undetermined,
+    }
+}
+
+// reads everything from the pipe on another thread, so both pipes drain at once
+fn read_pipe<R>(pipe: Option<R>) -> mpsc::Receiver<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut p) = pipe {
+            p.read_to_end(&mut buffer).ok();
+        }
+        tx.send(buffer).ok();
+    });
+    rx
+}
+
+fn require_success(data: CaptureData, cmd: &str) -> std::result::Result<CaptureData, Error> {
+    if data.success() {
+        Ok(data)
+    } else {
+        Err(Error::NonZeroExitStatus {
+            cmd: String::from(cmd),
+        })
+    }
+}
+
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error("become requires sudo or doas, but neither was found")]
+    BecomeUnavailable,
     #[error("`{}` could not begin: {}", cmd, source)]
-    CommandBegin { cmd: String, source: PopenError },
+    CommandBegin { cmd: String, source: io::Error },
     #[error("`{}` could not continue: {}", cmd, source)]
-    CommandWait { cmd: String, source: PopenError },
+    CommandWait { cmd: String, source: io::Error },
     #[error("`{}` exited with non-zero status code", cmd)]
     NonZeroExitStatus { cmd: String },
+    #[error("`{}` was killed after reaching the job timeout", cmd)]
+    TimedOut { cmd: String },
 }
 
 pub type Result = std::result::Result<Status, Error>;
@@ -149,6 +675,171 @@ mod tests {
         }
     }
 
+    #[cfg(not(windows))]
+    #[test]
+    fn captures_stdout() -> std::result::Result<(), Error> {
+        let cmd = Command {
+            argv: Some(vec![String::from("hello")]),
+            command: String::from("echo"),
+            ..Default::default()
+        };
+        start_capture();
+        cmd.execute()?;
+        assert_eq!(finish_capture(), "hello\n");
+        assert_eq!(finish_capture(), "");
+        Ok(())
+    }
+
+    #[test]
+    fn buffers_stdout_and_stderr() -> std::result::Result<(), Error> {
+        let cmd = Command {
+            argv: Some(vec![
+                String::from("-c"),
+                String::from("echo out; echo err >&2"),
+            ]),
+            command: String::from("sh"),
+            ..Default::default()
+        };
+        start_output();
+        cmd.execute()?;
+        let got = String::from_utf8(finish_output()).expect("UTF-8");
+        assert!(got.contains("out\n"));
+        assert!(got.contains("err\n"));
+        assert!(finish_output().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn escalate_with_become() {
+        assert_eq!(
+            escalate("ls", &["-l"]),
+            (String::from("ls"), vec![String::from("-l")])
+        );
+        set_become(Some(vec![
+            String::from("doas"),
+            String::from("-u"),
+            String::from("bob"),
+            String::from("--"),
+        ]));
+        let got = escalate("ls", &["-l"]);
+        set_become(None);
+        assert_eq!(got.0, "doas");
+        assert_eq!(got.1, vec!["-u", "bob", "--", "ls", "-l"]);
+    }
+
+    #[test]
+    fn scope_restores_what_was_set_before() {
+        set_become(Some(vec![String::from("sudo"), String::from("--")]));
+        set_environment(vec![(String::from("FOO"), String::from("bar"))]);
+        {
+            let _scope = Scope::enter();
+            set_become(None);
+            set_check(true);
+            extend_environment(vec![(String::from("BAZ"), String::from("qux"))]);
+            assert_eq!(environment().len(), 2);
+        }
+        assert!(is_become());
+        assert!(!is_check());
+        assert_eq!(
+            environment(),
+            vec![(String::from("FOO"), String::from("bar"))]
+        );
+        set_become(None);
+        set_environment(Vec::new());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn environment_is_set_for_command() -> std::result::Result<(), Error> {
+        let cmd = Command {
+            argv: Some(vec![String::from("-c"), String::from("echo $TUNING_TEST")]),
+            command: String::from("sh"),
+            ..Default::default()
+        };
+        set_environment(vec![(String::from("TUNING_TEST"), String::from("hello"))]);
+        start_capture();
+        let got = cmd.execute();
+        set_environment(Vec::new());
+        got?;
+        assert_eq!(finish_capture(), "hello\n");
+        Ok(())
+    }
+
+    #[test]
+    fn escalate_with_become_and_environment() {
+        set_become(Some(vec![String::from("sudo"), String::from("--")]));
+        set_environment(vec![(
+            String::from("DEBIAN_FRONTEND"),
+            String::from("noninteractive"),
+        )]);
+        let got = escalate("apt-get", &["update"]);
+        set_become(None);
+        set_environment(Vec::new());
+        assert_eq!(got.0, "sudo");
+        assert_eq!(
+            got.1,
+            vec![
+                "--",
+                "env",
+                "DEBIAN_FRONTEND=noninteractive",
+                "apt-get",
+                "update"
+            ]
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn killed_after_deadline() {
+        let cmd = Command {
+            argv: Some(vec![String::from("5")]),
+            command: String::from("sleep"),
+            ..Default::default()
+        };
+        set_deadline(Some(Instant::now() + std::time::Duration::from_millis(100)));
+        let got = cmd.execute();
+        set_deadline(None);
+        assert!(matches!(got, Err(Error::TimedOut { .. })));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn processes_started_by_command_killed_after_deadline() {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let marker = temp.join("marker");
+        let cmd = Command {
+            argv: Some(vec![
+                String::from("-c"),
+                format!("(sleep 0.5; touch {}) & wait", marker.display()),
+            ]),
+            command: String::from("sh"),
+            ..Default::default()
+        };
+        set_deadline(Some(Instant::now() + std::time::Duration::from_millis(100)));
+        let got = cmd.execute();
+        set_deadline(None);
+        assert!(matches!(got, Err(Error::TimedOut { .. })));
+
+        thread::sleep(std::time::Duration::from_secs(1));
+        assert!(!marker.exists());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn helper_killed_after_deadline() -> std::result::Result<(), Error> {
+        set_deadline(Some(Instant::now() + std::time::Duration::from_secs(5)));
+        let got = capture_success("echo", &["hello"])?;
+        assert_eq!(got.stdout_str(), "hello\n");
+
+        set_deadline(Some(Instant::now() + std::time::Duration::from_millis(100)));
+        let started = Instant::now();
+        let got = capture("sleep", &["5"]);
+        set_deadline(None);
+        assert!(matches!(got, Err(Error::TimedOut { .. })));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        Ok(())
+    }
+
     #[test]
     fn skips_when_creates_file_already_exists() {
         let cmd = Command {
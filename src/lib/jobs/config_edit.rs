@@ -0,0 +1,711 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+
+use super::{command, file, ini, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(
+        "{} has comments that would be lost by this change, please edit it by hand",
+        path.display()
+    )]
+    Comments { path: PathBuf },
+    #[error("unable to create {}: {}", path.display(), source)]
+    CreatePath { path: PathBuf, source: io::Error },
+    #[error("key must not be empty")]
+    EmptyKey,
+    #[error("INI key `{}` must be `section.key` or `section.subsection.key`", key)]
+    IniKey { key: String },
+    #[error("`{}` is not a table, so it cannot contain other keys", key)]
+    KeyConflict { key: String },
+    #[error(transparent)]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Toml {
+        #[from]
+        source: toml_edit::TomlError,
+    },
+    #[error(transparent)]
+    TomlSerialize {
+        #[from]
+        source: toml::ser::Error,
+    },
+    #[error("unable to detect format of {}, please set format", path.display())]
+    UnknownFormat { path: PathBuf },
+    #[error("value is required unless state = \"absent\"")]
+    ValueRequired,
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Yaml {
+        #[from]
+        source: serde_yaml::Error,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Ini,
+    Json,
+    Toml,
+    Yaml,
+}
+impl ConfigFormat {
+    fn detect(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "cfg" | "conf" | "ini" => Some(Self::Ini),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+// `key = "a.b.c"` splits on dots, whereas `key = ["editor.fontSize"]`
+// allows for keys that contain dots, as in VS Code's settings.json
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ConfigKey {
+    Dotted(String),
+    Path(Vec<String>),
+}
+impl Default for ConfigKey {
+    fn default() -> Self {
+        Self::Path(Vec::new())
+    }
+}
+impl ConfigKey {
+    fn segments(&self) -> Vec<String> {
+        match self {
+            Self::Dotted(s) => s.split('.').map(String::from).collect(),
+            Self::Path(p) => p.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigEditState {
+    Absent,
+    #[default]
+    Present,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct ConfigEdit {
+    pub format: Option<ConfigFormat>,
+    pub key: ConfigKey,
+    pub path: PathBuf,
+    pub state: Option<ConfigEditState>,
+    pub value: Option<toml::Value>,
+}
+impl ConfigEdit {
+    pub fn execute(&self) -> Result {
+        let format = self
+            .format
+            .or_else(|| ConfigFormat::detect(&self.path))
+            .ok_or_else(|| Error::UnknownFormat {
+                path: self.path.clone(),
+            })?;
+        let value = match self.state.unwrap_or_default() {
+            ConfigEditState::Absent => None,
+            ConfigEditState::Present => Some(self.value.as_ref().ok_or(Error::ValueRequired)?),
+        };
+        let keys = self.key.segments();
+        if keys.is_empty() || keys.iter().any(String::is_empty) {
+            return Err(Error::EmptyKey);
+        }
+
+        let text = match fs::read_to_string(&self.path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(Error::ReadPath {
+                    path: self.path.clone(),
+                    source: e,
+                })
+            }
+        };
+
+        let (output, previous) = match format {
+            ConfigFormat::Ini => edit_ini(&text, &keys, value)?,
+            ConfigFormat::Json => edit_json(&text, &keys, value)?,
+            ConfigFormat::Toml => edit_toml(&text, &keys, value)?,
+            ConfigFormat::Yaml => edit_yaml(&text, &keys, value)?,
+        };
+        let previous = previous.unwrap_or_else(|| String::from("absent"));
+        let current = match value {
+            Some(v) => display_value(v),
+            None => String::from("absent"),
+        };
+
+        if output == text {
+            return Ok(Status::NoChange(format!(
+                "{} = {}",
+                self.key_name(),
+                current
+            )));
+        }
+        let lossy = match format {
+            ConfigFormat::Json => strip_json_comments(&text).1,
+            ConfigFormat::Yaml => has_yaml_comments(&text),
+            ConfigFormat::Ini | ConfigFormat::Toml => false,
+        };
+        if lossy {
+            return Err(Error::Comments {
+                path: self.path.clone(),
+            });
+        }
+
+        file::show_diff(&self.path, text.as_bytes(), output.as_bytes());
+        let changed = Status::Changed(
+            format!("{} = {}", self.key_name(), previous),
+            format!("{} = {}", self.key_name(), current),
+        );
+        if command::is_check() {
+            return Ok(changed); // report what would change, without changing it
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::CreatePath {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+        fs::write(&self.path, output).map_err(|e| Error::WritePath {
+            path: self.path.clone(),
+            source: e,
+        })?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        match (self.state.unwrap_or_default(), &self.value) {
+            (ConfigEditState::Present, Some(v)) => format!(
+                "config_edit {}: {} = {}",
+                self.path.display(),
+                self.key_name(),
+                display_value(v)
+            ),
+            _ => format!(
+                "config_edit {}: remove {}",
+                self.path.display(),
+                self.key_name()
+            ),
+        }
+    }
+
+    fn key_name(&self) -> String {
+        match &self.key {
+            ConfigKey::Dotted(s) => s.clone(),
+            ConfigKey::Path(p) => p
+                .iter()
+                .map(|s| {
+                    if s.contains('.') {
+                        format!("{:?}", s)
+                    } else {
+                        s.clone()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("."),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// each edit_*() returns the new document and the previous value (if any),
+// and returns the input unchanged when there is nothing to do
+
+type Edit = std::result::Result<(String, Option<String>), Error>;
+
+fn display_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn edit_ini(text: &str, keys: &[String], value: Option<&toml::Value>) -> Edit {
+    let (section, subsection, key) = match keys {
+        [section, key] => (section, None, key),
+        [section, subsection, key] => (section, Some(subsection.as_str()), key),
+        _ => {
+            return Err(Error::IniKey {
+                key: keys.join("."),
+            })
+        }
+    };
+    let mut doc = ini::Document::parse(text, "");
+    let previous = doc.get(section, subsection, key);
+    match value {
+        Some(v) => {
+            let v = display_value(v);
+            if previous.as_ref() == Some(&v) {
+                return Ok((String::from(text), previous));
+            }
+            doc.set(section, subsection, key, &v);
+        }
+        None => {
+            if !doc.remove(section, subsection, key) {
+                return Ok((String::from(text), previous));
+            }
+        }
+    }
+    Ok((doc.to_string(), previous))
+}
+
+fn edit_json(text: &str, keys: &[String], value: Option<&toml::Value>) -> Edit {
+    use serde_json::{Map, Value};
+
+    let mut root: Value = if text.trim().is_empty() {
+        Value::Object(Map::new())
+    } else {
+        serde_json::from_str(&strip_json_comments(text).0)?
+    };
+    let (leaf, parents) = keys.split_last().ok_or(Error::EmptyKey)?;
+
+    let mut map = root
+        .as_object_mut()
+        .ok_or_else(|| Error::KeyConflict { key: String::new() })?;
+    for (i, segment) in parents.iter().enumerate() {
+        if value.is_none() && !map.contains_key(segment) {
+            return Ok((String::from(text), None));
+        }
+        map = map
+            .entry(segment.clone())
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| Error::KeyConflict {
+                key: keys[..=i].join("."),
+            })?;
+    }
+
+    let previous = map.get(leaf).cloned();
+    match value {
+        Some(v) => {
+            let v = serde_json::to_value(v)?;
+            if previous.as_ref() == Some(&v) {
+                return Ok((String::from(text), previous.map(|p| p.to_string())));
+            }
+            map.insert(leaf.clone(), v);
+        }
+        None => {
+            if previous.is_none() {
+                return Ok((String::from(text), None));
+            }
+            // `Map::remove()` would move the last key into this position
+            *map = std::mem::take(map)
+                .into_iter()
+                .filter(|(k, _)| k != leaf)
+                .collect();
+        }
+    }
+
+    let indent = detect_indent(text).unwrap_or_else(|| String::from("  "));
+    let mut output = Vec::<u8>::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    root.serialize(&mut serializer)?;
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    output.push('\n');
+    Ok((output, previous.map(|p| p.to_string())))
+}
+
+fn edit_toml(text: &str, keys: &[String], value: Option<&toml::Value>) -> Edit {
+    let mut doc = text.parse::<DocumentMut>()?;
+    let (leaf, parents) = keys.split_last().ok_or(Error::EmptyKey)?;
+
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for (i, segment) in parents.iter().enumerate() {
+        if !table.contains_key(segment) {
+            if value.is_none() {
+                return Ok((String::from(text), None));
+            }
+            let mut t = Table::new();
+            t.set_implicit(true);
+            table.insert(segment, Item::Table(t));
+        }
+        table = table
+            .get_mut(segment)
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| Error::KeyConflict {
+                key: keys[..=i].join("."),
+            })?;
+    }
+
+    let previous = table.get(leaf).map(|item| {
+        let mut item = item.clone();
+        if let Some(v) = item.as_value_mut() {
+            v.decor_mut().clear();
+        }
+        String::from(item.to_string().trim())
+    });
+    match value {
+        Some(v) => {
+            let mut v = to_toml_edit_value(v)?;
+            if let Some(Item::Value(existing)) = table.get(leaf) {
+                let mut bare = existing.clone();
+                bare.decor_mut().clear();
+                if bare.to_string() == v.to_string() {
+                    return Ok((String::from(text), previous));
+                }
+                // keep surrounding whitespace and comments
+                *v.decor_mut() = existing.decor().clone();
+            }
+            table.insert(leaf, Item::Value(v));
+        }
+        None => {
+            if table.remove(leaf).is_none() {
+                return Ok((String::from(text), None));
+            }
+        }
+    }
+    Ok((doc.to_string(), previous))
+}
+
+// serde_yaml does not retain comments, so execute() refuses to write
+// a change to a file that has any
+fn edit_yaml(text: &str, keys: &[String], value: Option<&toml::Value>) -> Edit {
+    use serde_yaml::{Mapping, Value};
+
+    let mut root: Value = if text.trim().is_empty() {
+        Value::Mapping(Mapping::new())
+    } else {
+        serde_yaml::from_str(text)?
+    };
+    let (leaf, parents) = keys.split_last().ok_or(Error::EmptyKey)?;
+
+    let mut map = match &mut root {
+        Value::Mapping(m) => m,
+        _ => return Err(Error::KeyConflict { key: String::new() }),
+    };
+    for (i, segment) in parents.iter().enumerate() {
+        let k = Value::String(segment.clone());
+        if !map.contains_key(&k) {
+            if value.is_none() {
+                return Ok((String::from(text), None));
+            }
+            map.insert(k.clone(), Value::Mapping(Mapping::new()));
+        }
+        map = match map.get_mut(&k) {
+            Some(Value::Mapping(m)) => m,
+            _ => {
+                return Err(Error::KeyConflict {
+                    key: keys[..=i].join("."),
+                })
+            }
+        };
+    }
+
+    let k = Value::String(leaf.clone());
+    let previous = map.get(&k).cloned();
+    let display = |v: &Value| serde_json::to_string(v).unwrap_or_default();
+    match value {
+        Some(v) => {
+            let v = serde_yaml::to_value(v)?;
+            if previous.as_ref() == Some(&v) {
+                return Ok((String::from(text), previous.as_ref().map(display)));
+            }
+            map.insert(k, v);
+        }
+        None => {
+            if map.remove(&k).is_none() {
+                return Ok((String::from(text), None));
+            }
+        }
+    }
+
+    let mut output = serde_yaml::to_string(&root)?;
+    if !text.trim_start().starts_with("---") {
+        output = String::from(output.trim_start_matches("---\n"));
+    }
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok((output, previous.as_ref().map(display)))
+}
+
+// removes comments and trailing commas, as allowed in JSONC files
+// such as VS Code's settings.json, and reports whether there were comments
+fn strip_json_comments(text: &str) -> (String, bool) {
+    let mut output = String::with_capacity(text.len());
+    let mut comments = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                output.push(c);
+                while let Some(c) = chars.next() {
+                    output.push(c);
+                    match c {
+                        '\\' => output.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                comments = true;
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                comments = true;
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '}' | ']' => {
+                let end = output.trim_end().len();
+                if output[..end].ends_with(',') {
+                    output.remove(end - 1);
+                }
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+    (output, comments)
+}
+
+// a `#` at the start of a line or after whitespace, outside of quotes
+fn has_yaml_comments(text: &str) -> bool {
+    text.lines().any(|line| {
+        let mut quote = None;
+        let mut previous = ' ';
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (None, '#') if previous.is_whitespace() => return true,
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (Some('"'), '\\') => {
+                    chars.next();
+                }
+                (Some(q), c) if q == c => quote = None,
+                _ => {}
+            }
+            previous = c;
+        }
+        false
+    })
+}
+
+// JSON files are indented with whatever the first indented line uses
+fn detect_indent(text: &str) -> Option<String> {
+    text.lines()
+        .map(|line| {
+            line.chars()
+                .take_while(|c| c.is_whitespace())
+                .collect::<String>()
+        })
+        .find(|indent| !indent.is_empty())
+}
+
+// wrap the value in a table so toml can serialize it, then take it back out
+fn to_toml_edit_value(value: &toml::Value) -> std::result::Result<toml_edit::Value, Error> {
+    let mut wrapper = toml::value::Table::new();
+    wrapper.insert(String::from("v"), value.clone());
+    let mut doc = toml::to_string(&wrapper)?.parse::<DocumentMut>()?;
+    let item = doc.remove("v").unwrap_or_default();
+    let mut v = item
+        .into_value()
+        .unwrap_or_else(|_| toml_edit::Value::from(""));
+    v.decor_mut().clear();
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(key: &str) -> Vec<String> {
+        ConfigKey::Dotted(String::from(key)).segments()
+    }
+
+    #[test]
+    fn name_with_key_path() {
+        let edit = ConfigEdit {
+            key: ConfigKey::Path(vec![String::from("editor.fontSize")]),
+            path: PathBuf::from("settings.json"),
+            value: Some(toml::Value::Integer(14)),
+            ..Default::default()
+        };
+        let got = edit.name();
+        let want = r#"config_edit settings.json: "editor.fontSize" = 14"#;
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn detect_format_from_extension() {
+        assert_eq!(
+            ConfigFormat::detect(&PathBuf::from("alacritty.yml")),
+            Some(ConfigFormat::Yaml)
+        );
+        assert_eq!(
+            ConfigFormat::detect(&PathBuf::from("Cargo.TOML")),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(ConfigFormat::detect(&PathBuf::from("config")), None);
+    }
+
+    #[test]
+    fn edit_json_preserves_order_and_indent() -> std::result::Result<(), Error> {
+        let input = "{\n    \"b\": 1,\n    \"editor.fontSize\": 12,\n    \"a\": true\n}\n";
+        let key = vec![String::from("editor.fontSize")];
+
+        let (got, previous) = edit_json(input, &key, Some(&toml::Value::Integer(14)))?;
+        assert_eq!(got, input.replace("12", "14"));
+        assert_eq!(previous, Some(String::from("12")));
+
+        let (got, _) = edit_json(&got, &key, None)?;
+        assert_eq!(got, "{\n    \"b\": 1,\n    \"a\": true\n}\n");
+
+        let (again, previous) = edit_json(&got, &key, None)?;
+        assert_eq!(again, got);
+        assert_eq!(previous, None);
+        Ok(())
+    }
+
+    #[test]
+    fn edit_json_creates_nested_objects() -> std::result::Result<(), Error> {
+        let (got, previous) = edit_json("", &keys("a.b"), Some(&toml::Value::from("c")))?;
+        assert_eq!(got, "{\n  \"a\": {\n    \"b\": \"c\"\n  }\n}\n");
+        assert_eq!(previous, None);
+        assert!(edit_json(&got, &keys("a.b.c"), Some(&toml::Value::from(1))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn edit_toml_preserves_comments() -> std::result::Result<(), Error> {
+        let input = "# settings\n[font]\nsize = 12 # points\nfamily = \"Iosevka\"\n";
+
+        let (got, previous) = edit_toml(input, &keys("font.size"), Some(&toml::Value::from(14)))?;
+        assert_eq!(got, input.replace("12", "14"));
+        assert_eq!(previous, Some(String::from("12")));
+
+        let (again, _) = edit_toml(&got, &keys("font.size"), Some(&toml::Value::from(14)))?;
+        assert_eq!(again, got);
+
+        let (got, _) = edit_toml(&got, &keys("font.family"), None)?;
+        assert_eq!(got, "# settings\n[font]\nsize = 14 # points\n");
+        Ok(())
+    }
+
+    #[test]
+    fn edit_toml_adds_new_tables() -> std::result::Result<(), Error> {
+        let (got, _) = edit_toml("", &keys("window.padding.x"), Some(&toml::Value::from(4)))?;
+        assert_eq!(got, "[window.padding]\nx = 4\n");
+        Ok(())
+    }
+
+    #[test]
+    fn edit_yaml_sets_and_removes() -> std::result::Result<(), Error> {
+        let input = "font:\n  size: 12\nshell: zsh\n";
+
+        let (got, previous) = edit_yaml(input, &keys("font.size"), Some(&toml::Value::from(14)))?;
+        assert_eq!(got, "font:\n  size: 14\nshell: zsh\n");
+        assert_eq!(previous, Some(String::from("12")));
+
+        let (got, _) = edit_yaml(&got, &keys("shell"), None)?;
+        assert_eq!(got, "font:\n  size: 14\n");
+
+        let (again, _) = edit_yaml(&got, &keys("missing.key"), None)?;
+        assert_eq!(again, got);
+        Ok(())
+    }
+
+    #[test]
+    fn edit_json_reads_jsonc() -> std::result::Result<(), Error> {
+        let input = "{\n  // font\n  \"editor.fontSize\": 12, /* px */\n  \"a\": \"//\",\n}\n";
+        let key = vec![String::from("editor.fontSize")];
+
+        let (got, previous) = edit_json(input, &key, Some(&toml::Value::Integer(12)))?;
+        assert_eq!(got, input);
+        assert_eq!(previous, Some(String::from("12")));
+        assert!(strip_json_comments(input).1);
+
+        let (got, _) = edit_json(
+            "{\n  \"a\": [1, 2,],\n}\n",
+            &keys("b"),
+            Some(&toml::Value::from(3)),
+        )?;
+        assert_eq!(got, "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": 3\n}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn execute_refuses_to_lose_comments() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let settings = temp.join("settings.json");
+        let jsonc = "{\n  // font\n  \"editor.fontSize\": 12,\n}\n";
+        fs::write(&settings, jsonc).expect("write");
+        let alacritty = temp.join("alacritty.yml");
+        let yaml = "# colours\nfont:\n  size: 12 # points\nshell: \"#!zsh\"\n";
+        fs::write(&alacritty, yaml).expect("write");
+
+        let edits = [
+            (
+                &settings,
+                ConfigKey::Path(vec![String::from("editor.fontSize")]),
+            ),
+            (&alacritty, ConfigKey::Dotted(String::from("font.size"))),
+        ];
+        for (path, key) in edits {
+            let mut edit = ConfigEdit {
+                key,
+                path: path.clone(),
+                value: Some(toml::Value::Integer(12)),
+                ..Default::default()
+            };
+            assert!(matches!(edit.execute()?, Status::NoChange(_)));
+
+            edit.value = Some(toml::Value::Integer(14));
+            assert!(matches!(edit.execute(), Err(Error::Comments { .. })));
+        }
+        assert_eq!(fs::read_to_string(&settings).expect("read"), jsonc);
+        assert_eq!(fs::read_to_string(&alacritty).expect("read"), yaml);
+        assert!(!has_yaml_comments(
+            "url: http://example.com/#top\nname: 'a # b'\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn edit_ini_sets_and_removes() -> std::result::Result<(), Error> {
+        let input = "[core]\neditor = vim\n";
+
+        let (got, previous) = edit_ini(
+            input,
+            &keys("core.editor"),
+            Some(&toml::Value::from("nvim")),
+        )?;
+        assert_eq!(got, "[core]\neditor = nvim\n");
+        assert_eq!(previous, Some(String::from("vim")));
+
+        let (got, _) = edit_ini(&got, &keys("core.editor"), None)?;
+        assert_eq!(got, "[core]\n");
+
+        assert!(edit_ini(&got, &keys("editor"), None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("neither docker nor podman found")]
+    EngineNotFound,
+    #[error("unable to parse output from `{}`: {}", cmd, source)]
+    ParseJson {
+        cmd: String,
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    Docker,
+    Podman,
+}
+impl ContainerEngine {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        }
+    }
+
+    fn detect() -> Option<Self> {
+        if which("docker").is_ok() {
+            Some(Self::Docker)
+        } else if which("podman").is_ok() {
+            Some(Self::Podman)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerImageState {
+    Absent,
+    #[default]
+    Present,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct ContainerImage {
+    pub digest: Option<String>,
+    pub engine: Option<ContainerEngine>,
+    pub image: String,
+    pub state: Option<ContainerImageState>,
+}
+impl ContainerImage {
+    pub fn execute(&self) -> Result {
+        let cmd = self
+            .engine
+            .or_else(ContainerEngine::detect)
+            .ok_or(Error::EngineNotFound)?
+            .command();
+        let reference = self.reference();
+        let previously = local_digests(cmd, &reference)?;
+
+        match self.state.unwrap_or_default() {
+            ContainerImageState::Absent => {
+                if previously.is_empty() {
+                    return Ok(Status::NoChange(String::from("absent")));
+                }
+                if !command::is_check() {
+                    command::capture_success(cmd, &["image", "rm", &reference])?;
+                }
+                Ok(Status::Changed(
+                    previously.join(", "),
+                    String::from("absent"),
+                ))
+            }
+            ContainerImageState::Present => {
+                if !previously.is_empty() && self.has_digest(&previously) {
+                    return Ok(Status::NoChange(previously.join(", ")));
+                }
+                let before = if previously.is_empty() {
+                    String::from("absent")
+                } else {
+                    previously.join(", ")
+                };
+                if command::is_check() {
+                    return Ok(Status::Changed(before, reference));
+                }
+                command::capture_success(cmd, &["pull", &reference])?;
+                let current = local_digests(cmd, &reference)?;
+                if current == previously {
+                    return Ok(Status::NoChange(current.join(", ")));
+                }
+                Ok(Status::Changed(before, current.join(", ")))
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let cmd = self
+            .engine
+            .or_else(ContainerEngine::detect)
+            .unwrap_or(ContainerEngine::Docker)
+            .command();
+        match self.state.unwrap_or_default() {
+            ContainerImageState::Absent => format!("{} image rm {}", cmd, self.reference()),
+            ContainerImageState::Present => format!("{} pull {}", cmd, self.reference()),
+        }
+    }
+
+    // without a pinned digest, any local copy of the image will do
+    fn has_digest(&self, digests: &[String]) -> bool {
+        match &self.digest {
+            Some(d) => digests.contains(d),
+            None => true,
+        }
+    }
+
+    fn reference(&self) -> String {
+        match &self.digest {
+            Some(d) => format!("{}@{}", self.image, d),
+            None => self.image.clone(),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// an image that is not present locally has no digests
+fn local_digests(cmd: &str, reference: &str) -> std::result::Result<Vec<String>, Error> {
+    let data = command::capture(
+        cmd,
+        &[
+            "image",
+            "inspect",
+            "--format",
+            "{{json .RepoDigests}}",
+            reference,
+        ],
+    )?;
+    if !data.success() {
+        return Ok(Vec::new());
+    }
+    parse_repo_digests(data.stdout_str()).map_err(|e| Error::ParseJson {
+        cmd: format!("{} image inspect {}", cmd, reference),
+        source: e,
+    })
+}
+
+// e.g. ["docker.io/library/alpine@sha256:abc..."] => ["sha256:abc..."]
+fn parse_repo_digests<S>(output: S) -> serde_json::Result<Vec<String>>
+where
+    S: AsRef<str>,
+{
+    let repo_digests: Option<Vec<String>> = serde_json::from_str(output.as_ref().trim())?;
+    let mut digests: Vec<String> = repo_digests
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|rd| rd.split('@').nth(1))
+        .map(String::from)
+        .collect();
+    digests.sort();
+    digests.dedup();
+    Ok(digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_digest() {
+        let image = ContainerImage {
+            digest: Some(String::from("sha256:abc")),
+            engine: Some(ContainerEngine::Podman),
+            image: String::from("alpine"),
+            ..Default::default()
+        };
+        let got = image.name();
+        let want = "podman pull alpine@sha256:abc";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_when_absent() {
+        let image = ContainerImage {
+            engine: Some(ContainerEngine::Docker),
+            image: String::from("alpine:3"),
+            state: Some(ContainerImageState::Absent),
+            ..Default::default()
+        };
+        let got = image.name();
+        let want = "docker image rm alpine:3";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_repo_digests_from_inspect() -> serde_json::Result<()> {
+        let input = r#"["docker.io/library/alpine@sha256:def","quay.io/alpine@sha256:abc","alpine@sha256:def"]
+"#;
+        let got = parse_repo_digests(input)?;
+        assert_eq!(
+            got,
+            vec![String::from("sha256:abc"), String::from("sha256:def")]
+        );
+        assert!(parse_repo_digests("null")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn has_digest_with_and_without_pin() {
+        let digests = vec![String::from("sha256:abc")];
+        let mut image = ContainerImage {
+            image: String::from("alpine"),
+            ..Default::default()
+        };
+        assert!(image.has_digest(&digests));
+        image.digest = Some(String::from("sha256:def"));
+        assert!(!image.has_digest(&digests));
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum DefaultsValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+impl Default for DefaultsValue {
+    fn default() -> Self {
+        Self::String(String::new())
+    }
+}
+impl fmt::Display for DefaultsValue {
+    // this matches the output of `defaults read`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{}", if *b { 1 } else { 0 }),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Float(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+impl DefaultsValue {
+    fn flag(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "-bool",
+            Self::Int(_) => "-int",
+            Self::Float(_) => "-float",
+            Self::String(_) => "-string",
+        }
+    }
+
+    fn write_arg(&self) -> String {
+        match self {
+            Self::Bool(b) => format!("{}", b),
+            _ => format!("{}", self),
+        }
+    }
+
+    fn is_equal_output<S>(&self, output: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        let o = output.as_ref().trim();
+        match self {
+            Self::Float(n) => o.parse::<f64>().map(|x| x == *n).unwrap_or(false),
+            _ => o == format!("{}", self),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Defaults {
+    pub current_host: Option<bool>,
+    pub domain: String,
+    pub key: String,
+    pub value: DefaultsValue,
+}
+impl Defaults {
+    pub fn execute(&self) -> Result {
+        let read = command::capture("defaults", &self.args("read"))?;
+        let previously = if read.success() {
+            let output = read.stdout_str();
+            if self.value.is_equal_output(&output) {
+                return Ok(Status::NoChange(format!(
+                    "{} {} = {}",
+                    self.domain, self.key, self.value
+                )));
+            }
+            String::from(output.trim())
+        } else {
+            String::from("absent")
+        };
+        let changed = Status::Changed(
+            previously,
+            format!("{} {} = {}", self.domain, self.key, self.value),
+        );
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        let mut args = self.args("write");
+        args.push(String::from(self.value.flag()));
+        args.push(self.value.write_arg());
+        command::capture_success("defaults", &args)?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = vec![String::from("defaults")];
+        parts.extend(self.args("write"));
+        parts.push(String::from(self.value.flag()));
+        parts.push(self.value.write_arg());
+        parts.join(" ")
+    }
+
+    fn args(&self, verb: &str) -> Vec<String> {
+        let mut args = Vec::<String>::new();
+        if self.current_host.unwrap_or(false) {
+            args.push(String::from("-currentHost"));
+        }
+        args.push(String::from(verb));
+        args.push(self.domain.clone());
+        args.push(self.key.clone());
+        args
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_bool() {
+        let defaults = Defaults {
+            domain: String::from("com.apple.dock"),
+            key: String::from("autohide"),
+            value: DefaultsValue::Bool(true),
+            ..Default::default()
+        };
+        let got = defaults.name();
+        let want = "defaults write com.apple.dock autohide -bool true";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_current_host_and_string() {
+        let defaults = Defaults {
+            current_host: Some(true),
+            domain: String::from("foo"),
+            key: String::from("bar"),
+            value: DefaultsValue::String(String::from("baz")),
+        };
+        let got = defaults.name();
+        let want = "defaults -currentHost write foo bar -string baz";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn value_is_equal_to_defaults_read_output() {
+        assert!(DefaultsValue::Bool(true).is_equal_output("1\n"));
+        assert!(!DefaultsValue::Bool(false).is_equal_output("1\n"));
+        assert!(DefaultsValue::Int(36).is_equal_output("36\n"));
+        assert!(DefaultsValue::Float(0.5).is_equal_output("0.5\n"));
+        assert!(DefaultsValue::Float(1.0).is_equal_output("1\n"));
+        assert!(DefaultsValue::String(String::from("left")).is_equal_output("left\n"));
+    }
+}
@@ -3,13 +3,20 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use colored::*;
+use mktemp::Temp;
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
-use super::Status;
+use super::{super::diff, command, Status};
 
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
     #[error("unable to link {}->{}: {}", src.display(), path.display(), source)]
     CreateLink {
         path: PathBuf,
@@ -31,7 +38,6 @@ pub enum Error {
     StateRequiresSrc { state: FileState },
     #[error("state={} is not yet implemented", format!("{:?}", state).to_lowercase())]
     StateNotImplemented { state: FileState },
-    #[allow(dead_code)] // TODO: test-only errors should not be here
     #[error(transparent)]
     TempPath { source: io::Error },
     #[error("unable to write {}: {}", path.display(), source)]
@@ -106,7 +112,7 @@ impl File {
 
 pub type Result = std::result::Result<Status, Error>;
 
-fn execute_absent<P>(path: P) -> Result
+pub fn execute_absent<P>(path: P) -> Result
 where
     P: AsRef<Path>,
 {
@@ -115,15 +121,21 @@ where
         return Ok(Status::NoChange(format!("{}", p.display())));
     }
 
-    (if p.is_dir() {
-        fs::remove_dir_all(&p)
+    if command::is_check() {
+        // report what would change, without changing it
+    } else if command::is_become() {
+        command::capture_success("rm", &["-r", "-f", "--", &p.to_string_lossy()])?;
     } else {
-        fs::remove_file(&p)
-    })
-    .map_err(|e| Error::RemovePath {
-        path: p.to_path_buf(),
-        source: e,
-    })?;
+        (if p.is_dir() {
+            fs::remove_dir_all(p)
+        } else {
+            fs::remove_file(p)
+        })
+        .map_err(|e| Error::RemovePath {
+            path: p.to_path_buf(),
+            source: e,
+        })?;
+    }
     Ok(Status::Changed(
         format!("{}", p.display()),
         String::from("absent"),
@@ -145,24 +157,24 @@ where
             });
         }
         previously = String::from("not directory");
-        execute_absent(&p)?;
+        execute_absent(p)?;
     } else {
         previously = String::from("absent");
     }
 
-    fs_create_dir_all(&p)?;
+    fs_create_dir_all(p)?;
     Ok(Status::Changed(
         previously,
         format!("directory: {}", p.display()),
     ))
 }
 
-fn execute_link<P>(src: P, dest: P, force: bool) -> Result
+pub fn execute_link<P>(src: P, dest: P, force: bool) -> Result
 where
     P: AsRef<Path>,
 {
     let s = src.as_ref();
-    if std::fs::symlink_metadata(&s).is_err() && !force {
+    if std::fs::symlink_metadata(s).is_err() && !force {
         return Err(Error::SrcNotFound {
             src: s.to_path_buf(),
         });
@@ -171,7 +183,7 @@ where
     let d = dest.as_ref();
     let mut previously = String::from("absent");
 
-    if let Ok(target) = std::fs::read_link(&d) {
+    if let Ok(target) = std::fs::read_link(d) {
         previously = format!("{} -> {}", target.display(), d.display());
         if s == target {
             return Ok(Status::NoChange(previously));
@@ -184,13 +196,13 @@ where
     };
     // dest does not exist, or is wrong symlink, or is not a symlink
 
-    match std::fs::symlink_metadata(&d) {
+    match std::fs::symlink_metadata(d) {
         Ok(attr) => {
             if !attr.file_type().is_symlink() {
                 previously = format!("existing: {}", &d.display());
             }
             if force {
-                execute_absent(&d)?;
+                execute_absent(d)?;
             } else {
                 return Err(Error::PathExists {
                     path: d.to_path_buf(),
@@ -199,16 +211,25 @@ where
         }
         Err(_) => {
             if let Some(parent) = d.parent() {
-                execute_directory(&parent, force)?;
+                execute_directory(parent, force)?;
             }
         }
     }
 
-    symbolic_link(&s, &d).map_err(|e| Error::CreateLink {
-        path: d.to_path_buf(),
-        src: s.to_path_buf(),
-        source: e,
-    })?;
+    if command::is_check() {
+        // report what would change, without changing it
+    } else if command::is_become() {
+        command::capture_success(
+            "ln",
+            &["-s", "--", &s.to_string_lossy(), &d.to_string_lossy()],
+        )?;
+    } else {
+        symbolic_link(&s, &d).map_err(|e| Error::CreateLink {
+            path: d.to_path_buf(),
+            src: s.to_path_buf(),
+            source: e,
+        })?;
+    }
 
     Ok(Status::Changed(
         previously,
@@ -226,9 +247,15 @@ where
         return Ok(Status::NoChange(format!("{}", p.display())));
     }
     if let Some(parent) = p.parent() {
-        execute_directory(&parent, false)?;
+        execute_directory(parent, false)?;
+    }
+    if command::is_check() {
+        // report what would change, without changing it
+    } else if command::is_become() {
+        command::capture_success("touch", &["--", &p.to_string_lossy()])?;
+    } else {
+        fs_write(p, "")?;
     }
-    fs_write(p, "")?;
     Ok(Status::Changed(
         String::from("absent"),
         format!("{}", p.display()),
@@ -239,6 +266,13 @@ fn fs_create_dir_all<P>(p: P) -> std::result::Result<(), Error>
 where
     P: AsRef<Path>,
 {
+    if command::is_check() {
+        return Ok(()); // report what would change, without changing it
+    }
+    if command::is_become() {
+        command::capture_success("mkdir", &["-p", "--", &p.as_ref().to_string_lossy()])?;
+        return Ok(());
+    }
     fs::create_dir_all(&p).map_err(|e| Error::CreatePath {
         path: p.as_ref().to_path_buf(),
         source: e,
@@ -256,6 +290,87 @@ where
     })
 }
 
+// the closest existing directory to `path`, if files cannot be created there,
+// which is checked by creating (and removing) one
+pub fn unwritable_dir<P>(path: P) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let dir = path.as_ref().ancestors().skip(1).find(|a| a.is_dir())?;
+    match Temp::new_file_in(dir) {
+        Ok(_) => None,
+        Err(_) => Some(dir.to_path_buf()),
+    }
+}
+
+// with `--diff`, shows how writing `after` over `before` changes `path`
+pub fn show_diff<P>(path: P, before: &[u8], after: &[u8])
+where
+    P: AsRef<Path>,
+{
+    if !command::is_diff() {
+        return;
+    }
+    let text = diff::unified(
+        path.as_ref(),
+        &String::from_utf8_lossy(before),
+        &String::from_utf8_lossy(after),
+    );
+    let text: String = text
+        .lines()
+        .map(|line| {
+            let line = match line.chars().next() {
+                Some('@') => line.cyan(),
+                Some('+') if !line.starts_with("+++") => line.green(),
+                Some('-') if !line.starts_with("---") => line.red(),
+                _ => line.normal(),
+            };
+            format!("{}\n", line)
+        })
+        .collect();
+    command::write_output(&text);
+}
+
+// writes to a path that may only be writable by root, via `sudo` if necessary
+pub fn write_as_root<P, C>(path: P, contents: C) -> std::result::Result<(), Error>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let p = path.as_ref();
+    // unreadable files are shown as empty
+    show_diff(p, &fs::read(p).unwrap_or_default(), contents.as_ref());
+    if command::is_check() {
+        return Ok(());
+    }
+    match fs::write(p, &contents) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if let Some(parent) = p.parent() {
+                if fs::create_dir_all(parent).is_ok() {
+                    return fs_write(p, contents);
+                }
+                let parent = parent.to_string_lossy();
+                command::capture_success_as_root("mkdir", &["-p", &parent])?;
+            }
+        }
+        result => {
+            return result.map_err(|e| Error::WritePath {
+                path: p.to_path_buf(),
+                source: e,
+            })
+        }
+    }
+
+    let temp = Temp::new_file().map_err(|e| Error::TempPath { source: e })?;
+    fs_write(&temp, contents)?;
+    command::capture_success_as_root(
+        "install",
+        &["-m", "0644", &temp.to_string_lossy(), &p.to_string_lossy()],
+    )?;
+    Ok(())
+}
+
 #[cfg(not(windows))]
 fn symbolic_link<P>(src: P, dest: P) -> io::Result<()>
 where
@@ -279,8 +394,6 @@ where
 
 #[cfg(test)]
 mod tests {
-    use mktemp::Temp;
-
     use super::*;
 
     #[test]
@@ -291,7 +404,7 @@ mod tests {
             ..Default::default()
         };
 
-        fs_create_dir_all(&file.path.parent().unwrap())?;
+        fs_create_dir_all(file.path.parent().unwrap())?;
         fs_write(&file.path, "")?;
         let got = file.execute()?;
 
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("neither ufw nor firewall-cmd found")]
+    BackendNotFound,
+    #[error(
+        "rule `{}` is not supported by firewalld, expected e.g. `allow 22/tcp` or `allow ssh`",
+        rule
+    )]
+    UnsupportedRule { rule: String },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallBackend {
+    Firewalld,
+    Ufw,
+}
+impl FirewallBackend {
+    fn detect() -> Option<Self> {
+        if which("ufw").is_ok() {
+            Some(Self::Ufw)
+        } else if which("firewall-cmd").is_ok() {
+            Some(Self::Firewalld)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Firewall {
+    pub backend: Option<FirewallBackend>,
+    pub rule: String,
+}
+impl Firewall {
+    pub fn execute(&self) -> Result {
+        let backend = self
+            .backend
+            .or_else(FirewallBackend::detect)
+            .ok_or(Error::BackendNotFound)?;
+        let rule = normalize(&self.rule);
+
+        match backend {
+            FirewallBackend::Ufw => {
+                let added = command::capture_success_as_root("ufw", &["show", "added"])?;
+                if parse_ufw_added(added.stdout_str()).contains(&rule) {
+                    return Ok(Status::NoChange(rule));
+                }
+                if command::is_check() {
+                    return Ok(Status::Changed(String::from("absent"), rule));
+                }
+                let args: Vec<&str> = rule.split_whitespace().collect();
+                command::capture_success_as_root("ufw", &args)?;
+            }
+            FirewallBackend::Firewalld => {
+                let (query, add) = firewalld_args(&rule)?;
+                let data = command::capture_as_root("firewall-cmd", &["--permanent", &query])?;
+                if data.success() {
+                    return Ok(Status::NoChange(rule));
+                }
+                if command::is_check() {
+                    return Ok(Status::Changed(String::from("absent"), rule));
+                }
+                command::capture_success_as_root("firewall-cmd", &["--permanent", &add])?;
+                command::capture_success_as_root("firewall-cmd", &["--reload"])?;
+            }
+        }
+
+        Ok(Status::Changed(String::from("absent"), rule))
+    }
+
+    pub fn name(&self) -> String {
+        let rule = normalize(&self.rule);
+        match self.backend.or_else(FirewallBackend::detect) {
+            Some(FirewallBackend::Firewalld) => match firewalld_args(&rule) {
+                Ok((_, add)) => format!("firewall-cmd --permanent {}", add),
+                Err(_) => format!("firewall-cmd {}", rule),
+            },
+            _ => format!("ufw {}", rule),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// translates simple ufw-style rules into firewalld arguments,
+// e.g. "allow 22/tcp" => ("--query-port=22/tcp", "--add-port=22/tcp")
+fn firewalld_args<S>(rule: S) -> std::result::Result<(String, String), Error>
+where
+    S: AsRef<str>,
+{
+    let unsupported = || Error::UnsupportedRule {
+        rule: String::from(rule.as_ref()),
+    };
+    let words: Vec<&str> = rule.as_ref().split_whitespace().collect();
+    if words.len() != 2 {
+        return Err(unsupported());
+    }
+    let kind = if words[1].contains('/') {
+        "port"
+    } else {
+        "service"
+    };
+    match words[0] {
+        "allow" => Ok((
+            format!("--query-{}={}", kind, words[1]),
+            format!("--add-{}={}", kind, words[1]),
+        )),
+        _ => Err(unsupported()),
+    }
+}
+
+fn normalize<S>(rule: S) -> String
+where
+    S: AsRef<str>,
+{
+    rule.as_ref()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// `ufw show added` prints e.g. "ufw allow 22/tcp"
+fn parse_ufw_added<S>(output: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("ufw "))
+        .map(normalize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_ufw() {
+        let firewall = Firewall {
+            backend: Some(FirewallBackend::Ufw),
+            rule: String::from("allow  22/tcp"),
+        };
+        let got = firewall.name();
+        let want = "ufw allow 22/tcp";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_firewalld() {
+        let firewall = Firewall {
+            backend: Some(FirewallBackend::Firewalld),
+            rule: String::from("allow ssh"),
+        };
+        let got = firewall.name();
+        let want = "firewall-cmd --permanent --add-service=ssh";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn firewalld_args_for_ports_and_services() -> std::result::Result<(), Error> {
+        assert_eq!(
+            firewalld_args("allow 22/tcp")?,
+            (
+                String::from("--query-port=22/tcp"),
+                String::from("--add-port=22/tcp")
+            )
+        );
+        assert_eq!(
+            firewalld_args("allow ssh")?,
+            (
+                String::from("--query-service=ssh"),
+                String::from("--add-service=ssh")
+            )
+        );
+        assert!(firewalld_args("deny from 10.0.0.1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_ufw_added_rules() {
+        let input = "Added user rules (see 'ufw status' for running firewall):\nufw allow 22/tcp\nufw limit ssh\n";
+        let got = parse_ufw_added(input);
+        assert_eq!(
+            got,
+            vec![String::from("allow 22/tcp"), String::from("limit ssh")]
+        );
+    }
+}
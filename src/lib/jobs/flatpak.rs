@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("flatpak requires app or url")]
+    NothingToDo,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlatpakScope {
+    System,
+    #[default]
+    User,
+}
+impl FlatpakScope {
+    fn flag(self) -> &'static str {
+        match self {
+            Self::System => "--system",
+            Self::User => "--user",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Flatpak {
+    pub app: Option<String>,
+    #[serde(default = "default_remote_value")]
+    pub remote: String,
+    pub scope: Option<FlatpakScope>,
+    pub url: Option<String>,
+}
+impl Default for Flatpak {
+    fn default() -> Self {
+        Self {
+            app: None,
+            remote: default_remote_value(),
+            scope: None,
+            url: None,
+        }
+    }
+}
+impl Flatpak {
+    pub fn execute(&self) -> Result {
+        if self.app.is_none() && self.url.is_none() {
+            return Err(Error::NothingToDo);
+        }
+        let scope = self.scope.unwrap_or_default().flag();
+        let mut previously = Vec::<String>::new();
+        let mut changed = Vec::<String>::new();
+
+        if let Some(url) = &self.url {
+            let remotes = list(&["remotes", scope, "--columns=name"])?;
+            if remotes.contains(&self.remote) {
+                previously.push(format!("remote: {}", self.remote));
+            } else {
+                if !command::is_check() {
+                    command::capture_success(
+                        "flatpak",
+                        &["remote-add", scope, "--if-not-exists", &self.remote, url],
+                    )?;
+                }
+                changed.push(format!("remote: {}", self.remote));
+            }
+        }
+
+        if let Some(app) = &self.app {
+            let apps = list(&["list", scope, "--app", "--columns=application"])?;
+            if apps.contains(app) {
+                previously.push(format!("app: {}", app));
+            } else {
+                if !command::is_check() {
+                    command::capture_success(
+                        "flatpak",
+                        &["install", scope, "--noninteractive", &self.remote, app],
+                    )?;
+                }
+                changed.push(format!("app: {}", app));
+            }
+        }
+
+        if changed.is_empty() {
+            Ok(Status::NoChange(previously.join(", ")))
+        } else {
+            Ok(Status::Changed(String::from("absent"), changed.join(", ")))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let scope = self.scope.unwrap_or_default().flag();
+        let mut parts = Vec::<String>::new();
+        if let Some(url) = &self.url {
+            parts.push(format!(
+                "flatpak remote-add {} --if-not-exists {} {}",
+                scope, self.remote, url
+            ));
+        }
+        if let Some(app) = &self.app {
+            parts.push(format!("flatpak install {} {} {}", scope, self.remote, app));
+        }
+        parts.join(" && ")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn default_remote_value() -> String {
+    String::from("flathub")
+}
+
+fn list(args: &[&str]) -> std::result::Result<Vec<String>, Error> {
+    let output = command::capture_success("flatpak", args)?.stdout_str();
+    Ok(parse_list(output))
+}
+
+fn parse_list<S>(output: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .map(|line| String::from(line.trim()))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_app() {
+        let flatpak = Flatpak {
+            app: Some(String::from("org.gimp.GIMP")),
+            ..Default::default()
+        };
+        let got = flatpak.name();
+        let want = "flatpak install --user flathub org.gimp.GIMP";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_app_and_remote() {
+        let flatpak = Flatpak {
+            app: Some(String::from("org.gimp.GIMP")),
+            scope: Some(FlatpakScope::System),
+            url: Some(String::from("https://flathub.org/repo/flathub.flatpakrepo")),
+            ..Default::default()
+        };
+        let got = flatpak.name();
+        let want = "flatpak remote-add --system --if-not-exists flathub https://flathub.org/repo/flathub.flatpakrepo && flatpak install --system flathub org.gimp.GIMP";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_list_skips_blank_lines() {
+        let got = parse_list("org.gimp.GIMP\n\norg.videolan.VLC\n");
+        assert_eq!(
+            got,
+            vec![
+                String::from("org.gimp.GIMP"),
+                String::from("org.videolan.VLC")
+            ]
+        );
+    }
+
+    #[test]
+    fn execute_errs_without_app_or_url() {
+        let flatpak = Flatpak::default();
+        assert!(flatpak.execute().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, ini::Document, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to find home_dir")]
+    HomeDir,
+    #[error("`{}` is not a valid key, expected section.key", key)]
+    InvalidKey { key: String },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Gitconfig {
+    pub path: Option<PathBuf>,
+    pub settings: BTreeMap<String, String>,
+}
+impl Gitconfig {
+    pub fn execute(&self) -> Result {
+        let path = self.path()?;
+        let text = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::ReadPath { path, source: e }),
+        };
+        let mut doc = Document::parse(&text, "\t");
+
+        let mut previously = Vec::<String>::new();
+        let mut changed = Vec::<String>::new();
+        for (key, value) in &self.settings {
+            let (section, subsection, name) = split_key(key)?;
+            let current = doc.get(section, subsection, name);
+            if current.as_ref() == Some(value) {
+                continue;
+            }
+            previously.push(format!(
+                "{}={}",
+                key,
+                current.unwrap_or_else(|| String::from("absent"))
+            ));
+            changed.push(format!("{}={}", key, value));
+            doc.set(section, subsection, name, value);
+        }
+
+        if changed.is_empty() {
+            return Ok(Status::NoChange(format!(
+                "{}: {}",
+                path.display(),
+                self.keys()
+            )));
+        }
+
+        let updated = doc.to_string();
+        file::show_diff(&path, text.as_bytes(), updated.as_bytes());
+        if !command::is_check() {
+            fs::write(&path, updated).map_err(|e| Error::WritePath {
+                path: path.clone(),
+                source: e,
+            })?;
+        }
+        Ok(Status::Changed(previously.join(", "), changed.join(", ")))
+    }
+
+    pub fn name(&self) -> String {
+        self.settings
+            .iter()
+            .map(|(k, v)| format!("git config --global {} {:?}", k, v))
+            .collect::<Vec<String>>()
+            .join(" && ")
+    }
+
+    fn keys(&self) -> String {
+        self.settings
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    pub fn targets(&self) -> Vec<PathBuf> {
+        self.path().into_iter().collect()
+    }
+
+    fn path(&self) -> std::result::Result<PathBuf, Error> {
+        match &self.path {
+            Some(p) => Ok(p.clone()),
+            None => Ok(dirs::home_dir().ok_or(Error::HomeDir)?.join(".gitconfig")),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// same rules as `git config`: the first dot ends the section name,
+// the last dot begins the key name, and anything between is a subsection
+fn split_key(key: &str) -> std::result::Result<(&str, Option<&str>, &str), Error> {
+    let invalid = || Error::InvalidKey {
+        key: String::from(key),
+    };
+    let first = key.find('.').ok_or_else(invalid)?;
+    let last = key.rfind('.').ok_or_else(invalid)?;
+    let (section, name) = (&key[..first], &key[last + 1..]);
+    if section.is_empty() || name.is_empty() {
+        return Err(invalid());
+    }
+    if first == last {
+        Ok((section, None, name))
+    } else {
+        Ok((section, Some(&key[first + 1..last]), name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn name_with_settings() {
+        let mut settings = BTreeMap::<String, String>::new();
+        settings.insert(String::from("alias.co"), String::from("checkout"));
+        settings.insert(String::from("user.email"), String::from("me@example.com"));
+        let gitconfig = Gitconfig {
+            settings,
+            ..Default::default()
+        };
+        let got = gitconfig.name();
+        let want = r#"git config --global alias.co "checkout" && git config --global user.email "me@example.com""#;
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn split_key_with_and_without_subsection() -> std::result::Result<(), Error> {
+        assert_eq!(split_key("user.email")?, ("user", None, "email"));
+        assert_eq!(
+            split_key("includeIf.gitdir:~/work/.path")?,
+            ("includeIf", Some("gitdir:~/work/"), "path")
+        );
+        assert!(split_key("nodot").is_err());
+        assert!(split_key("user.").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn execute_sets_only_changed_keys() -> std::result::Result<(), Error> {
+        let temp = Temp::new_file().expect("temp file");
+        let path = temp.to_path_buf();
+        fs::write(&path, "[user]\n\temail = me@example.com\n").expect("write");
+
+        let mut settings = BTreeMap::<String, String>::new();
+        settings.insert(String::from("user.email"), String::from("me@example.com"));
+        settings.insert(String::from("user.name"), String::from("Me"));
+        let gitconfig = Gitconfig {
+            path: Some(path.clone()),
+            settings,
+        };
+
+        let got = gitconfig.execute()?;
+        assert_eq!(
+            got,
+            Status::Changed(
+                String::from("user.name=absent"),
+                String::from("user.name=Me")
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "[user]\n\temail = me@example.com\n\tname = Me\n"
+        );
+
+        let got = gitconfig.execute()?;
+        assert_eq!(
+            got,
+            Status::NoChange(format!("{}: user.email, user.name", path.display()))
+        );
+        Ok(())
+    }
+}
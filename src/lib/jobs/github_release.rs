@@ -0,0 +1,421 @@
+use std::{
+    env::consts::{ARCH, OS},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use mktemp::Temp;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.bz2", ".tar.gz", ".tar.xz", ".tgz", ".zip"];
+const IGNORED_EXTENSIONS: &[&str] = &[
+    ".asc", ".deb", ".json", ".md5", ".msi", ".pem", ".pkg", ".rpm", ".sbom", ".sha256", ".sha512",
+    ".sig", ".txt",
+];
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("no release asset in {} {} matches {} {}", repo, tag, OS, ARCH)]
+    AssetNotFound { repo: String, tag: String },
+    #[error("{} not found in {}", binary, asset)]
+    BinaryNotFound { asset: String, binary: String },
+    #[error("unable to find cache_dir")]
+    CacheDir,
+    #[error("unable to copy {} to {}: {}", src.display(), dest.display(), source)]
+    CopyPath {
+        dest: PathBuf,
+        source: io::Error,
+        src: PathBuf,
+    },
+    #[error("unable to create {}: {}", path.display(), source)]
+    CreatePath { path: PathBuf, source: io::Error },
+    #[error("unable to find home_dir")]
+    HomeDir,
+    #[error(transparent)]
+    InvalidAsset {
+        #[from]
+        source: regex::Error,
+    },
+    #[error("unable to parse output from `{}`: {}", cmd, source)]
+    ParseJson {
+        cmd: String,
+        source: serde_json::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to set permissions on {}: {}", path.display(), source)]
+    SetPermissions { path: PathBuf, source: io::Error },
+    #[error("unable to create temporary directory: {}", source)]
+    TempPath { source: io::Error },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct GithubRelease {
+    pub asset: Option<String>,
+    pub binary: Option<String>,
+    pub dest: Option<PathBuf>,
+    pub repo: String,
+    pub version: Option<String>,
+}
+impl GithubRelease {
+    pub fn execute(&self) -> Result {
+        let binary = self.binary();
+        let dest = self.dest()?.join(executable_name(&binary));
+        let version_path = version_path(&binary)?;
+        let previously = if dest.is_file() {
+            fs::read_to_string(&version_path)
+                .map(|s| String::from(s.trim()))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        // pinned versions need no network access to detect no change
+        if let Some(v) = &self.version {
+            if &previously == v {
+                return Ok(Status::NoChange(format!("{} {}", binary, v)));
+            }
+        }
+
+        let release = self.fetch_release()?;
+        if previously == release.tag_name {
+            return Ok(Status::NoChange(format!("{} {}", binary, previously)));
+        }
+        let changed = Status::Changed(
+            format!(
+                "{} {}",
+                binary,
+                if previously.is_empty() {
+                    "absent"
+                } else {
+                    &previously
+                }
+            ),
+            format!("{} {}", binary, release.tag_name),
+        );
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        let pattern = match &self.asset {
+            Some(a) => Some(Regex::new(a)?),
+            None => None,
+        };
+        let asset = select_asset(&release.assets, OS, ARCH, pattern.as_ref()).ok_or_else(|| {
+            Error::AssetNotFound {
+                repo: self.repo.clone(),
+                tag: release.tag_name.clone(),
+            }
+        })?;
+
+        let temp = Temp::new_dir().map_err(|e| Error::TempPath { source: e })?;
+        let download = temp.join(&asset.name);
+        command::capture_success(
+            "curl",
+            &[
+                "-fsSL",
+                "-o",
+                &download.to_string_lossy(),
+                &asset.browser_download_url,
+            ],
+        )?;
+        let src = if is_archive(&asset.name) {
+            let extracted = temp.join("extracted");
+            create_dir_all(&extracted)?;
+            extract(&download, &extracted)?;
+            find_file(&extracted, &executable_name(&binary))?.ok_or_else(|| {
+                Error::BinaryNotFound {
+                    asset: asset.name.clone(),
+                    binary: binary.clone(),
+                }
+            })?
+        } else {
+            download
+        };
+
+        if let Some(parent) = dest.parent() {
+            create_dir_all(parent)?;
+        }
+        fs::copy(&src, &dest).map_err(|e| Error::CopyPath {
+            dest: dest.clone(),
+            source: e,
+            src,
+        })?;
+        set_executable(&dest)?;
+        if let Some(parent) = version_path.parent() {
+            create_dir_all(parent)?;
+        }
+        fs::write(&version_path, &release.tag_name).map_err(|e| Error::WritePath {
+            path: version_path.clone(),
+            source: e,
+        })?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "github_release {}@{} -> {}",
+            self.repo,
+            self.version.as_deref().unwrap_or("latest"),
+            self.binary()
+        )
+    }
+
+    // defaults to the repository name, e.g. "BurntSushi/ripgrep" => "ripgrep"
+    fn binary(&self) -> String {
+        self.binary
+            .clone()
+            .unwrap_or_else(|| String::from(self.repo.rsplit('/').next().unwrap_or_default()))
+    }
+
+    fn dest(&self) -> std::result::Result<PathBuf, Error> {
+        match &self.dest {
+            Some(d) => Ok(d.clone()),
+            None => Ok(dirs::home_dir()
+                .ok_or(Error::HomeDir)?
+                .join(".local")
+                .join("bin")),
+        }
+    }
+
+    fn fetch_release(&self) -> std::result::Result<Release, Error> {
+        let url = match &self.version {
+            Some(v) => format!(
+                "https://api.github.com/repos/{}/releases/tags/{}",
+                self.repo, v
+            ),
+            None => format!("https://api.github.com/repos/{}/releases/latest", self.repo),
+        };
+        let output = command::capture_success("curl", &["-fsSL", &url])?.stdout_str();
+        serde_json::from_str(&output).map_err(|e| Error::ParseJson {
+            cmd: format!("curl {}", url),
+            source: e,
+        })
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    browser_download_url: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    assets: Vec<Asset>,
+    tag_name: String,
+}
+
+fn arch_keywords(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "aarch64" => &["aarch64", "arm64"],
+        "x86" => &["i386", "i686", "x86"],
+        "x86_64" => &["amd64", "x64", "x86_64"],
+        _ => &[],
+    }
+}
+
+fn create_dir_all(path: &Path) -> std::result::Result<(), Error> {
+    fs::create_dir_all(path).map_err(|e| Error::CreatePath {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn executable_name(binary: &str) -> String {
+    if OS == "windows" {
+        format!("{}.exe", binary)
+    } else {
+        String::from(binary)
+    }
+}
+
+// bsdtar (macOS and Windows 10+) also handles .zip files
+fn extract(archive: &Path, dir: &Path) -> std::result::Result<(), Error> {
+    let a = archive.to_string_lossy();
+    let d = dir.to_string_lossy();
+    if a.ends_with(".zip") && OS == "linux" {
+        command::capture_success("unzip", &["-q", &a, "-d", &d])?;
+    } else {
+        command::capture_success("tar", &["-xf", &a, "-C", &d])?;
+    }
+    Ok(())
+}
+
+fn find_file(dir: &Path, name: &str) -> std::result::Result<Option<PathBuf>, Error> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::ReadPath {
+        path: dir.to_path_buf(),
+        source: e,
+    })?;
+    let mut subdirs = Vec::<PathBuf>::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            subdirs.push(path);
+        } else if entry.file_name().to_string_lossy() == name {
+            return Ok(Some(path));
+        }
+    }
+    for subdir in subdirs {
+        if let Some(found) = find_file(&subdir, name)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+fn is_archive(name: &str) -> bool {
+    let n = name.to_lowercase();
+    ARCHIVE_EXTENSIONS.iter().any(|ext| n.ends_with(ext))
+}
+
+fn os_keywords(os: &str) -> &'static [&'static str] {
+    match os {
+        "linux" => &["linux"],
+        "macos" => &["apple", "darwin", "macos", "osx"],
+        "windows" => &["win32", "win64", "windows"],
+        _ => &[],
+    }
+}
+
+fn select_asset<'a>(
+    assets: &'a [Asset],
+    os: &str,
+    arch: &str,
+    pattern: Option<&Regex>,
+) -> Option<&'a Asset> {
+    if let Some(p) = pattern {
+        return assets.iter().find(|a| p.is_match(&a.name));
+    }
+    let candidates: Vec<&Asset> = assets
+        .iter()
+        .filter(|a| {
+            let n = a.name.to_lowercase();
+            !IGNORED_EXTENSIONS.iter().any(|ext| n.ends_with(ext))
+                && !n.contains("android")
+                && os_keywords(os).iter().any(|k| n.contains(k))
+                && arch_keywords(arch).iter().any(|k| n.contains(k))
+        })
+        .collect();
+    // prefer archives over other files, e.g. .AppImage
+    candidates
+        .iter()
+        .find(|a| is_archive(&a.name))
+        .or_else(|| candidates.first())
+        .copied()
+}
+
+#[cfg(not(windows))]
+fn set_executable(path: &Path) -> std::result::Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|e| {
+        Error::SetPermissions {
+            path: path.to_path_buf(),
+            source: e,
+        }
+    })
+}
+
+#[cfg(windows)]
+fn set_executable(_path: &Path) -> std::result::Result<(), Error> {
+    Ok(())
+}
+
+fn version_path(binary: &str) -> std::result::Result<PathBuf, Error> {
+    Ok(dirs::cache_dir()
+        .ok_or(Error::CacheDir)?
+        .join("tuning")
+        .join("github_release")
+        .join(format!("{}.version", binary)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets(names: &[&str]) -> Vec<Asset> {
+        names
+            .iter()
+            .map(|n| Asset {
+                browser_download_url: format!("https://example.com/{}", n),
+                name: String::from(*n),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn name_with_defaults() {
+        let github_release = GithubRelease {
+            repo: String::from("BurntSushi/ripgrep"),
+            ..Default::default()
+        };
+        let got = github_release.name();
+        let want = "github_release BurntSushi/ripgrep@latest -> ripgrep";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn select_asset_for_os_and_arch() {
+        let assets = assets(&[
+            "ripgrep-14.1.0-aarch64-apple-darwin.tar.gz",
+            "ripgrep-14.1.0-x86_64-pc-windows-msvc.zip",
+            "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz.sha256",
+            "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz",
+            "ripgrep_14.1.0-1_amd64.deb",
+        ]);
+        let name = |os, arch| select_asset(&assets, os, arch, None).map(|a| a.name.as_str());
+
+        assert_eq!(
+            name("linux", "x86_64"),
+            Some("ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz")
+        );
+        assert_eq!(
+            name("macos", "aarch64"),
+            Some("ripgrep-14.1.0-aarch64-apple-darwin.tar.gz")
+        );
+        assert_eq!(
+            name("windows", "x86_64"),
+            Some("ripgrep-14.1.0-x86_64-pc-windows-msvc.zip")
+        );
+        assert_eq!(name("linux", "aarch64"), None);
+    }
+
+    #[test]
+    fn select_asset_with_pattern() -> std::result::Result<(), Error> {
+        let assets = assets(&["tool-linux-amd64", "tool-linux-amd64-static"]);
+        let pattern = Regex::new("static$")?;
+        let got = select_asset(&assets, "linux", "x86_64", Some(&pattern));
+        assert_eq!(
+            got.map(|a| a.name.as_str()),
+            Some("tool-linux-amd64-static")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn find_file_in_nested_directory() -> std::result::Result<(), Error> {
+        let temp = Temp::new_dir().map_err(|e| Error::TempPath { source: e })?;
+        let nested = temp.join("ripgrep-14.1.0").join("bin");
+        create_dir_all(&nested)?;
+        fs::write(nested.join("rg"), "").expect("write");
+
+        assert_eq!(find_file(&temp, "rg")?, Some(nested.join("rg")));
+        assert_eq!(find_file(&temp, "missing")?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use mktemp::Temp;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("key does not have fingerprint {}", fingerprint)]
+    FingerprintMismatch { fingerprint: String },
+    #[error("src has other keys besides {}: {}", fingerprint, others.join(", "))]
+    OtherKeys {
+        fingerprint: String,
+        others: Vec<String>,
+    },
+    #[error("gpg_key requires src or url")]
+    SrcRequired,
+    #[error(transparent)]
+    TempPath { source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct GpgKey {
+    pub fingerprint: String,
+    pub keyring: Option<PathBuf>,
+    pub src: Option<PathBuf>,
+    pub url: Option<String>,
+}
+impl GpgKey {
+    pub fn execute(&self) -> Result {
+        let fingerprint = normalize(&self.fingerprint);
+        let description = match &self.keyring {
+            Some(k) => format!("{} in {}", fingerprint, k.display()),
+            None => fingerprint.clone(),
+        };
+        if self.is_present(&fingerprint)? {
+            return Ok(Status::NoChange(description));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(String::from("absent"), description));
+        }
+
+        // keep the temporary file alive until we are done with it
+        let temp = Temp::new_file().map_err(|e| Error::TempPath { source: e })?;
+        let src = match (&self.src, &self.url) {
+            (Some(s), _) => s.clone(),
+            (None, Some(u)) => {
+                let t = temp.to_path_buf();
+                command::capture_success("curl", &["-fsSL", "-o", &t.to_string_lossy(), u])?;
+                t
+            }
+            (None, None) => return Err(Error::SrcRequired),
+        };
+
+        let shown = colons(&["--show-keys"], &src)?;
+        if !parse_fingerprints(&shown).contains(&fingerprint) {
+            return Err(Error::FingerprintMismatch { fingerprint });
+        }
+        // every key in src is imported, so any others would be trusted too
+        let others: Vec<String> = parse_primary_fingerprints(&shown)
+            .into_iter()
+            .filter(|f| *f != fingerprint)
+            .collect();
+        if !others.is_empty() {
+            return Err(Error::OtherKeys {
+                fingerprint,
+                others,
+            });
+        }
+
+        let s = src.to_string_lossy();
+        match &self.keyring {
+            Some(k) => {
+                let k = k.to_string_lossy();
+                command::capture_success("gpg", &["--dearmor", "--yes", "-o", &k, &s])?
+            }
+            None => command::capture_success("gpg", &["--batch", "--import", &s])?,
+        };
+
+        Ok(Status::Changed(String::from("absent"), description))
+    }
+
+    pub fn name(&self) -> String {
+        let src = match (&self.src, &self.url) {
+            (Some(s), _) => format!("{}", s.display()),
+            (None, Some(u)) => u.clone(),
+            (None, None) => String::new(),
+        };
+        match &self.keyring {
+            Some(k) => format!("gpg --dearmor -o {} {}", k.display(), src),
+            None => format!("gpg --import {}", src),
+        }
+    }
+
+    fn is_present(&self, fingerprint: &str) -> std::result::Result<bool, Error> {
+        match &self.keyring {
+            Some(k) => {
+                if !k.exists() {
+                    return Ok(false);
+                }
+                Ok(parse_fingerprints(colons(&["--show-keys"], k)?)
+                    .contains(&String::from(fingerprint)))
+            }
+            None => {
+                let data = command::capture("gpg", &["--list-keys", "--with-colons", fingerprint])?;
+                Ok(data.success())
+            }
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// machine-readable output, see `parse_fingerprints()`
+fn colons<P>(args: &[&str], path: P) -> std::result::Result<String, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut a = vec!["--with-colons"];
+    a.extend(args);
+    let p = path.as_ref().to_string_lossy();
+    a.push(&p);
+    Ok(command::capture_success("gpg", &a)?.stdout_str())
+}
+
+fn normalize<S>(fingerprint: S) -> String
+where
+    S: AsRef<str>,
+{
+    fingerprint
+        .as_ref()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+// parses `gpg --with-colons` output, e.g. "fpr:::::::::ABCD1234:"
+fn parse_fingerprints<S>(output: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .filter(|line| line.starts_with("fpr:"))
+        .filter_map(|line| line.split(':').nth(9))
+        .map(normalize)
+        .collect()
+}
+
+// like `parse_fingerprints()`, but without subkeys, i.e. one fingerprint per key
+fn parse_primary_fingerprints<S>(output: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    let mut record = "";
+    let mut fingerprints = Vec::new();
+    for line in output.as_ref().lines() {
+        let kind = line.split(':').next().unwrap_or_default();
+        if kind != "fpr" {
+            record = kind;
+        } else if record == "pub" || record == "sec" {
+            fingerprints.extend(line.split(':').nth(9).map(normalize));
+        }
+    }
+    fingerprints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_url() {
+        let gpg_key = GpgKey {
+            url: Some(String::from("https://example.com/key.asc")),
+            ..Default::default()
+        };
+        let got = gpg_key.name();
+        let want = "gpg --import https://example.com/key.asc";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_src_and_keyring() {
+        let gpg_key = GpgKey {
+            keyring: Some(PathBuf::from("/etc/apt/keyrings/example.gpg")),
+            src: Some(PathBuf::from("key.asc")),
+            ..Default::default()
+        };
+        let got = gpg_key.name();
+        let want = "gpg --dearmor -o /etc/apt/keyrings/example.gpg key.asc";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn normalize_removes_spaces_and_uppercases() {
+        assert_eq!(normalize("abcd 1234 EF56"), "ABCD1234EF56");
+    }
+
+    #[test]
+    fn parse_fingerprints_from_colons() {
+        let input = "pub:-:4096:1:AAAA:1:::-:::scESC::::::23::0:\nfpr:::::::::0123456789ABCDEF:\nuid:-::::1::AAAA::Someone <someone@example.com>::::::::::0:\nsub:-:4096:1:BBBB:1::::::e::::::23:\nfpr:::::::::FEDCBA9876543210:\n";
+        let got = parse_fingerprints(input);
+        assert_eq!(
+            got,
+            vec![
+                String::from("0123456789ABCDEF"),
+                String::from("FEDCBA9876543210")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_primary_fingerprints_skips_subkeys() {
+        let input = "pub:-:4096:1:AAAA:1:::-:::scESC::::::23::0:\nfpr:::::::::0123456789ABCDEF:\nuid:-::::1::AAAA::Someone <someone@example.com>::::::::::0:\nsub:-:4096:1:BBBB:1::::::e::::::23:\nfpr:::::::::FEDCBA9876543210:\npub:-:4096:1:CCCC:1:::-:::scESC::::::23::0:\nfpr:::::::::00112233445566FF:\n";
+        let got = parse_primary_fingerprints(input);
+        assert_eq!(
+            got,
+            vec![
+                String::from("0123456789ABCDEF"),
+                String::from("00112233445566FF")
+            ]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::cell::RefCell;
+
+use serde::{Deserialize, Serialize};
+use tera::Context;
+use thiserror::Error as ThisError;
+
+use super::{
+    super::{
+        facts::{self, Facts},
+        template,
+    },
+    command, Execute, Job, Status,
+};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("{}: {}", name, source)]
+    Child {
+        name: String,
+        source: Box<super::Error>,
+    },
+    #[error("unable to prepare template context: {}", source)]
+    Context { source: tera::Error },
+    #[error(transparent)]
+    Facts {
+        #[from]
+        source: facts::Error,
+    },
+}
+
+thread_local! {
+    // set while the outermost group on this thread runs its children,
+    // so that nested groups use its facts instead of gathering them again
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+// children run one at a time in the order they are defined,
+// and the group's own `needs`, `retries` and `timeout` apply to all of them,
+// so children may not set their own, and `when` expressions of children only see facts
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Group {
+    pub jobs: Vec<Job>,
+}
+impl Group {
+    pub fn execute(&self) -> Result {
+        if let Some(context) = CONTEXT.with(|c| c.borrow().clone()) {
+            return self.execute_children(&context);
+        }
+        let context =
+            template::context(&Facts::gather()?).map_err(|e| Error::Context { source: e })?;
+        CONTEXT.with(|c| c.replace(Some(context.clone())));
+        let result = self.execute_children(&context);
+        CONTEXT.with(|c| c.replace(None));
+        result
+    }
+
+    fn execute_children(&self, context: &Context) -> Result {
+        let mut previously = Vec::<String>::new();
+        let mut current = Vec::<String>::new();
+        let mut done = false;
+        let mut unchecked = Vec::<String>::new();
+
+        for job in &self.jobs {
+            let name = job.name();
+            let when = job.when(context).map_err(|e| Error::Child {
+                name: name.clone(),
+                source: Box::new(e),
+            })?;
+            if !when || !super::is_os_supported(&job.os()) {
+                continue;
+            }
+            let result = if command::is_check() {
+                job.check()
+            } else {
+                job.execute()
+            };
+            match result {
+                Ok(Status::Changed(from, to)) | Ok(Status::WouldChange(from, to)) => {
+                    previously.push(format!("{}: {}", name, from));
+                    current.push(format!("{}: {}", name, to));
+                }
+                Ok(Status::Done) => done = true,
+                Ok(Status::Unchecked(_)) => unchecked.push(name),
+                Ok(_) => {}
+                Err(_) if job.ignore_errors() => {}
+                Err(e) => {
+                    return Err(Error::Child {
+                        name,
+                        source: Box::new(e),
+                    })
+                }
+            }
+        }
+
+        if !current.is_empty() {
+            Ok(Status::Changed(previously.join(", "), current.join(", ")))
+        } else if done {
+            Ok(Status::Done)
+        } else if !unchecked.is_empty() {
+            Ok(Status::Unchecked(format!(
+                "not checked: {}",
+                unchecked.join(", ")
+            )))
+        } else {
+            Ok(Status::NoChange(format!("{} jobs", self.jobs.len())))
+        }
+    }
+
+    // children that set what only the group itself honours, including in nested groups
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for job in &self.jobs {
+            let mut fields = Vec::new();
+            if !job.needs().is_empty() {
+                fields.push("needs");
+            }
+            if job.metadata.retries.is_some() {
+                fields.push("retries");
+            }
+            if job.timeout().is_some() {
+                fields.push("timeout");
+            }
+            for field in fields {
+                problems.push(format!(
+                    "child {:?} sets `{}`, which only the group itself honours",
+                    job.name(),
+                    field
+                ));
+            }
+            if let super::Spec::Group(g) = &job.spec {
+                problems.extend(g.problems());
+            }
+        }
+        problems
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "group: {}",
+            self.jobs
+                .iter()
+                .map(|j| j.name())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{super::Main, *};
+
+    fn group(input: &str) -> Group {
+        let mut main = Main::try_from(input).expect("valid toml");
+        match main.jobs.remove(0).spec {
+            super::super::Spec::Group(g) => g,
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn name_lists_children() {
+        let g = group(
+            r#"
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              name = "first"
+              type = "assert"
+              that = true
+              [[jobs.jobs]]
+              name = "second"
+              type = "assert"
+              that = true
+            "#,
+        );
+        assert_eq!(g.name(), "group: first, second");
+    }
+
+    #[test]
+    fn execute_skips_children_and_stops_at_errors() {
+        let g = group(
+            r#"
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              name = "skipped"
+              type = "assert"
+              that = false
+              when = false
+              [[jobs.jobs]]
+              name = "fine"
+              type = "assert"
+              that = true
+            "#,
+        );
+        assert_eq!(
+            g.execute().ok(),
+            Some(Status::NoChange(String::from("2 jobs")))
+        );
+
+        let g = group(
+            r#"
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              name = "broken"
+              type = "assert"
+              that = false
+            "#,
+        );
+        match g.execute() {
+            Err(Error::Child { name, .. }) => assert_eq!(name, "broken"),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn problems_list_children_that_set_what_only_groups_honour() {
+        let g = group(
+            r#"
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              name = "fine"
+              type = "assert"
+              that = true
+              [[jobs.jobs]]
+              name = "impatient"
+              type = "assert"
+              that = true
+              needs = ["fine"]
+              timeout = "1s"
+              [[jobs.jobs]]
+              type = "group"
+                [[jobs.jobs.jobs]]
+                name = "nested"
+                type = "assert"
+                that = true
+                retries = 2
+            "#,
+        );
+        assert_eq!(
+            g.problems(),
+            vec![
+                r#"child "impatient" sets `needs`, which only the group itself honours"#,
+                r#"child "impatient" sets `timeout`, which only the group itself honours"#,
+                r#"child "nested" sets `retries`, which only the group itself honours"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn execute_shares_facts_with_nested_groups_only_while_running() {
+        let g = group(
+            r#"
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              type = "group"
+                [[jobs.jobs.jobs]]
+                name = "nested"
+                type = "assert"
+                that = true
+                when = "is_os_linux or not is_os_linux"
+            "#,
+        );
+        assert_eq!(
+            g.execute().ok(),
+            Some(Status::NoChange(String::from("1 jobs")))
+        );
+        assert!(CONTEXT.with(|c| c.borrow().is_none()));
+    }
+}
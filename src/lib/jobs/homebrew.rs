@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+// where Homebrew installs itself when it is not already in the PATH
+const BREW_PATHS: &[&str] = &[
+    "/opt/homebrew/bin/brew",              // macOS (Apple Silicon)
+    "/usr/local/bin/brew",                 // macOS (Intel)
+    "/home/linuxbrew/.linuxbrew/bin/brew", // Linux
+];
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("brew not found")]
+    BrewNotFound,
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("homebrew requires tap, formula or cask")]
+    NothingToDo,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Homebrew {
+    pub cask: Option<String>,
+    pub formula: Option<String>,
+    pub tap: Option<String>,
+}
+impl Homebrew {
+    pub fn execute(&self) -> Result {
+        if self.cask.is_none() && self.formula.is_none() && self.tap.is_none() {
+            return Err(Error::NothingToDo);
+        }
+        let brew = brew_path().ok_or(Error::BrewNotFound)?;
+        let brew = brew.to_string_lossy();
+        let mut previously = Vec::<String>::new();
+        let mut changed = Vec::<String>::new();
+
+        if let Some(tap) = &self.tap {
+            let taps = list(&brew, &["tap"])?;
+            if taps.iter().any(|t| t.eq_ignore_ascii_case(tap)) {
+                previously.push(format!("tap: {}", tap));
+            } else {
+                if !command::is_check() {
+                    command::capture_success(&brew, &["tap", tap])?;
+                }
+                changed.push(format!("tap: {}", tap));
+            }
+        }
+
+        if let Some(formula) = &self.formula {
+            let formulae = list(&brew, &["list", "--formula", "-1"])?;
+            if formulae.contains(&short_name(formula)) {
+                previously.push(format!("formula: {}", formula));
+            } else {
+                if !command::is_check() {
+                    command::capture_success(&brew, &["install", formula])?;
+                }
+                changed.push(format!("formula: {}", formula));
+            }
+        }
+
+        if let Some(cask) = &self.cask {
+            let casks = list(&brew, &["list", "--cask", "-1"])?;
+            if casks.contains(&short_name(cask)) {
+                previously.push(format!("cask: {}", cask));
+            } else {
+                if !command::is_check() {
+                    command::capture_success(&brew, &["install", "--cask", cask])?;
+                }
+                changed.push(format!("cask: {}", cask));
+            }
+        }
+
+        if changed.is_empty() {
+            Ok(Status::NoChange(previously.join(", ")))
+        } else {
+            Ok(Status::Changed(String::from("absent"), changed.join(", ")))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = Vec::<String>::new();
+        if let Some(tap) = &self.tap {
+            parts.push(format!("brew tap {}", tap));
+        }
+        if let Some(formula) = &self.formula {
+            parts.push(format!("brew install {}", formula));
+        }
+        if let Some(cask) = &self.cask {
+            parts.push(format!("brew install --cask {}", cask));
+        }
+        parts.join(" && ")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn brew_path() -> Option<PathBuf> {
+    which("brew").ok().or_else(|| {
+        BREW_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(dirs::home_dir().map(|h| h.join(".linuxbrew").join("bin").join("brew")))
+            .find(|p| p.is_file())
+    })
+}
+
+fn list(brew: &str, args: &[&str]) -> std::result::Result<Vec<String>, Error> {
+    let output = command::capture_success(brew, args)?.stdout_str();
+    Ok(output
+        .lines()
+        .map(|line| String::from(line.trim()))
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+// `brew list` omits the tap prefix, e.g. "user/tap/foo" is listed as "foo"
+fn short_name<S>(name: S) -> String
+where
+    S: AsRef<str>,
+{
+    String::from(name.as_ref().rsplit('/').next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_formula() {
+        let homebrew = Homebrew {
+            formula: Some(String::from("ripgrep")),
+            ..Default::default()
+        };
+        let got = homebrew.name();
+        let want = "brew install ripgrep";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_tap_and_cask() {
+        let homebrew = Homebrew {
+            cask: Some(String::from("font-fira-code")),
+            tap: Some(String::from("homebrew/cask-fonts")),
+            ..Default::default()
+        };
+        let got = homebrew.name();
+        let want = "brew tap homebrew/cask-fonts && brew install --cask font-fira-code";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn short_name_strips_tap() {
+        assert_eq!(short_name("ripgrep"), "ripgrep");
+        assert_eq!(short_name("user/tap/foo"), "foo");
+    }
+
+    #[test]
+    fn execute_errs_without_tap_formula_or_cask() {
+        let homebrew = Homebrew::default();
+        assert!(homebrew.execute().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::env::consts::OS;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("hostname is not supported on {}", os)]
+    UnsupportedOs { os: String },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Hostname {
+    pub hostname: String,
+}
+impl Hostname {
+    pub fn execute(&self) -> Result {
+        let previously = current_hostname()?;
+        if previously == self.hostname {
+            return Ok(Status::NoChange(previously));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(previously, self.hostname.clone()));
+        }
+
+        match OS {
+            "linux" => {
+                if which("hostnamectl").is_ok() {
+                    command::capture_success_as_root(
+                        "hostnamectl",
+                        &["set-hostname", &self.hostname],
+                    )?;
+                } else {
+                    command::capture_success_as_root("hostname", &[&self.hostname])?;
+                }
+            }
+            "macos" => {
+                // LocalHostName is used for Bonjour, and does not allow dots
+                let local = self.hostname.replace('.', "-");
+                command::capture_success_as_root("scutil", &["--set", "HostName", &self.hostname])?;
+                command::capture_success_as_root("scutil", &["--set", "LocalHostName", &local])?;
+                command::capture_success_as_root(
+                    "scutil",
+                    &["--set", "ComputerName", &self.hostname],
+                )?;
+            }
+            os => {
+                return Err(Error::UnsupportedOs {
+                    os: String::from(os),
+                })
+            }
+        }
+
+        Ok(Status::Changed(previously, self.hostname.clone()))
+    }
+
+    pub fn name(&self) -> String {
+        match OS {
+            "macos" => format!("scutil --set HostName {}", self.hostname),
+            _ => format!("hostnamectl set-hostname {}", self.hostname),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn current_hostname() -> std::result::Result<String, Error> {
+    let data = match OS {
+        "linux" if which("hostnamectl").is_ok() => {
+            command::capture_success("hostnamectl", &["--static"])?
+        }
+        "macos" => {
+            let data = command::capture("scutil", &["--get", "HostName"])?;
+            if !data.success() {
+                // HostName is not set by default
+                return Ok(String::new());
+            }
+            data
+        }
+        _ => command::capture_success("hostname", &[] as &[&str])?,
+    };
+    Ok(String::from(data.stdout_str().trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_hostname() {
+        let hostname = Hostname {
+            hostname: String::from("my-laptop"),
+        };
+        let got = hostname.name();
+        let want = match OS {
+            "macos" => "scutil --set HostName my-laptop",
+            _ => "hostnamectl set-hostname my-laptop",
+        };
+        assert_eq!(got, want);
+    }
+}
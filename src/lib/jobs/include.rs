@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::Status;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("{} was not spliced into the config before running", path.display())]
+    NotSpliced { path: PathBuf },
+}
+
+// this never runs on its own:
+// `Main::splice_includes()` replaces it with the jobs from `path`
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Include {
+    pub path: PathBuf,
+}
+impl Include {
+    pub fn execute(&self) -> Result {
+        Err(Error::NotSpliced {
+            path: self.path.clone(),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        format!("include {}", self.path.display())
+    }
+
+    // relative paths are relative to the directory of the including file
+    pub fn resolve<P>(&self, dir: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        if self.path.is_absolute() {
+            self.path.clone()
+        } else {
+            dir.as_ref().join(&self.path)
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_path() {
+        let include = Include {
+            path: PathBuf::from("git.toml"),
+        };
+        assert_eq!(include.name(), "include git.toml");
+    }
+
+    #[test]
+    fn resolve_relative_and_absolute() {
+        let include = Include {
+            path: PathBuf::from("tools/git.toml"),
+        };
+        assert_eq!(
+            include.resolve("/home/me/.config/tuning"),
+            PathBuf::from("/home/me/.config/tuning/tools/git.toml")
+        );
+
+        let include = Include {
+            path: std::env::temp_dir().join("git.toml"),
+        };
+        assert_eq!(include.resolve("/elsewhere"), include.path);
+    }
+}
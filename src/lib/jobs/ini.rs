@@ -0,0 +1,269 @@
+// minimal line-preserving editor for INI-style files (e.g. ~/.gitconfig),
+// section and key names are case-insensitive, subsection names are not
+
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct Document {
+    indent: String,
+    lines: Vec<String>,
+}
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+impl Document {
+    pub fn parse<S, I>(text: S, indent: I) -> Self
+    where
+        S: AsRef<str>,
+        I: AsRef<str>,
+    {
+        Self {
+            indent: String::from(indent.as_ref()),
+            lines: text.as_ref().lines().map(String::from).collect(),
+        }
+    }
+
+    pub fn get(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<String> {
+        let (start, end) = self.find_section(section, subsection)?;
+        self.lines[start..end]
+            .iter()
+            .rev() // later values win
+            .filter_map(parse_entry)
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn set(&mut self, section: &str, subsection: Option<&str>, key: &str, value: &str) {
+        let (start, end) = match self.find_section(section, subsection) {
+            Some(r) => r,
+            None => {
+                self.lines.push(render_header(section, subsection));
+                (self.lines.len(), self.lines.len())
+            }
+        };
+
+        // update the last matching entry in place, keeping its indentation
+        if let Some(i) = (start..end).rev().find(|i| {
+            parse_entry(&self.lines[*i]).is_some_and(|(k, _)| k.eq_ignore_ascii_case(key))
+        }) {
+            let line = &self.lines[i];
+            let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+            let name = line.trim().split('=').next().unwrap_or_default().trim();
+            self.lines[i] = format!("{}{} = {}", indent, name, quote(value));
+            return;
+        }
+
+        // otherwise add it after the last non-blank line of the section
+        let mut insert_at = end;
+        while insert_at > start && self.lines[insert_at - 1].trim().is_empty() {
+            insert_at -= 1;
+        }
+        let indent = self.lines[start..end]
+            .iter()
+            .find(|line| parse_entry(line).is_some())
+            .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+            .unwrap_or_else(|| self.indent.clone());
+        self.lines
+            .insert(insert_at, format!("{}{} = {}", indent, key, quote(value)));
+    }
+
+    pub fn remove(&mut self, section: &str, subsection: Option<&str>, key: &str) -> bool {
+        let (start, end) = match self.find_section(section, subsection) {
+            Some(r) => r,
+            None => return false,
+        };
+        let matches: Vec<usize> = (start..end)
+            .filter(|i| {
+                parse_entry(&self.lines[*i]).is_some_and(|(k, _)| k.eq_ignore_ascii_case(key))
+            })
+            .collect();
+        for i in matches.iter().rev() {
+            self.lines.remove(*i);
+        }
+        !matches.is_empty()
+    }
+
+    // returns the range of lines after the header, up to the next header
+    fn find_section(&self, section: &str, subsection: Option<&str>) -> Option<(usize, usize)> {
+        let header = self.lines.iter().position(|line| {
+            parse_header(line).is_some_and(|(s, ss)| {
+                s.eq_ignore_ascii_case(section) && ss.as_deref() == subsection
+            })
+        })?;
+        let start = header + 1;
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| parse_header(line).is_some())
+            .map_or(self.lines.len(), |i| start + i);
+        Some((start, end))
+    }
+}
+
+fn parse_entry<S>(line: S) -> Option<(String, String)>
+where
+    S: AsRef<str>,
+{
+    let l = line.as_ref().trim();
+    if l.is_empty() || l.starts_with('#') || l.starts_with(';') || l.starts_with('[') {
+        return None;
+    }
+    let mut parts = l.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts
+        .next()
+        .map(unquote)
+        .unwrap_or_else(|| String::from("true"));
+    Some((String::from(key), value))
+}
+
+// e.g. `[user]`, `[includeIf "gitdir:~/work/"]`, `[branch.master]`
+fn parse_header<S>(line: S) -> Option<(String, Option<String>)>
+where
+    S: AsRef<str>,
+{
+    let l = line.as_ref().trim();
+    if !l.starts_with('[') {
+        return None;
+    }
+    let inner = &l[1..l.find(']')?];
+    match inner.find(|c: char| c.is_whitespace()) {
+        Some(i) => {
+            let subsection = inner[i..].trim().trim_matches('"').replace("\\\"", "\"");
+            Some((String::from(&inner[..i]), Some(subsection)))
+        }
+        None => match inner.find('.') {
+            Some(i) => Some((
+                String::from(&inner[..i]),
+                Some(String::from(&inner[i + 1..])),
+            )),
+            None => Some((String::from(inner), None)),
+        },
+    }
+}
+
+fn render_header(section: &str, subsection: Option<&str>) -> String {
+    match subsection {
+        Some(ss) => format!("[{} \"{}\"]", section, ss.replace('"', "\\\"")),
+        None => format!("[{}]", section),
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.contains(['#', ';', '"', '\\'])
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+    {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        String::from(value)
+    }
+}
+
+fn unquote<S>(value: S) -> String
+where
+    S: AsRef<str>,
+{
+    let mut output = String::new();
+    let mut in_quotes = false;
+    let mut chars = value.as_ref().trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    output.push(match next {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                }
+            }
+            '#' | ';' if !in_quotes => break,
+            _ => output.push(c),
+        }
+    }
+    String::from(output.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GITCONFIG: &str = r#"# managed by hand
+[user]
+	name = Someone
+	email = someone@example.com
+
+[includeIf "gitdir:~/work/"]
+	path = ~/work/.gitconfig
+"#;
+
+    #[test]
+    fn get_existing_values() {
+        let doc = Document::parse(GITCONFIG, "\t");
+        assert_eq!(
+            doc.get("user", None, "email"),
+            Some(String::from("someone@example.com"))
+        );
+        assert_eq!(doc.get("User", None, "Name"), Some(String::from("Someone")));
+        assert_eq!(
+            doc.get("includeIf", Some("gitdir:~/work/"), "path"),
+            Some(String::from("~/work/.gitconfig"))
+        );
+        assert_eq!(doc.get("user", None, "missing"), None);
+    }
+
+    #[test]
+    fn set_replaces_existing_value_in_place() {
+        let mut doc = Document::parse(GITCONFIG, "\t");
+        doc.set("user", None, "email", "other@example.com");
+        assert_eq!(
+            doc.to_string(),
+            GITCONFIG.replace("someone@example.com", "other@example.com")
+        );
+    }
+
+    #[test]
+    fn set_adds_key_to_existing_section() {
+        let mut doc = Document::parse(GITCONFIG, "\t");
+        doc.set("user", None, "signingkey", "ABCD");
+        assert_eq!(
+            doc.to_string(),
+            GITCONFIG.replace(
+                "someone@example.com\n",
+                "someone@example.com\n\tsigningkey = ABCD\n"
+            )
+        );
+    }
+
+    #[test]
+    fn set_adds_new_section() {
+        let mut doc = Document::parse(GITCONFIG, "\t");
+        doc.set("alias", None, "co", "checkout");
+        assert_eq!(
+            doc.to_string(),
+            format!("{}[alias]\n\tco = checkout\n", GITCONFIG)
+        );
+    }
+
+    #[test]
+    fn remove_existing_key() {
+        let mut doc = Document::parse(GITCONFIG, "\t");
+        assert!(doc.remove("user", None, "name"));
+        assert!(!doc.remove("user", None, "name"));
+        assert_eq!(doc.to_string(), GITCONFIG.replace("\tname = Someone\n", ""));
+    }
+
+    #[test]
+    fn quote_and_unquote_special_values() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("!git log # all"), r#""!git log # all""#);
+        assert_eq!(unquote(r#""!git log # all""#), "!git log # all");
+        assert_eq!(unquote("value ; comment"), "value");
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::Status;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(
+        "template {} was not instantiated into the config before running",
+        template
+    )]
+    NotSpliced { template: String },
+}
+
+// this never runs on its own:
+// `Main::splice_instances()` replaces it with the jobs from `[templates.<template>]`,
+// rendered with `{{ args.* }}`
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Instance {
+    #[serde(default)]
+    pub args: BTreeMap<String, serde_json::Value>,
+    pub template: String,
+}
+impl Instance {
+    pub fn execute(&self) -> Result {
+        Err(Error::NotSpliced {
+            template: self.template.clone(),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|(k, v)| match v {
+                serde_json::Value::String(s) => format!("{}={}", k, s),
+                _ => format!("{}={}", k, v),
+            })
+            .collect();
+        if args.is_empty() {
+            format!("{} instance", self.template)
+        } else {
+            format!("{} instance: {}", self.template, args.join(", "))
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_args() {
+        let mut args = BTreeMap::new();
+        args.insert(String::from("name"), serde_json::json!("vim"));
+        args.insert(String::from("shallow"), serde_json::json!(true));
+        let instance = Instance {
+            args,
+            template: String::from("dotfile"),
+        };
+        assert_eq!(instance.name(), "dotfile instance: name=vim, shallow=true");
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use thiserror::Error as ThisError;
+
+use super::super::{
+    facts::{self, Facts},
+    state,
+};
+
+lazy_static! {
+    // one writer of the state file at a time
+    static ref MUTEX: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Facts {
+        #[from]
+        source: facts::Error,
+    },
+    #[error(transparent)]
+    State {
+        #[from]
+        source: state::Error,
+    },
+}
+
+// job names, and when each one last completed, in seconds since the epoch
+type State = BTreeMap<String, u64>;
+
+// how long ago the job last completed, if within `interval`
+pub fn ran_within(name: &str, interval: Duration) -> Result<Option<Duration>, Error> {
+    let _lock = MUTEX.lock().unwrap();
+    let completed: State = state::read_state(&state_path()?)?;
+    Ok(elapsed(&completed, name, now()).filter(|e| *e < interval))
+}
+
+pub fn record(name: &str) -> Result<(), Error> {
+    let _lock = MUTEX.lock().unwrap();
+    let path = state_path()?;
+    let mut completed: State = state::read_state(&path)?;
+    completed.insert(String::from(name), now());
+    Ok(state::write_state(&path, &completed)?)
+}
+
+fn elapsed(state: &State, name: &str, now: u64) -> Option<Duration> {
+    state
+        .get(name)
+        .map(|then| Duration::from_secs(now.saturating_sub(*then)))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn state_path() -> Result<PathBuf, Error> {
+    Ok(state::intervals_path(&Facts::gather()?.cache_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn write_then_read_elapsed() -> Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = state::intervals_path(&temp);
+        assert_eq!(state::read_state::<State>(&path)?, State::new());
+
+        let mut state = State::new();
+        state.insert(String::from("brew update"), 1000);
+        state::write_state(&path, &state)?;
+
+        let state: State = state::read_state(&path)?;
+        assert_eq!(
+            elapsed(&state, "brew update", 1060),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(elapsed(&state, "something else", 1060), None);
+        Ok(())
+    }
+}
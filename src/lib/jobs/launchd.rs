@@ -0,0 +1,198 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("unable to create {}: {}", path.display(), source)]
+    CreatePath { path: PathBuf, source: io::Error },
+    #[error("unable to find home_dir")]
+    HomeDir,
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to remove {}: {}", path.display(), source)]
+    RemovePath { path: PathBuf, source: io::Error },
+    #[error("launchd requires src unless state = \"absent\"")]
+    SrcRequired,
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchdState {
+    Absent,
+    #[default]
+    Present,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Launchd {
+    pub label: String,
+    pub src: Option<PathBuf>,
+    pub state: Option<LaunchdState>,
+}
+impl Launchd {
+    pub fn execute(&self) -> Result {
+        let path = self.path()?;
+        let p = path.to_string_lossy();
+        let loaded = command::capture("launchctl", &["list", &self.label])?.success();
+        let installed = read_optional(&path)?;
+
+        if self.state.unwrap_or_default() == LaunchdState::Absent {
+            if !loaded && installed.is_none() {
+                return Ok(Status::NoChange(format!("{}: absent", self.label)));
+            }
+            let changed = Status::Changed(
+                format!("{}: {}", self.label, state_label(loaded)),
+                format!("{}: absent", self.label),
+            );
+            if command::is_check() {
+                return Ok(changed);
+            }
+            if loaded {
+                command::capture_success("launchctl", &["unload", &p])?;
+            }
+            if installed.is_some() {
+                fs::remove_file(&path).map_err(|e| Error::RemovePath {
+                    path: path.clone(),
+                    source: e,
+                })?;
+            }
+            return Ok(changed);
+        }
+
+        let src = self.src.as_ref().ok_or(Error::SrcRequired)?;
+        let contents = fs::read(src).map_err(|e| Error::ReadPath {
+            path: src.clone(),
+            source: e,
+        })?;
+        let outdated = installed.as_ref() != Some(&contents);
+        if loaded && !outdated {
+            return Ok(Status::NoChange(format!("{}: loaded", self.label)));
+        }
+        let previously = match (&installed, outdated) {
+            (None, _) => String::from("absent"),
+            (Some(_), true) => String::from("outdated"),
+            (Some(_), false) => String::from(state_label(loaded)),
+        };
+        let changed = Status::Changed(
+            format!("{}: {}", self.label, previously),
+            format!("{}: loaded", self.label),
+        );
+        if outdated {
+            file::show_diff(&path, installed.as_deref().unwrap_or_default(), &contents);
+        }
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        if outdated {
+            // launchd only reads the plist at load time
+            if loaded {
+                command::capture_success("launchctl", &["unload", &p])?;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::CreatePath {
+                    path: parent.to_path_buf(),
+                    source: e,
+                })?;
+            }
+            fs::write(&path, &contents).map_err(|e| Error::WritePath {
+                path: path.clone(),
+                source: e,
+            })?;
+        }
+        command::capture_success("launchctl", &["load", &p])?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        let action = match self.state.unwrap_or_default() {
+            LaunchdState::Absent => "unload",
+            LaunchdState::Present => "load",
+        };
+        format!(
+            "launchctl {} ~/Library/LaunchAgents/{}.plist",
+            action, self.label
+        )
+    }
+
+    fn path(&self) -> std::result::Result<PathBuf, Error> {
+        Ok(dirs::home_dir()
+            .ok_or(Error::HomeDir)?
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", self.label)))
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn read_optional(path: &PathBuf) -> std::result::Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(b) => Ok(Some(b)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::ReadPath {
+            path: path.clone(),
+            source: e,
+        }),
+    }
+}
+
+fn state_label(loaded: bool) -> &'static str {
+    if loaded {
+        "loaded"
+    } else {
+        "unloaded"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_when_present() {
+        let launchd = Launchd {
+            label: String::from("com.example.backup"),
+            src: Some(PathBuf::from("backup.plist")),
+            ..Default::default()
+        };
+        let got = launchd.name();
+        let want = "launchctl load ~/Library/LaunchAgents/com.example.backup.plist";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_when_absent() {
+        let launchd = Launchd {
+            label: String::from("com.example.backup"),
+            state: Some(LaunchdState::Absent),
+            ..Default::default()
+        };
+        let got = launchd.name();
+        let want = "launchctl unload ~/Library/LaunchAgents/com.example.backup.plist";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn path_is_in_launch_agents() -> std::result::Result<(), Error> {
+        let launchd = Launchd {
+            label: String::from("com.example.backup"),
+            ..Default::default()
+        };
+        let got = launchd.path()?;
+        assert!(got.ends_with("Library/LaunchAgents/com.example.backup.plist"));
+        Ok(())
+    }
+}
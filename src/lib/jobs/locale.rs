@@ -0,0 +1,191 @@
+use std::{env::consts::OS, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+const LOCALTIME: &str = "/etc/localtime";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("locale requires lang or timezone")]
+    NothingToDo,
+    #[error("locale is not supported on {}", os)]
+    UnsupportedOs { os: String },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Locale {
+    pub lang: Option<String>,
+    pub timezone: Option<String>,
+}
+impl Locale {
+    pub fn execute(&self) -> Result {
+        if self.lang.is_none() && self.timezone.is_none() {
+            return Err(Error::NothingToDo);
+        }
+        if OS != "linux" && OS != "macos" {
+            return Err(Error::UnsupportedOs {
+                os: String::from(OS),
+            });
+        }
+        let mut previously = Vec::<String>::new();
+        let mut current = Vec::<String>::new();
+        let mut changed = false;
+
+        if let Some(tz) = &self.timezone {
+            let before = current_timezone();
+            if &before != tz {
+                if !command::is_check() {
+                    match OS {
+                        "macos" => {
+                            command::capture_success_as_root("systemsetup", &["-settimezone", tz])?
+                        }
+                        _ => {
+                            command::capture_success_as_root("timedatectl", &["set-timezone", tz])?
+                        }
+                    };
+                }
+                changed = true;
+            }
+            previously.push(format!("timezone={}", before));
+            current.push(format!("timezone={}", tz));
+        }
+
+        if let Some(lang) = &self.lang {
+            let before = current_lang()?;
+            if &before != lang {
+                if !command::is_check() {
+                    match OS {
+                        "macos" => command::capture_success(
+                            "defaults",
+                            &["write", "-g", "AppleLocale", "-string", lang],
+                        )?,
+                        _ => command::capture_success_as_root(
+                            "localectl",
+                            &["set-locale", &format!("LANG={}", lang)],
+                        )?,
+                    };
+                }
+                changed = true;
+            }
+            previously.push(format!("lang={}", before));
+            current.push(format!("lang={}", lang));
+        }
+
+        if changed {
+            Ok(Status::Changed(previously.join(", "), current.join(", ")))
+        } else {
+            Ok(Status::NoChange(current.join(", ")))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = Vec::<String>::new();
+        if let Some(tz) = &self.timezone {
+            parts.push(match OS {
+                "macos" => format!("systemsetup -settimezone {}", tz),
+                _ => format!("timedatectl set-timezone {}", tz),
+            });
+        }
+        if let Some(lang) = &self.lang {
+            parts.push(match OS {
+                "macos" => format!("defaults write -g AppleLocale -string {}", lang),
+                _ => format!("localectl set-locale LANG={}", lang),
+            });
+        }
+        parts.join(" && ")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn current_lang() -> std::result::Result<String, Error> {
+    if OS == "macos" {
+        let data = command::capture("defaults", &["read", "-g", "AppleLocale"])?;
+        return Ok(String::from(data.stdout_str().trim()));
+    }
+    let output = command::capture_success("localectl", &["status"])?.stdout_str();
+    Ok(parse_localectl_lang(output).unwrap_or_default())
+}
+
+// `systemsetup -gettimezone` needs root, so inspect the symlink instead
+fn current_timezone() -> String {
+    fs::read_link(LOCALTIME)
+        .ok()
+        .and_then(timezone_from_path)
+        .unwrap_or_default()
+}
+
+// e.g. "   System Locale: LANG=en_AU.UTF-8"
+fn parse_localectl_lang<S>(output: S) -> Option<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .filter_map(|line| line.split(':').nth(1))
+        .flat_map(|values| values.split_whitespace())
+        .find_map(|value| value.strip_prefix("LANG="))
+        .map(String::from)
+}
+
+// e.g. /usr/share/zoneinfo/Australia/Sydney
+fn timezone_from_path<P>(path: P) -> Option<String>
+where
+    P: AsRef<Path>,
+{
+    let p = path.as_ref().to_string_lossy();
+    let index = p.find("zoneinfo/")?;
+    Some(String::from(&p[index + "zoneinfo/".len()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_timezone_and_lang() {
+        let locale = Locale {
+            lang: Some(String::from("en_AU.UTF-8")),
+            timezone: Some(String::from("Australia/Sydney")),
+        };
+        let got = locale.name();
+        let want = match OS {
+            "macos" => "systemsetup -settimezone Australia/Sydney && defaults write -g AppleLocale -string en_AU.UTF-8",
+            _ => "timedatectl set-timezone Australia/Sydney && localectl set-locale LANG=en_AU.UTF-8",
+        };
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_localectl_lang_from_status() {
+        let input = "   System Locale: LANG=en_AU.UTF-8\n                  LC_TIME=en_GB.UTF-8\n       VC Keymap: us\n";
+        assert_eq!(
+            parse_localectl_lang(input),
+            Some(String::from("en_AU.UTF-8"))
+        );
+        assert_eq!(parse_localectl_lang("VC Keymap: us"), None);
+    }
+
+    #[test]
+    fn timezone_from_localtime_target() {
+        assert_eq!(
+            timezone_from_path("/usr/share/zoneinfo/Australia/Sydney"),
+            Some(String::from("Australia/Sydney"))
+        );
+        assert_eq!(
+            timezone_from_path("/var/db/timezone/zoneinfo/UTC"),
+            Some(String::from("UTC"))
+        );
+        assert_eq!(timezone_from_path("/etc/somewhere"), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{env::consts::OS, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+const ETC_SHELLS: &str = "/etc/shells";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("{} is not listed in {}", shell.display(), ETC_SHELLS)]
+    NotInShells { shell: PathBuf },
+    #[error("unable to read {}: {}", ETC_SHELLS, source)]
+    ReadShells { source: io::Error },
+    #[error("login_shell is not supported on {}", os)]
+    UnsupportedOs { os: String },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct LoginShell {
+    pub shell: PathBuf,
+    pub user: Option<String>,
+}
+impl LoginShell {
+    pub fn execute(&self) -> Result {
+        if OS != "linux" && OS != "macos" {
+            return Err(Error::UnsupportedOs {
+                os: String::from(OS),
+            });
+        }
+        let user = match &self.user {
+            Some(u) => u.clone(),
+            None => current_user()?,
+        };
+        let shell = self.shell.to_string_lossy();
+
+        let previously = current_shell(&user)?;
+        if previously == shell {
+            return Ok(Status::NoChange(format!("{}: {}", user, shell)));
+        }
+
+        let shells = fs::read_to_string(ETC_SHELLS).map_err(|e| Error::ReadShells { source: e })?;
+        if !parse_shells(shells).iter().any(|s| s == shell.as_ref()) {
+            return Err(Error::NotInShells {
+                shell: self.shell.clone(),
+            });
+        }
+        let changed = Status::Changed(
+            format!("{}: {}", user, previously),
+            format!("{}: {}", user, shell),
+        );
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        match OS {
+            "macos" => command::capture_success_as_root(
+                "dscl",
+                &[
+                    ".",
+                    "-create",
+                    &format!("/Users/{}", user),
+                    "UserShell",
+                    &shell,
+                ],
+            )?,
+            _ if which("usermod").is_ok() => {
+                command::capture_success_as_root("usermod", &["--shell", &shell, &user])?
+            }
+            _ => command::capture_success_as_root("chsh", &["-s", &shell, &user])?,
+        };
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        let user = self.user.clone().unwrap_or_else(|| String::from("$USER"));
+        format!("chsh -s {} {}", self.shell.display(), user)
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn current_user() -> std::result::Result<String, Error> {
+    let data = command::capture_success("id", &["-un"])?;
+    Ok(String::from(data.stdout_str().trim()))
+}
+
+fn current_shell(user: &str) -> std::result::Result<String, Error> {
+    if OS == "macos" {
+        let path = format!("/Users/{}", user);
+        let data = command::capture_success("dscl", &[".", "-read", &path, "UserShell"])?;
+        return Ok(parse_dscl_shell(data.stdout_str()).unwrap_or_default());
+    }
+    let data = command::capture_success("getent", &["passwd", user])?;
+    Ok(parse_passwd_shell(data.stdout_str()).unwrap_or_default())
+}
+
+// e.g. "UserShell: /bin/zsh"
+fn parse_dscl_shell<S>(output: S) -> Option<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("UserShell:"))
+        .map(|s| String::from(s.trim()))
+}
+
+// e.g. "me:x:1000:1000:Me:/home/me:/bin/bash"
+fn parse_passwd_shell<S>(output: S) -> Option<String>
+where
+    S: AsRef<str>,
+{
+    let line = output.as_ref().lines().next()?;
+    line.split(':').nth(6).map(|s| String::from(s.trim()))
+}
+
+fn parse_shells<S>(text: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    text.as_ref()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_user() {
+        let login_shell = LoginShell {
+            shell: PathBuf::from("/usr/bin/fish"),
+            user: Some(String::from("me")),
+        };
+        let got = login_shell.name();
+        let want = "chsh -s /usr/bin/fish me";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_shell_from_dscl_and_passwd() {
+        assert_eq!(
+            parse_dscl_shell("UserShell: /bin/zsh\n"),
+            Some(String::from("/bin/zsh"))
+        );
+        assert_eq!(
+            parse_passwd_shell("me:x:1000:1000:Me:/home/me:/bin/bash\n"),
+            Some(String::from("/bin/bash"))
+        );
+        assert_eq!(parse_passwd_shell(""), None);
+    }
+
+    #[test]
+    fn parse_shells_skips_comments() {
+        let input = "# /etc/shells: valid login shells\n/bin/sh\n\n/usr/bin/zsh\n";
+        let got = parse_shells(input);
+        assert_eq!(
+            got,
+            vec![String::from("/bin/sh"), String::from("/usr/bin/zsh")]
+        );
+    }
+}
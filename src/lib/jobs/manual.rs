@@ -0,0 +1,143 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, IsTerminal},
+    path::Path,
+    sync::Mutex,
+};
+
+use colored::*;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{
+    super::{
+        events,
+        facts::{self, Facts},
+        state,
+    },
+    command, Status,
+};
+
+lazy_static! {
+    // one prompt at a time, and one writer of the state file at a time
+    static ref MUTEX: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Facts {
+        #[from]
+        source: facts::Error,
+    },
+    #[error(transparent)]
+    State {
+        #[from]
+        source: state::Error,
+    },
+}
+
+// instructions that the user has done, as in `state_line()`
+type Acknowledged = BTreeSet<String>;
+
+// never changes anything itself,
+// but reminds the user about a step that `tuning` cannot do for them
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Manual {
+    pub instruction: String,
+}
+impl Manual {
+    pub fn execute(&self) -> Result {
+        let _lock = MUTEX.lock().unwrap();
+        let path = state::acknowledged_path(&Facts::gather()?.cache_dir);
+        if is_acknowledged(&path, &self.instruction)? {
+            return Ok(Status::NoChange(String::from("acknowledged")));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(
+                String::from("not yet acknowledged"),
+                String::from("acknowledged"),
+            ));
+        }
+
+        let reminder = format!(
+            "{} {}",
+            "manual:".yellow().bold(),
+            self.instruction.yellow()
+        );
+        // without a terminal, we can only remind the user, alongside any other job output
+        if !io::stdin().is_terminal() {
+            command::write_output(&format!("{}\n", reminder));
+            return Ok(Status::NoChange(String::from("not yet acknowledged")));
+        }
+        // on stderr, so that it shows right away, and stays out of `--output json`
+        let question = format!("{} done? [y/N] ", reminder);
+        if !events::ask(&question, io::stdin().lock(), io::stderr().lock()) {
+            return Ok(Status::NoChange(String::from("not yet acknowledged")));
+        }
+
+        acknowledge(&path, &self.instruction)?;
+        Ok(Status::Changed(
+            String::from("not yet acknowledged"),
+            String::from("acknowledged"),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        format!("manual: {}", self.instruction)
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn acknowledge(path: &Path, instruction: &str) -> std::result::Result<(), Error> {
+    let mut acknowledged: Acknowledged = state::read_state(path)?;
+    acknowledged.insert(state_line(instruction));
+    Ok(state::write_state(path, &acknowledged)?)
+}
+
+fn is_acknowledged(path: &Path, instruction: &str) -> std::result::Result<bool, Error> {
+    let acknowledged: Acknowledged = state::read_state(path)?;
+    Ok(acknowledged.contains(&state_line(instruction)))
+}
+
+// the same instruction, however it is wrapped or indented
+fn state_line(instruction: &str) -> String {
+    instruction
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn name_with_instruction() {
+        let manual = Manual {
+            instruction: String::from("log into 1Password"),
+        };
+        assert_eq!(manual.name(), "manual: log into 1Password");
+    }
+
+    #[test]
+    fn acknowledge_then_is_acknowledged() -> std::result::Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = state::acknowledged_path(&temp);
+        let instruction = "import\n  browser profile";
+
+        assert!(!is_acknowledged(&path, instruction)?);
+        acknowledge(&path, instruction)?;
+        acknowledge(&path, "something else")?;
+
+        assert!(is_acknowledged(&path, instruction)?);
+        assert!(is_acknowledged(&path, "import browser profile")?);
+        assert!(!is_acknowledged(&path, "import browser")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("`mas install {}` failed: {}", id, output)]
+    InstallFailed { id: u64, output: String },
+    #[error("not signed in to the Mac App Store, please sign in with App Store.app first")]
+    NotSignedIn,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Mas {
+    pub id: u64,
+}
+impl Mas {
+    pub fn execute(&self) -> Result {
+        let list = command::capture_success("mas", &["list"])?.stdout_str();
+        if parse_list(list).contains(&self.id) {
+            return Ok(Status::NoChange(format!("{}: installed", self.id)));
+        }
+        let changed = Status::Changed(
+            format!("{}: absent", self.id),
+            format!("{}: installed", self.id),
+        );
+        if command::is_check() {
+            return Ok(changed);
+        }
+
+        let id = self.id.to_string();
+        let data = command::capture("mas", &["install", &id])?;
+        if !data.success() {
+            let output = format!("{}{}", data.stdout_str(), data.stderr_str());
+            if is_not_signed_in(&output) {
+                return Err(Error::NotSignedIn);
+            }
+            return Err(Error::InstallFailed {
+                id: self.id,
+                output: String::from(output.trim()),
+            });
+        }
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        format!("mas install {}", self.id)
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// mas has used a few different phrasings over time,
+// e.g. "Not signed in" and "Sign in to the App Store"
+fn is_not_signed_in(output: &str) -> bool {
+    let o = output.to_lowercase();
+    o.contains("not signed in") || o.contains("sign in")
+}
+
+// e.g. "497799835  Xcode  (15.0)"
+fn parse_list<S>(list: S) -> Vec<u64>
+where
+    S: AsRef<str>,
+{
+    list.as_ref()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter_map(|id| id.parse::<u64>().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_id() {
+        let mas = Mas { id: 497799835 };
+        assert_eq!(mas.name(), "mas install 497799835");
+    }
+
+    #[test]
+    fn parse_list_with_apps() {
+        let list = "  497799835  Xcode      (15.0)\n1333542190  1Password 7 (7.9.11)\nNo installed apps found\n";
+        assert_eq!(parse_list(list), vec![497799835, 1333542190]);
+    }
+
+    #[test]
+    fn detect_not_signed_in() {
+        assert!(is_not_signed_in("Error: Not signed in"));
+        assert!(is_not_signed_in(
+            "Warning: Sign in to the App Store to install apps"
+        ));
+        assert!(!is_not_signed_in("Error: No apps found with identifier"));
+    }
+}
@@ -1,42 +1,385 @@
+mod alternatives;
+mod apply;
+mod assert;
+mod aur;
+mod cargo;
 mod command;
+mod config_edit;
+mod container_image;
+mod defaults;
 mod file;
+mod firewall;
+mod flatpak;
+mod gitconfig;
+mod github_release;
+mod gpg_key;
+mod group;
+mod homebrew;
+mod hostname;
+mod include;
+mod ini;
+mod instance;
+mod interval;
+mod launchd;
+mod locale;
+mod login_shell;
+mod manual;
+mod mas;
+mod mount;
+mod package_repo;
+mod pip;
+mod script;
+mod ssh_config;
+mod ssh_keygen;
+mod stow;
+mod sysctl;
+mod udev;
+mod vscode;
+mod wait_for;
+mod windows_package;
+mod xcode_clt;
 
-use std::{convert::TryFrom, fmt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
 
 use colored::*;
 use serde::{Deserialize, Serialize};
+use tera::Context;
 use thiserror::Error as ThisError;
 
+use super::template;
+
+pub use command::{
+    finish_capture, finish_output, is_past_deadline, set_deadline, set_diff, start_capture,
+    start_output, start_streaming,
+};
+
+use alternatives::Alternatives;
+use apply::Apply;
+use assert::Assert;
+use aur::Aur;
+use cargo::Cargo;
 use command::Command;
+use config_edit::ConfigEdit;
+use container_image::ContainerImage;
+use defaults::Defaults;
 use file::File;
+use firewall::Firewall;
+use flatpak::Flatpak;
+use gitconfig::Gitconfig;
+use github_release::GithubRelease;
+use gpg_key::GpgKey;
+use group::Group;
+use homebrew::Homebrew;
+use hostname::Hostname;
+use include::Include;
+use instance::Instance;
+use launchd::Launchd;
+use locale::Locale;
+use login_shell::LoginShell;
+use manual::Manual;
+use mas::Mas;
+use mount::Mount;
+use package_repo::PackageRepo;
+use pip::Pip;
+use script::Script;
+use ssh_config::SshConfig;
+use ssh_keygen::SshKeygen;
+use stow::Stow;
+use sysctl::Sysctl;
+use udev::Udev;
+use vscode::Vscode;
+use wait_for::WaitFor;
+use windows_package::WindowsPackage;
+use xcode_clt::XcodeClt;
 
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error(transparent)]
+    AlternativesJob {
+        #[from]
+        source: alternatives::Error,
+    },
+    #[error(transparent)]
+    ApplyJob {
+        #[from]
+        source: apply::Error,
+    },
+    #[error(transparent)]
+    AssertJob {
+        #[from]
+        source: assert::Error,
+    },
+    #[error(transparent)]
+    AurJob {
+        #[from]
+        source: aur::Error,
+    },
+    #[error(transparent)]
+    CargoJob {
+        #[from]
+        source: cargo::Error,
+    },
     #[error(transparent)]
     CommandJob {
         #[from]
         source: command::Error,
     },
     #[error(transparent)]
+    ConfigEditJob {
+        #[from]
+        source: config_edit::Error,
+    },
+    #[error(transparent)]
+    ContainerImageJob {
+        #[from]
+        source: container_image::Error,
+    },
+    #[error(transparent)]
+    DefaultsJob {
+        #[from]
+        source: defaults::Error,
+    },
+    #[error(transparent)]
     FileJob {
         #[from]
         source: file::Error,
     },
     #[error(transparent)]
+    FirewallJob {
+        #[from]
+        source: firewall::Error,
+    },
+    #[error(transparent)]
+    FlatpakJob {
+        #[from]
+        source: flatpak::Error,
+    },
+    #[error(transparent)]
+    GitconfigJob {
+        #[from]
+        source: gitconfig::Error,
+    },
+    #[error(transparent)]
+    GithubReleaseJob {
+        #[from]
+        source: github_release::Error,
+    },
+    #[error(transparent)]
+    GpgKeyJob {
+        #[from]
+        source: gpg_key::Error,
+    },
+    #[error(transparent)]
+    GroupJob {
+        #[from]
+        source: group::Error,
+    },
+    #[error(transparent)]
+    HomebrewJob {
+        #[from]
+        source: homebrew::Error,
+    },
+    #[error(transparent)]
+    HostnameJob {
+        #[from]
+        source: hostname::Error,
+    },
+    #[error(transparent)]
+    IncludeJob {
+        #[from]
+        source: include::Error,
+    },
+    #[error(transparent)]
+    InstanceJob {
+        #[from]
+        source: instance::Error,
+    },
+    #[error(transparent)]
+    Interval {
+        #[from]
+        source: interval::Error,
+    },
+    #[error(transparent)]
+    LaunchdJob {
+        #[from]
+        source: launchd::Error,
+    },
+    #[error(transparent)]
+    LocaleJob {
+        #[from]
+        source: locale::Error,
+    },
+    #[error(transparent)]
+    LoginShellJob {
+        #[from]
+        source: login_shell::Error,
+    },
+    #[error(transparent)]
+    ManualJob {
+        #[from]
+        source: manual::Error,
+    },
+    #[error(transparent)]
+    MasJob {
+        #[from]
+        source: mas::Error,
+    },
+    #[error(transparent)]
+    MountJob {
+        #[from]
+        source: mount::Error,
+    },
+    #[error(transparent)]
+    PackageRepoJob {
+        #[from]
+        source: package_repo::Error,
+    },
+    #[error(transparent)]
     ParseToml {
         #[from]
         source: toml::de::Error,
     },
+    #[error(transparent)]
+    PipJob {
+        #[from]
+        source: pip::Error,
+    },
+    #[error(transparent)]
+    ScriptJob {
+        #[from]
+        source: script::Error,
+    },
+    #[error(transparent)]
+    SshConfigJob {
+        #[from]
+        source: ssh_config::Error,
+    },
+    #[error(transparent)]
+    SshKeygenJob {
+        #[from]
+        source: ssh_keygen::Error,
+    },
+    #[error(transparent)]
+    StowJob {
+        #[from]
+        source: stow::Error,
+    },
+    #[error(transparent)]
+    SysctlJob {
+        #[from]
+        source: sysctl::Error,
+    },
+    #[error("timed out after {}", humantime::format_duration(*timeout))]
+    Timeout { timeout: Duration },
+    #[error(transparent)]
+    UdevJob {
+        #[from]
+        source: udev::Error,
+    },
+    #[error(transparent)]
+    VscodeJob {
+        #[from]
+        source: vscode::Error,
+    },
+    #[error(transparent)]
+    WaitForJob {
+        #[from]
+        source: wait_for::Error,
+    },
+    #[error("unable to evaluate when = {:?}: {}", expression, source)]
+    When {
+        expression: String,
+        source: tera::Error,
+    },
+    #[error(transparent)]
+    WindowsPackageJob {
+        #[from]
+        source: windows_package::Error,
+    },
+    #[error(transparent)]
+    XcodeCltJob {
+        #[from]
+        source: xcode_clt::Error,
+    },
     #[allow(dead_code)] // TODO: fake test-only errors should not be here
     #[error("fake test-only error")]
     SomethingBad,
 }
 
 pub trait Execute {
+    // like `execute()`, but only reports what would change, e.g. as WouldChange,
+    // or Unchecked when that cannot be known without running
+    fn check(&self) -> Result {
+        Ok(Status::Unchecked(String::from("not checked")))
+    }
+    // jobs to wait for, if they exist, whether or not they succeed
+    fn after(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // handlers to run at the end, if this job ran, whether or not it succeeded
+    fn always(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // shown under the name when the job starts
+    fn description(&self) -> Option<String> {
+        None
+    }
     fn execute(&self) -> Result;
+    fn ignore_errors(&self) -> bool {
+        false
+    }
+    // may remove or overwrite things, so asks first when confirming
+    fn is_destructive(&self) -> bool {
+        false
+    }
+    // the "type" field in the config, e.g. "command"
+    fn kind(&self) -> &'static str;
+    // jobs with the same lock never run at the same time
+    fn lock(&self) -> Option<String> {
+        None
+    }
     fn name(&self) -> String;
     fn needs(&self) -> Vec<String>;
-    fn when(&self) -> bool;
+    // e.g. ["linux", "macos"], empty for every OS
+    fn os(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // handlers to run at the end, if this job Changed something
+    fn notify(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // e.g. fields that nothing would honour, so the config should not be run as it is
+    fn problems(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // where to store the result for later `when` expressions
+    fn register(&self) -> Option<String> {
+        None
+    }
+    // handlers to run at the end, if this job failed
+    fn rescue(&self) -> Vec<String> {
+        Vec::new()
+    }
+    // how many more times to execute a failed job
+    fn retries(&self) -> u32 {
+        0
+    }
+    fn retry_delay(&self) -> Duration {
+        Duration::from_millis(0)
+    }
+    fn tags(&self) -> Vec<String> {
+        Vec::new()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+    fn when(&self, context: &Context) -> std::result::Result<bool, Error>;
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -48,54 +391,668 @@ pub struct Job {
     #[serde(flatten)]
     spec: Spec,
 }
-impl Execute for Job {
-    fn execute(&self) -> Result {
+impl Job {
+    // as a table of the main.toml file, after templates are rendered
+    pub fn to_toml(&self) -> std::result::Result<String, toml::ser::Error> {
+        let mut value = toml::Value::try_from(self)?;
+        if let Some(table) = value.as_table_mut() {
+            // otherwise the name of the Rust struct, as each struct is tagged with "type"
+            table.insert(
+                String::from("type"),
+                toml::Value::String(String::from(self.kind())),
+            );
+        }
+        toml::to_string_pretty(&value)
+    }
+
+    // changes whenever the rendered job changes, or any file that it reads or manages changes,
+    // or None when it manages more than files, as what it manages may change without a trace
+    pub fn fingerprint(&self) -> Option<String> {
+        let targets = self.spec.targets()?;
+        let mut text = format!("{:?}", self);
+        for input in self.spec.inputs() {
+            describe_tree(input, &mut text);
+        }
+        for target in targets {
+            let meta = fs::symlink_metadata(&target).ok();
+            text.push_str(&describe(&target, meta.as_ref()));
+        }
+        Some(format!("{:016x}", fnv1a(text.as_bytes())))
+    }
+
+    // nothing, for jobs that would not run on this machine anyway
+    pub fn warnings(&self) -> Vec<String> {
+        if !is_os_supported(&self.os()) || self.metadata.when == When::Bool(false) {
+            return Vec::new();
+        }
+        self.spec.warnings()
+    }
+
+    // NoChange if the job completed within its `interval`
+    fn throttled(&self) -> std::result::Result<Option<Status>, Error> {
+        if let Some(i) = self.metadata.interval {
+            if let Some(e) = interval::ran_within(&self.name(), i)? {
+                return Ok(Some(Status::NoChange(format!(
+                    "ran {} ago",
+                    humantime::format_duration(e)
+                ))));
+            }
+        }
+        Ok(None)
+    }
+
+    // helper commands run via sudo or doas, and with `environment`, until the scope is dropped,
+    // after which they run as they did before, e.g. for the rest of a group
+    fn enter(&self) -> std::result::Result<command::Scope, Error> {
+        let scope = command::Scope::enter();
+        if self.metadata.r#become.unwrap_or(false) {
+            command::set_become(Some(command::become_prefix(
+                self.metadata.become_user.as_deref(),
+            )?));
+        }
+        if let Some(e) = &self.metadata.environment {
+            command::extend_environment(e.clone().into_iter().collect());
+        }
+        Ok(scope)
+    }
+
+    // guards run like the job would, e.g. with `become` and `environment`
+    fn execute_guarded(&self) -> Result {
+        if let Some(g) = &self.metadata.only_if {
+            if !script::succeeds(g)? {
+                return Ok(Status::Skipped);
+            }
+        }
+        if let Some(g) = &self.metadata.unless {
+            if script::succeeds(g)? {
+                return Ok(Status::Skipped);
+            }
+        }
+        self.execute_spec()
+    }
+
+    fn execute_spec(&self) -> Result {
         match &self.spec {
+            Spec::Alternatives(j) => j
+                .execute()
+                .map_err(|e| Error::AlternativesJob { source: e }),
+            Spec::Apply(j) => j.execute().map_err(|e| Error::ApplyJob { source: e }),
+            Spec::Assert(j) => j.execute().map_err(|e| Error::AssertJob { source: e }),
+            Spec::Aur(j) => j.execute().map_err(|e| Error::AurJob { source: e }),
+            Spec::Cargo(j) => j.execute().map_err(|e| Error::CargoJob { source: e }),
             Spec::Command(j) => j.execute().map_err(|e| Error::CommandJob { source: e }),
+            Spec::ConfigEdit(j) => j.execute().map_err(|e| Error::ConfigEditJob { source: e }),
+            Spec::ContainerImage(j) => j
+                .execute()
+                .map_err(|e| Error::ContainerImageJob { source: e }),
+            Spec::Defaults(j) => j.execute().map_err(|e| Error::DefaultsJob { source: e }),
             Spec::File(j) => j.execute().map_err(|e| Error::FileJob { source: e }),
+            Spec::Firewall(j) => j.execute().map_err(|e| Error::FirewallJob { source: e }),
+            Spec::Flatpak(j) => j.execute().map_err(|e| Error::FlatpakJob { source: e }),
+            Spec::Gitconfig(j) => j.execute().map_err(|e| Error::GitconfigJob { source: e }),
+            Spec::GithubRelease(j) => j
+                .execute()
+                .map_err(|e| Error::GithubReleaseJob { source: e }),
+            Spec::GpgKey(j) => j.execute().map_err(|e| Error::GpgKeyJob { source: e }),
+            Spec::Group(j) => j.execute().map_err(|e| Error::GroupJob { source: e }),
+            Spec::Homebrew(j) => j.execute().map_err(|e| Error::HomebrewJob { source: e }),
+            Spec::Hostname(j) => j.execute().map_err(|e| Error::HostnameJob { source: e }),
+            Spec::Include(j) => j.execute().map_err(|e| Error::IncludeJob { source: e }),
+            Spec::Instance(j) => j.execute().map_err(|e| Error::InstanceJob { source: e }),
+            Spec::Launchd(j) => j.execute().map_err(|e| Error::LaunchdJob { source: e }),
+            Spec::Locale(j) => j.execute().map_err(|e| Error::LocaleJob { source: e }),
+            Spec::LoginShell(j) => j.execute().map_err(|e| Error::LoginShellJob { source: e }),
+            Spec::Manual(j) => j.execute().map_err(|e| Error::ManualJob { source: e }),
+            Spec::Mas(j) => j.execute().map_err(|e| Error::MasJob { source: e }),
+            Spec::Mount(j) => j.execute().map_err(|e| Error::MountJob { source: e }),
+            Spec::PackageRepo(j) => j.execute().map_err(|e| Error::PackageRepoJob { source: e }),
+            Spec::Pip(j) => j.execute().map_err(|e| Error::PipJob { source: e }),
+            Spec::Script(j) => j.execute().map_err(|e| Error::ScriptJob { source: e }),
+            Spec::SshConfig(j) => j.execute().map_err(|e| Error::SshConfigJob { source: e }),
+            Spec::SshKeygen(j) => j.execute().map_err(|e| Error::SshKeygenJob { source: e }),
+            Spec::Stow(j) => j.execute().map_err(|e| Error::StowJob { source: e }),
+            Spec::Sysctl(j) => j.execute().map_err(|e| Error::SysctlJob { source: e }),
+            Spec::Udev(j) => j.execute().map_err(|e| Error::UdevJob { source: e }),
+            Spec::Vscode(j) => j.execute().map_err(|e| Error::VscodeJob { source: e }),
+            Spec::WaitFor(j) => j.execute().map_err(|e| Error::WaitForJob { source: e }),
+            Spec::WindowsPackage(j) => j
+                .execute()
+                .map_err(|e| Error::WindowsPackageJob { source: e }),
+            Spec::XcodeClt(j) => j.execute().map_err(|e| Error::XcodeCltJob { source: e }),
+        }
+    }
+}
+impl Execute for Job {
+    fn after(&self) -> Vec<String> {
+        self.metadata.after.clone().unwrap_or_default()
+    }
+    fn always(&self) -> Vec<String> {
+        self.metadata.always.clone().unwrap_or_default()
+    }
+    fn description(&self) -> Option<String> {
+        self.metadata.description.clone()
+    }
+    // every job type queries what is there, and stops short of changing it when checking
+    fn check(&self) -> Result {
+        if let Some(s) = self.throttled()? {
+            return Ok(s);
         }
+        let scope = self.enter()?;
+        command::set_check(true);
+        let result = self.execute_guarded();
+        drop(scope);
+        match result {
+            Ok(Status::Changed(from, to)) => Ok(Status::WouldChange(from, to)),
+            r => r,
+        }
+    }
+    fn execute(&self) -> Result {
+        if let Some(s) = self.throttled()? {
+            return Ok(s);
+        }
+        let scope = self.enter()?;
+        let result = self.execute_guarded();
+        drop(scope);
+        if self.metadata.interval.is_some() && is_result_done(&result) {
+            interval::record(&self.name())?;
+        }
+        result
+    }
+    fn ignore_errors(&self) -> bool {
+        self.metadata.ignore_errors.unwrap_or(false)
+    }
+    fn is_destructive(&self) -> bool {
+        self.spec.is_destructive()
+    }
+    fn kind(&self) -> &'static str {
+        self.spec.kind()
+    }
+    fn lock(&self) -> Option<String> {
+        self.metadata.lock.clone()
     }
     fn name(&self) -> String {
         match &self.spec {
+            Spec::Alternatives(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Apply(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Assert(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Aur(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Cargo(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
             Spec::Command(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::ConfigEdit(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::ContainerImage(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Defaults(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
             Spec::File(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Firewall(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Flatpak(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Gitconfig(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::GithubRelease(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::GpgKey(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Group(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Homebrew(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Hostname(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Include(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Instance(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Launchd(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Locale(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::LoginShell(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Manual(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Mas(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Mount(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::PackageRepo(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Pip(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Script(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::SshConfig(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::SshKeygen(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Stow(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Sysctl(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Udev(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::Vscode(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::WaitFor(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::WindowsPackage(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
+            Spec::XcodeClt(j) => self.metadata.name.clone().unwrap_or_else(|| j.name()),
         }
     }
     fn needs(&self) -> Vec<String> {
         self.metadata.needs.clone().unwrap_or_default()
     }
-    fn when(&self) -> bool {
-        self.metadata.when
+    fn notify(&self) -> Vec<String> {
+        self.metadata.notify.clone().unwrap_or_default()
+    }
+    fn os(&self) -> Vec<String> {
+        self.metadata.os.clone().unwrap_or_default()
+    }
+    fn problems(&self) -> Vec<String> {
+        match &self.spec {
+            Spec::Group(g) => g.problems(),
+            _ => Vec::new(),
+        }
+    }
+    fn register(&self) -> Option<String> {
+        self.metadata.register.clone()
+    }
+    fn rescue(&self) -> Vec<String> {
+        self.metadata.rescue.clone().unwrap_or_default()
+    }
+    fn retries(&self) -> u32 {
+        self.metadata.retries.unwrap_or(0)
+    }
+    fn retry_delay(&self) -> Duration {
+        Duration::from_millis(self.metadata.retry_delay_ms.unwrap_or(0))
+    }
+    fn tags(&self) -> Vec<String> {
+        self.metadata.tags.clone().unwrap_or_default()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.metadata.timeout
+    }
+    fn when(&self, context: &Context) -> std::result::Result<bool, Error> {
+        self.metadata.when.evaluate(context)
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Metadata {
+    after: Option<Vec<String>>,
+    always: Option<Vec<String>>,
+    r#become: Option<bool>,
+    become_user: Option<String>,
+    description: Option<String>,
+    environment: Option<BTreeMap<String, String>>,
+    ignore_errors: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    interval: Option<Duration>,
+    lock: Option<String>,
+    matrix: Option<BTreeMap<String, Vec<serde_json::Value>>>,
     name: Option<String>,
     needs: Option<Vec<String>>,
-    #[serde(default = "default_when_value")]
-    when: bool,
+    notify: Option<Vec<String>>,
+    only_if: Option<String>,
+    os: Option<Vec<String>>,
+    register: Option<String>,
+    rescue: Option<Vec<String>>,
+    retries: Option<u32>,
+    retry_delay_ms: Option<u64>,
+    tags: Option<Vec<String>>,
+    #[serde(default, with = "humantime_serde")]
+    timeout: Option<Duration>,
+    unless: Option<String>,
+    #[serde(default)]
+    when: When,
+    with_items: Option<Vec<serde_json::Value>>,
 }
-impl Default for Metadata {
-    fn default() -> Self {
-        Self {
-            name: None,
-            needs: None,
-            when: true,
+
+// either a plain boolean, e.g. from a template expression,
+// or an expression that is evaluated just before the job would run
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum When {
+    Bool(bool),
+    Expression(String),
+}
+impl When {
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Bool(true), w) | (w, Self::Bool(true)) => w,
+            (Self::Bool(false), _) | (_, Self::Bool(false)) => Self::Bool(false),
+            (Self::Expression(a), Self::Expression(b)) => {
+                Self::Expression(format!("({}) and ({})", a, b))
+            }
+        }
+    }
+
+    pub fn evaluate(&self, context: &Context) -> std::result::Result<bool, Error> {
+        match self {
+            Self::Bool(b) => Ok(*b),
+            Self::Expression(e) => template::evaluate(e, context).map_err(|err| Error::When {
+                expression: e.clone(),
+                source: err,
+            }),
         }
     }
 }
+impl Default for When {
+    fn default() -> Self {
+        Self::Bool(true)
+    }
+}
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
-#[serde(rename_all = "lowercase", tag = "type")]
+#[serde(rename_all = "snake_case", tag = "type")]
 pub enum Spec {
+    Alternatives(Alternatives),
+    Apply(Apply),
+    Assert(Assert),
+    Aur(Aur),
+    Cargo(Cargo),
     Command(Command),
+    ConfigEdit(ConfigEdit),
+    ContainerImage(ContainerImage),
+    Defaults(Defaults),
     File(File),
+    Firewall(Firewall),
+    Flatpak(Flatpak),
+    Gitconfig(Gitconfig),
+    GithubRelease(GithubRelease),
+    GpgKey(GpgKey),
+    Group(Group),
+    Homebrew(Homebrew),
+    Hostname(Hostname),
+    Include(Include),
+    Instance(Instance),
+    Launchd(Launchd),
+    Locale(Locale),
+    LoginShell(LoginShell),
+    Manual(Manual),
+    Mas(Mas),
+    Mount(Mount),
+    PackageRepo(PackageRepo),
+    Pip(Pip),
+    Script(Script),
+    SshConfig(SshConfig),
+    SshKeygen(SshKeygen),
+    Stow(Stow),
+    Sysctl(Sysctl),
+    Udev(Udev),
+    Vscode(Vscode),
+    WaitFor(WaitFor),
+    WindowsPackage(WindowsPackage),
+    XcodeClt(XcodeClt),
+}
+impl Spec {
+    // files that the job reads, or whose presence decides whether it runs
+    pub fn inputs(&self) -> Vec<&Path> {
+        if let Self::Group(j) = self {
+            return j.jobs.iter().flat_map(|c| c.spec.inputs()).collect();
+        }
+        let paths = match self {
+            Self::Command(j) => vec![&j.creates, &j.removes],
+            Self::File(j) => vec![&j.src],
+            Self::GpgKey(j) => vec![&j.src],
+            Self::Launchd(j) => vec![&j.src],
+            Self::Script(j) => vec![&j.creates, &j.removes],
+            Self::Udev(j) => vec![&j.src],
+            _ => Vec::new(),
+        };
+        let mut inputs: Vec<&Path> = paths.into_iter().flatten().map(|p| p.as_path()).collect();
+        if let Self::Stow(j) = self {
+            inputs.push(&j.src);
+        }
+        inputs
+    }
+
+    // files that the job manages, or None when it manages more than files,
+    // e.g. packages, which could be removed without changing any files that we know of
+    pub fn targets(&self) -> Option<Vec<PathBuf>> {
+        match self {
+            Self::Command(_) | Self::Script(_) => Some(Vec::new()),
+            Self::ConfigEdit(j) => Some(vec![j.path.clone()]),
+            Self::File(j) => Some(vec![j.path.clone()]),
+            Self::Gitconfig(j) => Some(j.targets()),
+            Self::GpgKey(j) => j.keyring.clone().map(|k| vec![k]),
+            Self::Group(j) => j
+                .jobs
+                .iter()
+                .map(|c| c.spec.targets())
+                .collect::<Option<Vec<_>>>()
+                .map(|t| t.into_iter().flatten().collect()),
+            Self::SshConfig(j) => Some(j.targets()),
+            Self::SshKeygen(j) => Some(j.targets()),
+            Self::Stow(j) => Some(j.targets()),
+            Self::Udev(j) => Some(j.targets()),
+            _ => None,
+        }
+    }
+
+    // problems with this machine that would stop the job from working, see `tuning doctor`
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut executable = |cmd: &str| {
+            if which::which(cmd).is_err() {
+                warnings.push(format!("{:?} is not an executable in PATH", cmd));
+            }
+        };
+        match self {
+            Self::Command(j) => executable(&j.command),
+            Self::Script(j) => executable(&j.shell()),
+            _ => {}
+        }
+        let sources = match self {
+            Self::File(j)
+                if j.state == file::FileState::Hard || j.state == file::FileState::Link =>
+            {
+                j.src.iter().map(|s| s.as_path()).collect()
+            }
+            Self::Stow(j) => vec![j.src.as_path()],
+            _ => Vec::new(),
+        };
+        for src in sources {
+            if !src.exists() {
+                warnings.push(format!("src {} does not exist", src.display()));
+            }
+        }
+        let targets = match self {
+            Self::File(j) => vec![j.path.clone()],
+            Self::Stow(j) => vec![j.dest.join("x")],
+            _ => Vec::new(),
+        };
+        for target in targets {
+            if let Some(dir) = file::unwritable_dir(&target) {
+                warnings.push(format!("unable to write to {}", dir.display()));
+            }
+        }
+        if let Self::Group(j) = self {
+            for child in &j.jobs {
+                warnings.extend(
+                    child
+                        .warnings()
+                        .into_iter()
+                        .map(|w| format!("{:?}: {}", child.name(), w)),
+                );
+            }
+        }
+        warnings
+    }
+
+    // removes things, or overwrites whatever is in the way
+    pub fn is_destructive(&self) -> bool {
+        match self {
+            Self::Command(j) => j.removes.is_some(),
+            Self::File(j) => j.force.unwrap_or(false),
+            Self::Stow(j) => j.force.unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    // the "type" field in the config, e.g. "command"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Alternatives(_) => "alternatives",
+            Self::Apply(_) => "apply",
+            Self::Assert(_) => "assert",
+            Self::Aur(_) => "aur",
+            Self::Cargo(_) => "cargo",
+            Self::Command(_) => "command",
+            Self::ConfigEdit(_) => "config_edit",
+            Self::ContainerImage(_) => "container_image",
+            Self::Defaults(_) => "defaults",
+            Self::File(_) => "file",
+            Self::Firewall(_) => "firewall",
+            Self::Flatpak(_) => "flatpak",
+            Self::Gitconfig(_) => "gitconfig",
+            Self::GithubRelease(_) => "github_release",
+            Self::GpgKey(_) => "gpg_key",
+            Self::Group(_) => "group",
+            Self::Homebrew(_) => "homebrew",
+            Self::Hostname(_) => "hostname",
+            Self::Include(_) => "include",
+            Self::Instance(_) => "instance",
+            Self::Launchd(_) => "launchd",
+            Self::Locale(_) => "locale",
+            Self::LoginShell(_) => "login_shell",
+            Self::Manual(_) => "manual",
+            Self::Mas(_) => "mas",
+            Self::Mount(_) => "mount",
+            Self::PackageRepo(_) => "package_repo",
+            Self::Pip(_) => "pip",
+            Self::Script(_) => "script",
+            Self::SshConfig(_) => "ssh_config",
+            Self::SshKeygen(_) => "ssh_keygen",
+            Self::Stow(_) => "stow",
+            Self::Sysctl(_) => "sysctl",
+            Self::Udev(_) => "udev",
+            Self::Vscode(_) => "vscode",
+            Self::WaitFor(_) => "wait_for",
+            Self::WindowsPackage(_) => "windows_package",
+            Self::XcodeClt(_) => "xcode_clt",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct Main {
+    // only run after `jobs`, and only if a job that notifies them Changed something
+    #[serde(default)]
+    pub handlers: Vec<Job>,
     pub jobs: Vec<Job>,
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+// how to run the jobs, rather than what they do
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Settings {
+    // check first, then ask once whether to apply what would change
+    #[serde(default)]
+    pub ask: bool,
+    // ask before executing each destructive job
+    #[serde(default)]
+    pub confirm: bool,
+    // the most jobs with each tag or type that can run at the same time
+    #[serde(default)]
+    pub limits: HashMap<String, usize>,
+    // dispatch jobs in the order they are configured, rather than as soon as they can run
+    #[serde(default)]
+    pub ordered: bool,
+    // only print jobs that changed, failed, or were skipped, and count the rest
+    #[serde(default)]
+    pub quiet: bool,
+    // how many jobs can run at the same time, defaults to the number of CPUs
+    pub threads: Option<usize>,
+    // stop starting new jobs after this long, and time out jobs that are still running
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+}
+impl Main {
+    // replaces each job that has `with_items` with one job per item,
+    // or that has `matrix` with one job per combination of its values,
+    // that `render` returns given the index of the job, "item" or "matrix", and the value,
+    // and jobs that need it then need all of those jobs instead
+    pub fn expand_items<F, E>(self, mut render: F) -> std::result::Result<Self, E>
+    where
+        F: FnMut(usize, &str, &serde_json::Value) -> std::result::Result<Job, E>,
+    {
+        let mut jobs = Vec::<Job>::new();
+        let mut aliases = HashMap::<String, Vec<String>>::new();
+        for (index, mut job) in self.jobs.into_iter().enumerate() {
+            let matrix = job.metadata.matrix.take();
+            let (variable, items) = match (job.metadata.with_items.take(), matrix) {
+                (Some(i), _) => ("item", i),
+                (None, Some(m)) => ("matrix", matrix_combinations(&m)),
+                (None, None) => {
+                    jobs.push(job);
+                    continue;
+                }
+            };
+            let name = job.name();
+            let mut names = Vec::<String>::new();
+            for item in &items {
+                let mut child = render(index, variable, item)?;
+                // every job needs a distinct name
+                if child.name() == name {
+                    child.metadata.name = Some(format!("{}: {}", name, item_display(item)));
+                }
+                names.push(child.name());
+                jobs.push(child);
+            }
+            aliases.insert(name, names);
+        }
+
+        repoint_needs(&mut jobs, &aliases);
+        Ok(Self {
+            handlers: self.handlers,
+            jobs,
+            settings: self.settings,
+        })
+    }
+
+    // replaces each `include` job with the jobs that `load` returns for it,
+    // and handlers from included files join our own
+    pub fn splice_includes<F, E>(self, mut load: F) -> std::result::Result<Self, E>
+    where
+        F: FnMut(&Include) -> std::result::Result<Main, E>,
+    {
+        self.splice(|spec| match spec {
+            Spec::Include(i) => Some(load(i)),
+            _ => None,
+        })
+    }
+
+    // replaces each `instance` job with the jobs that `load` returns for it
+    pub fn splice_instances<F, E>(self, mut load: F) -> std::result::Result<Self, E>
+    where
+        F: FnMut(&Instance) -> std::result::Result<Main, E>,
+    {
+        self.splice(|spec| match spec {
+            Spec::Instance(i) => Some(load(i)),
+            _ => None,
+        })
+    }
+
+    // replaces each job that `load` returns jobs for,
+    // which inherit the replaced job's `needs`, `tags` and `when`,
+    // and jobs that need the replaced job then need all of those jobs instead
+    fn splice<F, E>(self, mut load: F) -> std::result::Result<Self, E>
+    where
+        F: FnMut(&Spec) -> Option<std::result::Result<Main, E>>,
+    {
+        let mut handlers = self.handlers;
+        let mut jobs = Vec::<Job>::new();
+        let mut aliases = HashMap::<String, Vec<String>>::new();
+        for job in self.jobs {
+            let loaded = match load(&job.spec) {
+                Some(l) => l?,
+                None => {
+                    jobs.push(job);
+                    continue;
+                }
+            };
+            let mut names = Vec::<String>::new();
+            handlers.extend(loaded.handlers);
+            for mut child in loaded.jobs {
+                child.metadata.when = child.metadata.when.and(job.metadata.when.clone());
+                if let Some(needs) = &job.metadata.needs {
+                    let mut n = child.metadata.needs.take().unwrap_or_default();
+                    n.extend(needs.iter().cloned());
+                    child.metadata.needs = Some(n);
+                }
+                if let Some(tags) = &job.metadata.tags {
+                    let mut t = child.metadata.tags.take().unwrap_or_default();
+                    t.extend(tags.iter().cloned());
+                    child.metadata.tags = Some(t);
+                }
+                names.push(child.name());
+                jobs.push(child);
+            }
+            aliases.insert(job.name(), names);
+        }
+
+        repoint_needs(&mut jobs, &aliases);
+        Ok(Self {
+            handlers,
+            jobs,
+            settings: self.settings,
+        })
+    }
 }
 impl TryFrom<&str> for Main {
     type Error = Error;
@@ -104,38 +1061,255 @@ impl TryFrom<&str> for Main {
     }
 }
 
-pub type Result = std::result::Result<Status, Error>;
-pub fn result_display(result: &Result) -> String {
-    match result {
-        Ok(s) => format!("{}", s),
-        Err(e) => format!("{:#?}", e).red().to_string(),
+// keys in a config that nothing reads, e.g. a typo like "nedds",
+// which would otherwise be ignored
+pub fn unknown_fields(input: &str) -> std::result::Result<Vec<String>, Error> {
+    let raw: toml::Value = toml::from_str(input).map_err(|e| Error::ParseToml { source: e })?;
+    let mut unknown = Vec::new();
+    let main = match raw.as_table() {
+        Some(m) => m,
+        None => return Ok(unknown),
+    };
+    for key in main.keys() {
+        if !["handlers", "jobs", "settings", "templates"].contains(&key.as_str()) {
+            unknown.push(format!("unknown field {:?}", key));
+        }
     }
-}
-pub fn is_result_settled(result: &Result) -> bool {
-    match result {
-        Ok(s) => match s {
-            Status::Blocked | Status::Skipped => true,
-            _ => s.is_done(),
-        },
-        Err(_) => true,
+    if let Some(Ok(settings)) = main
+        .get("settings")
+        .map(|s| s.clone().try_into::<Settings>())
+    {
+        for key in extra_keys(&main["settings"], &settings) {
+            unknown.push(format!("settings: unknown field {:?}", key));
+        }
     }
-}
-pub fn is_result_done(result: &Result) -> bool {
-    match result {
-        Ok(s) => s.is_done(),
-        Err(_) => false,
+    for list in ["handlers", "jobs"].iter() {
+        for table in main
+            .get(*list)
+            .and_then(|j| j.as_array())
+            .into_iter()
+            .flatten()
+        {
+            // jobs that do not parse at all are reported elsewhere
+            if let Ok(job) = table.clone().try_into::<Job>() {
+                for key in extra_keys(table, &job) {
+                    unknown.push(format!("job {:?}: unknown field {:?}", job.name(), key));
+                }
+            }
+        }
     }
+    Ok(unknown)
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum Status {
+// keys in `raw` that are not there once it is `parsed` and serialized again
+fn extra_keys(raw: &toml::Value, parsed: &impl Serialize) -> Vec<String> {
+    let known = match toml::Value::try_from(parsed) {
+        Ok(toml::Value::Table(t)) => t,
+        _ => return Vec::new(),
+    };
+    raw.as_table()
+        .into_iter()
+        .flatten()
+        .map(|(k, _)| k)
+        // "type" is replaced by the name of the Rust struct, see `Job::to_toml()`
+        .filter(|k| k.as_str() != "type" && !known.contains_key(*k))
+        .cloned()
+        .collect()
+}
+
+// what would tell us that the file changed, without following it if it is a symlink
+fn describe(path: &Path, meta: Option<&fs::Metadata>) -> String {
+    let kind = match meta {
+        Some(m) if m.is_symlink() => "symlink",
+        Some(m) if m.is_dir() => "dir",
+        Some(_) => "file",
+        None => "absent",
+    };
+    let modified = meta
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+    format!(
+        "\n{}: {} {:?} {:?} {:?} {:?}",
+        path.display(),
+        kind,
+        meta.map(|m| m.len()),
+        meta.map(mode),
+        modified,
+        fs::read_link(path).ok()
+    )
+}
+
+// like `describe()`, but following `path` if it is a symlink, and describing everything in it,
+// where symlinks are not followed, e.g. for the `src` of `stow`
+fn describe_tree(path: &Path, text: &mut String) {
+    let meta = fs::metadata(path).ok();
+    text.push_str(&describe(path, meta.as_ref()));
+    if !meta.is_some_and(|m| m.is_dir()) {
+        return;
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for entry in entries {
+        match fs::symlink_metadata(&entry) {
+            Ok(m) if m.is_dir() => describe_tree(&entry, text),
+            m => text.push_str(&describe(&entry, m.ok().as_ref())),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    meta.permissions().mode()
+}
+
+#[cfg(windows)]
+fn mode(meta: &fs::Metadata) -> u32 {
+    u32::from(meta.permissions().readonly())
+}
+
+// 64-bit FNV-1a, which (unlike `DefaultHasher`) is the same for every build,
+// so fingerprints from earlier runs remain comparable
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// e.g. "python", or "os=linux, version=3" for a combination from `matrix`
+fn item_display(item: &serde_json::Value) -> String {
+    match item {
+        serde_json::Value::Object(o) => o
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, item_display(v)))
+            .collect::<Vec<String>>()
+            .join(", "),
+        serde_json::Value::String(s) => s.clone(),
+        _ => item.to_string(),
+    }
+}
+
+// the cartesian product, e.g. {a=[1,2], b=[3]} => [{a=1,b=3}, {a=2,b=3}]
+fn matrix_combinations(
+    matrix: &BTreeMap<String, Vec<serde_json::Value>>,
+) -> Vec<serde_json::Value> {
+    let mut combinations = vec![serde_json::Map::new()];
+    for (key, values) in matrix {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|c| {
+                values.iter().map(move |v| {
+                    let mut c = c.clone();
+                    c.insert(key.clone(), v.clone());
+                    c
+                })
+            })
+            .collect();
+    }
+    combinations
+        .into_iter()
+        .map(serde_json::Value::Object)
+        .collect()
+}
+
+// jobs that need (or run after) one of the `aliases` keys then need all of its jobs instead
+fn repoint_needs(jobs: &mut [Job], aliases: &HashMap<String, Vec<String>>) {
+    let repoint = |names: &mut Vec<String>| {
+        *names = names
+            .iter()
+            .flat_map(|n| aliases.get(n).cloned().unwrap_or_else(|| vec![n.clone()]))
+            .collect();
+    };
+    for job in jobs {
+        if let Some(after) = &mut job.metadata.after {
+            repoint(after);
+        }
+        if let Some(needs) = &mut job.metadata.needs {
+            repoint(needs);
+        }
+    }
+}
+
+// whether we are running on one of `os`, with the same names as `is_os_*` facts
+pub fn is_os_supported(os: &[String]) -> bool {
+    os.is_empty() || os.iter().any(|o| o == std::env::consts::OS)
+}
+
+pub type Result = std::result::Result<Status, Error>;
+pub fn result_display(result: &Result) -> String {
+    match result {
+        Ok(s) => format!("{}", s),
+        Err(e) => format!("{:#?}", e).red().to_string(),
+    }
+}
+// machine-readable status, with the most relevant detail
+pub fn result_status(result: &Result) -> (&'static str, String) {
+    match result {
+        Ok(Status::Blocked) => ("blocked", String::new()),
+        Ok(Status::Changed(_, to)) => ("changed", to.clone()),
+        Ok(Status::Done) => ("done", String::new()),
+        Ok(Status::Ignored(e)) => ("ignored", e.clone()),
+        Ok(Status::InProgress) => ("inprogress", String::new()),
+        Ok(Status::NoChange(s)) => ("nochange", s.clone()),
+        Ok(Status::Pending) => ("pending", String::new()),
+        Ok(Status::Skipped) => ("skipped", String::new()),
+        Ok(Status::Unchecked(s)) => ("unchecked", s.clone()),
+        Ok(Status::WouldChange(_, to)) => ("wouldchange", to.clone()),
+        Err(e) => ("failed", e.to_string()),
+    }
+}
+// what `register` stores, for later `when` expressions
+pub fn result_facts(result: &Result, stdout: &str) -> serde_json::Value {
+    let (status, message) = result_status(result);
+    let stdout = stdout.trim();
+    // e.g. `tool --json` output can be used as structured data
+    let value = serde_json::from_str(stdout)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from(stdout)));
+    serde_json::json!({
+        "changed": status == "changed",
+        "failed": matches!(status, "failed" | "ignored"),
+        "message": message,
+        "skipped": status == "skipped",
+        "status": status,
+        "stdout": stdout,
+        "value": value,
+    })
+}
+
+pub fn is_result_settled(result: &Result) -> bool {
+    match result {
+        Ok(s) => match s {
+            Status::Blocked | Status::Skipped => true,
+            _ => s.is_done(),
+        },
+        Err(_) => true,
+    }
+}
+pub fn is_result_done(result: &Result) -> bool {
+    match result {
+        Ok(s) => s.is_done(),
+        Err(_) => false,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
     Blocked,                 // when "needs" are not yet Done
     Changed(String, String), // more specific kind of Done
     Done,
+    Ignored(String), // failed, but with "ignore_errors", so treated as Done
     InProgress,
-    NoChange(String), // more specific kind of Done
-    Pending,          // when no "needs"; or "needs" are all Done
-    Skipped,          // when "when" is false
+    NoChange(String),            // more specific kind of Done
+    Pending,                     // when no "needs"; or "needs" are all Done
+    Skipped,                     // when "when" is false
+    Unchecked(String),           // when checking, but the job cannot tell without running
+    WouldChange(String, String), // when checking, what Changed would have been
 }
 impl fmt::Display for Status {
     // TODO: should Display include terminal output concerns?
@@ -150,29 +1324,40 @@ impl fmt::Display for Status {
                 to.yellow()
             ),
             Self::Done => write!(f, "{}", "done".blue()),
+            Self::Ignored(e) => write!(f, "{}: {}", "ignored".red(), e.red().dimmed()),
             Self::InProgress => write!(f, "{}", "inprogress".cyan()),
             Self::NoChange(s) => write!(f, "{}: {}", "nochange".green(), s.green()),
             Self::Pending => write!(f, "{}", "pending".white()),
             Self::Skipped => write!(f, "{}", "skipped".blue()),
+            Self::Unchecked(s) => write!(f, "{}: {}", "unchecked".white(), s.white().dimmed()),
+            Self::WouldChange(from, to) => write!(
+                f,
+                "{}: {} => {}",
+                "would change".magenta(),
+                from.magenta().dimmed(),
+                to.magenta()
+            ),
         }
     }
 }
 impl Status {
     pub fn is_done(&self) -> bool {
         match &self {
-            Self::Changed(_, _) | Self::Done | Self::NoChange(_) => true,
+            // so that the jobs that need a checked job are checked too
+            Self::Changed(_, _)
+            | Self::Done
+            | Self::Ignored(_)
+            | Self::NoChange(_)
+            | Self::Unchecked(_)
+            | Self::WouldChange(_, _) => true,
             Self::Blocked | Self::InProgress | Self::Pending | Self::Skipped => false,
         }
     }
 }
 
-fn default_when_value() -> bool {
-    true
-}
-
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{collections::BTreeMap, path::PathBuf};
 
     use file::FileState;
 
@@ -191,6 +1376,7 @@ mod tests {
         let got = Main::try_from(input)?;
 
         let want = Main {
+            handlers: Vec::new(),
             jobs: vec![Job {
                 metadata: Metadata {
                     name: Some(String::from("run something")),
@@ -202,6 +1388,7 @@ mod tests {
                     ..Default::default()
                 }),
             }],
+            settings: Settings::default(),
         };
 
         assert_eq!(got.jobs.len(), 1);
@@ -223,6 +1410,7 @@ mod tests {
         let got = Main::try_from(input)?;
 
         let want = Main {
+            handlers: Vec::new(),
             jobs: vec![Job {
                 metadata: Metadata {
                     name: Some(String::from("mkdir /tmp")),
@@ -235,6 +1423,281 @@ mod tests {
                     state: FileState::Directory,
                 }),
             }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn defaults_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "defaults"
+            domain = "com.apple.dock"
+            key = "tilesize"
+            value = 36
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Defaults(Defaults {
+                    domain: String::from("com.apple.dock"),
+                    key: String::from("tilesize"),
+                    value: defaults::DefaultsValue::Int(36),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cargo_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "cargo"
+            crate = "ripgrep"
+            locked = true
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Cargo(Cargo {
+                    krate: String::from("ripgrep"),
+                    locked: Some(true),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pip_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "pip"
+            manager = "pipx"
+            package = "black"
+            version = "19.10b0"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Pip(Pip {
+                    manager: Some(pip::PipManager::Pipx),
+                    package: String::from("black"),
+                    version: Some(String::from("19.10b0")),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn flatpak_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "flatpak"
+            app = "org.gimp.GIMP"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Flatpak(Flatpak {
+                    app: Some(String::from("org.gimp.GIMP")),
+                    remote: String::from("flathub"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn homebrew_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "homebrew"
+            tap = "homebrew/cask-fonts"
+            cask = "font-fira-code"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Homebrew(Homebrew {
+                    cask: Some(String::from("font-fira-code")),
+                    tap: Some(String::from("homebrew/cask-fonts")),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aur_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "aur"
+            helper = "yay"
+            package = "visual-studio-code-bin"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Aur(Aur {
+                    helper: Some(aur::AurHelper::Yay),
+                    package: String::from("visual-studio-code-bin"),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ssh_keygen_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "ssh_keygen"
+            path = "/home/me/.ssh/id_ed25519"
+            comment = "me@example.com"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::SshKeygen(SshKeygen {
+                    comment: Some(String::from("me@example.com")),
+                    path: PathBuf::from("/home/me/.ssh/id_ed25519"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ssh_config_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "ssh_config"
+            host = "github"
+            options = { HostName = "github.com", User = "git" }
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let mut options = BTreeMap::<String, String>::new();
+        options.insert(String::from("HostName"), String::from("github.com"));
+        options.insert(String::from("User"), String::from("git"));
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::SshConfig(SshConfig {
+                    host: String::from("github"),
+                    options,
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gpg_key_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "gpg_key"
+            fingerprint = "0123456789ABCDEF"
+            url = "https://example.com/key.asc"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::GpgKey(GpgKey {
+                    fingerprint: String::from("0123456789ABCDEF"),
+                    url: Some(String::from("https://example.com/key.asc")),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
         };
 
         assert_eq!(got.jobs.len(), 1);
@@ -243,6 +1706,1398 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vscode_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "vscode"
+            extensions = ["rust-lang.rust"]
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Vscode(Vscode {
+                    extensions: vec![String::from("rust-lang.rust")],
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gitconfig_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "gitconfig"
+            settings = { "user.email" = "me@example.com" }
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let mut settings = BTreeMap::<String, String>::new();
+        settings.insert(String::from("user.email"), String::from("me@example.com"));
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Gitconfig(Gitconfig {
+                    settings,
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hostname_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "hostname"
+            hostname = "my-laptop"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Hostname(Hostname {
+                    hostname: String::from("my-laptop"),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sysctl_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "sysctl"
+            key = "vm.swappiness"
+            value = "10"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Sysctl(Sysctl {
+                    key: String::from("vm.swappiness"),
+                    value: String::from("10"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn locale_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "locale"
+            lang = "en_AU.UTF-8"
+            timezone = "Australia/Sydney"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Locale(Locale {
+                    lang: Some(String::from("en_AU.UTF-8")),
+                    timezone: Some(String::from("Australia/Sydney")),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn firewall_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "firewall"
+            rule = "allow 22/tcp"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Firewall(Firewall {
+                    rule: String::from("allow 22/tcp"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn container_image_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "container_image"
+            image = "alpine:3"
+            state = "absent"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::ContainerImage(ContainerImage {
+                    image: String::from("alpine:3"),
+                    state: Some(container_image::ContainerImageState::Absent),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_edit_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "config_edit"
+            path = "/home/me/.config/Code/User/settings.json"
+            key = ["editor.fontSize"]
+            value = 14
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::ConfigEdit(ConfigEdit {
+                    key: config_edit::ConfigKey::Path(vec![String::from("editor.fontSize")]),
+                    path: PathBuf::from("/home/me/.config/Code/User/settings.json"),
+                    value: Some(toml::Value::Integer(14)),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alternatives_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "alternatives"
+            alternative = "editor"
+            path = "/usr/bin/nvim"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Alternatives(Alternatives {
+                    alternative: String::from("editor"),
+                    path: PathBuf::from("/usr/bin/nvim"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn login_shell_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "login_shell"
+            shell = "/usr/bin/zsh"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::LoginShell(LoginShell {
+                    shell: PathBuf::from("/usr/bin/zsh"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mount_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "mount"
+            device = "UUID=1234"
+            fstype = "ext4"
+            mountpoint = "/mnt/data"
+            options = "noatime"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Mount(Mount {
+                    device: String::from("UUID=1234"),
+                    fstype: String::from("ext4"),
+                    mountpoint: PathBuf::from("/mnt/data"),
+                    options: Some(String::from("noatime")),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn launchd_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "launchd"
+            label = "com.example.backup"
+            src = "/Users/me/.dotfiles/backup.plist"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Launchd(Launchd {
+                    label: String::from("com.example.backup"),
+                    src: Some(PathBuf::from("/Users/me/.dotfiles/backup.plist")),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn windows_package_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "windows_package"
+            manager = "winget"
+            package = "Git.Git"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::WindowsPackage(WindowsPackage {
+                    manager: Some(windows_package::WindowsManager::Winget),
+                    package: String::from("Git.Git"),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn github_release_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "github_release"
+            repo = "BurntSushi/ripgrep"
+            binary = "rg"
+            version = "14.1.0"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::GithubRelease(GithubRelease {
+                    binary: Some(String::from("rg")),
+                    repo: String::from("BurntSushi/ripgrep"),
+                    version: Some(String::from("14.1.0")),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stow_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "stow"
+            src = "/home/me/.dotfiles/zsh"
+            dest = "/home/me"
+            prune = true
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Stow(Stow {
+                    dest: PathBuf::from("/home/me"),
+                    prune: Some(true),
+                    src: PathBuf::from("/home/me/.dotfiles/zsh"),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn package_repo_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "package_repo"
+            kind = "apt"
+            repo = "docker"
+            source = "deb https://download.docker.com/linux/ubuntu noble stable"
+            key_url = "https://download.docker.com/linux/ubuntu/gpg"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::PackageRepo(PackageRepo {
+                    key_url: Some(String::from("https://download.docker.com/linux/ubuntu/gpg")),
+                    kind: Some(package_repo::PackageRepoKind::Apt),
+                    repo: String::from("docker"),
+                    source: Some(String::from(
+                        "deb https://download.docker.com/linux/ubuntu noble stable",
+                    )),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn assert_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "assert"
+            that = true
+            path = "/etc/hosts"
+            msg = "hosts file is missing"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Assert(Assert {
+                    msg: Some(String::from("hosts file is missing")),
+                    path: Some(PathBuf::from("/etc/hosts")),
+                    that: Some(true),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_for_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "wait_for"
+            address = "localhost:5432"
+            max_seconds = 30
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::WaitFor(WaitFor {
+                    address: Some(String::from("localhost:5432")),
+                    max_seconds: Some(30),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn group_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            name = "zsh"
+            type = "group"
+            needs = ["packages"]
+              [[jobs.jobs]]
+              type = "file"
+              path = "/home/me/.zshrc"
+              state = "touch"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata {
+                    name: Some(String::from("zsh")),
+                    needs: Some(vec![String::from("packages")]),
+                    ..Default::default()
+                },
+                spec: Spec::Group(Group {
+                    jobs: vec![Job {
+                        metadata: Metadata::default(),
+                        spec: Spec::File(File {
+                            path: PathBuf::from("/home/me/.zshrc"),
+                            state: FileState::Touch,
+                            ..Default::default()
+                        }),
+                    }],
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn splice_includes_inherits_metadata() -> std::result::Result<(), Error> {
+        let main = Main::try_from(
+            r#"
+            [[jobs]]
+            name = "git"
+            type = "include"
+            path = "git.toml"
+            needs = ["packages"]
+            when = false
+
+            [[jobs]]
+            name = "after git"
+            type = "command"
+            command = "true"
+            needs = ["git"]
+            "#,
+        )?;
+
+        let got = main.splice_includes(|include| {
+            assert_eq!(include.path, PathBuf::from("git.toml"));
+            Main::try_from(
+                r#"
+                [[jobs]]
+                name = "gitconfig"
+                type = "command"
+                command = "true"
+
+                [[jobs]]
+                name = "git-lfs"
+                type = "command"
+                command = "true"
+                needs = ["gitconfig"]
+                "#,
+            )
+        })?;
+
+        let names: Vec<String> = got.jobs.iter().map(|j| j.name()).collect();
+        assert_eq!(names, vec!["gitconfig", "git-lfs", "after git"]);
+        assert!(got
+            .jobs
+            .iter()
+            .take(2)
+            .all(|j| j.metadata.when == When::Bool(false)));
+        assert_eq!(got.jobs[1].needs(), vec!["gitconfig", "packages"]);
+        assert_eq!(got.jobs[2].needs(), vec!["gitconfig", "git-lfs"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "script"
+            creates = "/usr/local/bin/thing"
+            script = """
+            cd /tmp/thing
+            make install
+            """
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Script(Script {
+                    creates: Some(PathBuf::from("/usr/local/bin/thing")),
+                    script: String::from(
+                        "            cd /tmp/thing\n            make install\n            ",
+                    ),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mas_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            name = "Xcode"
+            type = "mas"
+            id = 497799835
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata {
+                    name: Some(String::from("Xcode")),
+                    ..Default::default()
+                },
+                spec: Spec::Mas(Mas { id: 497799835 }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn xcode_clt_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "xcode_clt"
+            max_seconds = 600
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::XcodeClt(XcodeClt {
+                    max_seconds: Some(600),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn udev_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "udev"
+            filename = "70-u2f.rules"
+            rules = 'KERNEL=="hidraw*", SUBSYSTEM=="hidraw", TAG+="uaccess"'
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Udev(Udev {
+                    filename: Some(String::from("70-u2f.rules")),
+                    rules: Some(String::from(
+                        r#"KERNEL=="hidraw*", SUBSYSTEM=="hidraw", TAG+="uaccess""#,
+                    )),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn manual_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "manual"
+            instruction = "log into 1Password"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Manual(Manual {
+                    instruction: String::from("log into 1Password"),
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "apply"
+            repo = "https://example.com/team/dotfiles.git"
+            path = "tuning/main.toml"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let want = Main {
+            handlers: Vec::new(),
+            jobs: vec![Job {
+                metadata: Metadata::default(),
+                spec: Spec::Apply(Apply {
+                    path: Some(PathBuf::from("tuning/main.toml")),
+                    repo: Some(String::from("https://example.com/team/dotfiles.git")),
+                    ..Default::default()
+                }),
+            }],
+            settings: Settings::default(),
+        };
+
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got, want);
+
+        Ok(())
+    }
+
+    #[test]
+    fn when_expression_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            when = "is_os_linux and has_executable(exe='something')"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(
+            got.jobs[0].metadata.when,
+            When::Expression(String::from(
+                "is_os_linux and has_executable(exe='something')"
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn when_evaluates_expressions() -> std::result::Result<(), Error> {
+        let mut context = Context::new();
+        context.insert("is_os_linux", &true);
+        context.insert("is_os_macos", &false);
+
+        assert!(When::Bool(true).evaluate(&context)?);
+        assert!(When::Expression(String::from("is_os_linux")).evaluate(&context)?);
+        assert!(!When::Expression(String::from("is_os_macos")).evaluate(&context)?);
+        assert!(
+            !When::Expression(String::from("has_executable(exe='missing_command')"))
+                .evaluate(&context)?
+        );
+        assert!(!When::Expression(String::from("missing_value")).evaluate(&context)?);
+        assert!(matches!(
+            When::Expression(String::from("is_os_linux and")).evaluate(&context),
+            Err(Error::When { .. })
+        ));
+
+        let both = When::Expression(String::from("is_os_linux"))
+            .and(When::Expression(String::from("is_os_macos")));
+        assert!(!both.evaluate(&context)?);
+        assert_eq!(
+            When::Bool(true).and(When::Expression(String::from("x"))),
+            When::Expression(String::from("x"))
+        );
+        assert_eq!(
+            When::Expression(String::from("x")).and(When::Bool(false)),
+            When::Bool(false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            tags = ["gui", "shell"]
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(
+            got.jobs[0].tags(),
+            vec![String::from("gui"), String::from("shell")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_errors_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            ignore_errors = true
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert!(got.jobs[0].ignore_errors());
+        assert!(Status::Ignored(String::from("oops")).is_done());
+
+        Ok(())
+    }
+
+    #[test]
+    fn retries_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            retries = 3
+            retry_delay_ms = 500
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(got.jobs[0].retries(), 3);
+        assert_eq!(got.jobs[0].retry_delay(), Duration::from_millis(500));
+
+        Ok(())
+    }
+
+    #[test]
+    fn timeout_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            timeout = "1m 30s"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(got.jobs[0].timeout(), Some(Duration::from_secs(90)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn become_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "something"
+            become = true
+            become_user = "postgres"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(got.jobs[0].metadata.r#become, Some(true));
+        assert_eq!(
+            got.jobs[0].metadata.become_user,
+            Some(String::from("postgres"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn result_facts_with_json_stdout() {
+        let got = result_facts(&Ok(Status::Done), "{\"version\": \"1.2.3\"}\n");
+        assert_eq!(got["status"], "done");
+        assert_eq!(got["changed"], false);
+        assert_eq!(got["value"]["version"], "1.2.3");
+
+        let got = result_facts(&Err(Error::SomethingBad), "v1.2.3\n");
+        assert_eq!(got["failed"], true);
+        assert_eq!(got["stdout"], "v1.2.3");
+        assert_eq!(got["value"], "v1.2.3");
+    }
+
+    #[test]
+    fn handlers_and_notify_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[handlers]]
+            name = "reload tmux"
+            type = "command"
+            command = "tmux"
+            argv = ["source-file", "~/.tmux.conf"]
+
+            [[jobs]]
+            type = "file"
+            src = "tmux.conf"
+            path = "~/.tmux.conf"
+            state = "link"
+            notify = ["reload tmux"]
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(got.handlers[0].name(), "reload tmux");
+        assert_eq!(got.jobs[0].notify(), vec![String::from("reload tmux")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn environment_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "apt-get"
+            argv = ["upgrade"]
+            environment = { DEBIAN_FRONTEND = "noninteractive" }
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let mut want = BTreeMap::new();
+        want.insert(
+            String::from("DEBIAN_FRONTEND"),
+            String::from("noninteractive"),
+        );
+        assert_eq!(got.jobs[0].metadata.environment, Some(want));
+
+        Ok(())
+    }
+
+    #[test]
+    fn group_environment_applies_to_every_job() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let path = temp.join("out.txt");
+        let input = format!(
+            r#"
+            [[jobs]]
+            type = "group"
+            environment = {{ FOO = "bar" }}
+              [[jobs.jobs]]
+              type = "script"
+              script = "echo one=$FOO >> {0}"
+              [[jobs.jobs]]
+              type = "script"
+              script = "echo two=$FOO >> {0}"
+            "#,
+            path.display()
+        );
+
+        let got = Main::try_from(input.as_str())?;
+        got.jobs[0].execute()?;
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one=bar\ntwo=bar\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn interval_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "brew"
+            argv = ["update"]
+            interval = "7d"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(
+            got.jobs[0].metadata.interval,
+            Some(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn description_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "command"
+            command = "sh"
+            argv = ["-c", "curl -fsSL https://example.com/install.sh | sh"]
+            description = "install the example tool"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(
+            got.jobs[0].description(),
+            Some(String::from("install the example tool"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn fingerprint_changes_with_spec_and_inputs() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let src = temp.join("src.txt");
+        let input = |command: &str| {
+            format!(
+                r#"
+                [[jobs]]
+                type = "file"
+                path = "{}"
+                src = "{}"
+                state = "link"
+
+                [[jobs]]
+                type = "command"
+                command = "{}"
+                "#,
+                temp.join("dest.txt").display(),
+                src.display(),
+                command
+            )
+        };
+        let before = Main::try_from(input("true").as_str())?;
+        let after = Main::try_from(input("false").as_str())?;
+        let fingerprint = before.jobs[0].fingerprint();
+        assert_eq!(fingerprint, after.jobs[0].fingerprint());
+        assert_ne!(before.jobs[1].fingerprint(), after.jobs[1].fingerprint());
+
+        std::fs::write(&src, "hello").expect("write");
+        assert_ne!(fingerprint, before.jobs[0].fingerprint());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint_changes_with_src_trees_and_targets() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let src = temp.join("src");
+        let dest = temp.join("dest");
+        let gitconfig = temp.join("gitconfig");
+        fs::create_dir_all(src.join("sub")).expect("create src");
+        fs::create_dir_all(&dest).expect("create dest");
+        fs::write(src.join("sub").join("old"), "old").expect("write");
+        fs::write(&gitconfig, "[user]\n\tname = me\n").expect("write");
+        let input = format!(
+            r#"
+            [[jobs]]
+            type = "stow"
+            src = {:?}
+            dest = {:?}
+
+            [[jobs]]
+            type = "gitconfig"
+            path = {:?}
+            settings = {{ "user.name" = "me" }}
+
+            [[jobs]]
+            type = "homebrew"
+            formula = "git"
+            "#,
+            src.display().to_string(),
+            dest.display().to_string(),
+            gitconfig.display().to_string()
+        );
+        let got = Main::try_from(input.as_str())?;
+        got.jobs[0].execute()?;
+
+        let stow = got.jobs[0].fingerprint();
+        fs::write(src.join("sub").join("new"), "new").expect("write");
+        assert_ne!(stow, got.jobs[0].fingerprint());
+
+        let stow = got.jobs[0].fingerprint();
+        fs::remove_file(dest.join("sub").join("old")).expect("remove link");
+        assert_ne!(stow, got.jobs[0].fingerprint());
+
+        let gitconfig_fingerprint = got.jobs[1].fingerprint();
+        fs::remove_file(&gitconfig).expect("remove");
+        assert_ne!(gitconfig_fingerprint, got.jobs[1].fingerprint());
+
+        // a package could be removed without any trace in the files we know of
+        assert_eq!(got.jobs[2].fingerprint(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_toml_round_trips() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            name = "hello"
+            type = "command"
+            command = "echo"
+            argv = ["hello"]
+            tags = ["shell"]
+            "#;
+        let got = Main::try_from(input)?;
+
+        let output = got.jobs[0].to_toml().expect("to_toml");
+        assert!(output.contains("type = 'command'"));
+        let again = Main::try_from(format!("[[jobs]]\n{}", output).as_str())?;
+        assert_eq!(again.jobs, got.jobs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_fields_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [settings]
+            threds = 2
+
+            [[jobs]]
+            name = "a"
+            type = "command"
+            command = "echo"
+            nedds = ["b"]
+
+            [[jobs]]
+            name = "b"
+            type = "file"
+            path = "~/.vimrc"
+            state = "touch"
+            tags = ["vim"]
+
+            [[job]]
+            name = "c"
+            "#;
+
+        let got = unknown_fields(input)?;
+
+        assert_eq!(
+            got,
+            vec![
+                r#"unknown field "job""#,
+                r#"settings: unknown field "threds""#,
+                r#"job "a": unknown field "nedds""#,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_destructive_toml() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            type = "file"
+            path = "~/.vimrc"
+            state = "absent"
+            force = true
+
+            [[jobs]]
+            type = "file"
+            path = "~/.config"
+            state = "directory"
+
+            [[jobs]]
+            type = "command"
+            command = "rm"
+            argv = ["old.txt"]
+            removes = "old.txt"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        let got: Vec<bool> = got.jobs.iter().map(|j| j.is_destructive()).collect();
+        assert_eq!(got, vec![true, false, true]);
+
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn only_if_and_unless_skip_job() -> std::result::Result<(), Error> {
+        let input = r#"
+            [[jobs]]
+            name = "only if false"
+            type = "command"
+            command = "./throw_if_attempt_to_execute"
+            only_if = "false"
+
+            [[jobs]]
+            name = "unless true"
+            type = "command"
+            command = "./throw_if_attempt_to_execute"
+            unless = "true"
+            "#;
+
+        let got = Main::try_from(input)?;
+
+        assert_eq!(got.jobs[0].execute()?, Status::Skipped);
+        assert_eq!(got.jobs[1].execute()?, Status::Skipped);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_reports_without_changing() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let path = temp.join("new").join("file.txt");
+        let input = format!(
+            r#"
+            [[jobs]]
+            type = "file"
+            path = {:?}
+            state = "touch"
+
+            [[jobs]]
+            type = "command"
+            command = "./throw_if_attempt_to_execute"
+
+            [[jobs]]
+            type = "wait_for"
+            path = {:?}
+
+            [[jobs]]
+            type = "group"
+              [[jobs.jobs]]
+              type = "command"
+              command = "./throw_if_attempt_to_execute"
+              [[jobs.jobs]]
+              type = "file"
+              path = {:?}
+              state = "touch"
+            "#,
+            path.display().to_string(),
+            temp.join("missing").display().to_string(),
+            path.display().to_string()
+        );
+
+        let got = Main::try_from(input.as_str())?;
+
+        assert!(matches!(got.jobs[0].check()?, Status::WouldChange(_, _)));
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+        assert_eq!(
+            got.jobs[1].check()?,
+            Status::WouldChange(
+                String::from("not run"),
+                String::from("run: ./throw_if_attempt_to_execute")
+            )
+        );
+        assert!(matches!(got.jobs[2].check()?, Status::Unchecked(_)));
+        // each job in the group is checked, not only the first
+        assert!(matches!(got.jobs[3].check()?, Status::WouldChange(_, _)));
+        assert!(!path.parent().unwrap().exists());
+        assert!(!command::is_check());
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_for_missing_executables_and_sources() -> std::result::Result<(), Error> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let input = format!(
+            r#"
+            [[jobs]]
+            type = "command"
+            command = "./throw_if_attempt_to_execute"
+
+            [[jobs]]
+            type = "file"
+            src = {:?}
+            path = {:?}
+            state = "link"
+
+            [[jobs]]
+            type = "command"
+            command = "./throw_if_attempt_to_execute"
+            when = false
+            "#,
+            temp.join("missing").display().to_string(),
+            temp.join("new").join("link").display().to_string()
+        );
+
+        let got = Main::try_from(input.as_str())?;
+
+        assert_eq!(
+            got.jobs[0].warnings(),
+            vec!["\"./throw_if_attempt_to_execute\" is not an executable in PATH"]
+        );
+        assert_eq!(
+            got.jobs[1].warnings(),
+            vec![format!(
+                "src {} does not exist",
+                temp.join("missing").display()
+            )]
+        );
+        assert!(got.jobs[2].warnings().is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn absent_when_defaults_to_true() -> std::result::Result<(), Error> {
         let input = r#"
@@ -255,10 +3110,11 @@ mod tests {
         let got = Main::try_from(input)?;
 
         let want = Main {
+            handlers: Vec::new(),
             jobs: vec![Job {
                 metadata: Metadata {
                     name: Some(String::from("run something")),
-                    when: true,
+                    when: When::Bool(true),
                     ..Default::default()
                 },
                 spec: Spec::Command(Command {
@@ -266,6 +3122,7 @@ mod tests {
                     ..Default::default()
                 }),
             }],
+            settings: Settings::default(),
         };
 
         assert_eq!(got.jobs.len(), 1);
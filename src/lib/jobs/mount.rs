@@ -0,0 +1,218 @@
+use std::{env::consts::OS, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+const FSTAB: &str = "/etc/fstab";
+const MOUNTS: &str = "/proc/self/mounts";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error(transparent)]
+    File {
+        #[from]
+        source: file::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("mount is not supported on {}", os)]
+    UnsupportedOs { os: String },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Mount {
+    pub device: String,
+    pub fstype: String,
+    pub mountpoint: PathBuf,
+    pub options: Option<String>,
+}
+impl Mount {
+    pub fn execute(&self) -> Result {
+        if OS != "linux" {
+            return Err(Error::UnsupportedOs {
+                os: String::from(OS),
+            });
+        }
+        let mut previously = Vec::<String>::new();
+        let mut changed = Vec::<String>::new();
+
+        let fstab = read_to_string(FSTAB)?;
+        let updated = upsert_entry(&fstab, &self.entry());
+        if updated != fstab {
+            file::write_as_root(FSTAB, updated)?;
+            previously.push(String::from("fstab: missing or different"));
+            changed.push(format!("fstab: {}", self.entry()));
+        }
+
+        if !self.mountpoint.is_dir() && !command::is_check() {
+            let mountpoint = self.mountpoint.to_string_lossy();
+            command::capture_success_as_root("mkdir", &["-p", &mountpoint])?;
+        }
+
+        let mountpoint = escape(self.mountpoint.to_string_lossy());
+        if !parse_mountpoints(read_to_string(MOUNTS)?).contains(&mountpoint) {
+            if !command::is_check() {
+                command::capture_success_as_root("mount", &[&self.mountpoint.to_string_lossy()])?;
+            }
+            previously.push(String::from("unmounted"));
+            changed.push(String::from("mounted"));
+        }
+
+        if changed.is_empty() {
+            Ok(Status::NoChange(format!(
+                "{} mounted",
+                self.mountpoint.display()
+            )))
+        } else {
+            Ok(Status::Changed(previously.join(", "), changed.join(", ")))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "mount -t {} -o {} {} {}",
+            self.fstype,
+            self.options(),
+            self.device,
+            self.mountpoint.display()
+        )
+    }
+
+    fn entry(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t0\t0",
+            escape(&self.device),
+            escape(self.mountpoint.to_string_lossy()),
+            self.fstype,
+            self.options()
+        )
+    }
+
+    fn options(&self) -> String {
+        self.options
+            .clone()
+            .unwrap_or_else(|| String::from("defaults"))
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// fstab and mounts use octal escapes for whitespace, e.g. "\040" for " "
+fn escape<S>(field: S) -> String
+where
+    S: AsRef<str>,
+{
+    field.as_ref().replace(' ', "\\040").replace('\t', "\\011")
+}
+
+fn parse_mountpoints<S>(mounts: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    mounts
+        .as_ref()
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(String::from)
+        .collect()
+}
+
+fn read_to_string(path: &str) -> std::result::Result<String, Error> {
+    fs::read_to_string(path).map_err(|e| Error::ReadPath {
+        path: PathBuf::from(path),
+        source: e,
+    })
+}
+
+// replaces the entry for the same mountpoint, ignoring differences in whitespace
+fn upsert_entry<S>(fstab: S, entry: &str) -> String
+where
+    S: AsRef<str>,
+{
+    let fields: Vec<&str> = entry.split_whitespace().collect();
+    let mut found = false;
+    let mut lines: Vec<String> = fstab
+        .as_ref()
+        .lines()
+        .map(|line| {
+            let l = line.trim();
+            if l.starts_with('#') || l.split_whitespace().nth(1) != Some(fields[1]) {
+                return String::from(line);
+            }
+            found = true;
+            let existing: Vec<&str> = l.split_whitespace().collect();
+            // dump and pass default to 0 when omitted
+            if existing[..existing.len().min(4)] == fields[..4]
+                && existing[4..].iter().all(|f| *f == "0")
+            {
+                String::from(line)
+            } else {
+                String::from(entry)
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(String::from(entry));
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount() -> Mount {
+        Mount {
+            device: String::from("UUID=1234"),
+            fstype: String::from("ext4"),
+            mountpoint: PathBuf::from("/mnt/My Data"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn name_with_defaults() {
+        let got = mount().name();
+        let want = "mount -t ext4 -o defaults UUID=1234 /mnt/My Data";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn upsert_entry_appends_once() {
+        let input = "# <file system> <mount point> <type> <options> <dump> <pass>\nUUID=abcd / ext4 errors=remount-ro 0 1\n";
+        let entry = mount().entry();
+        let got = upsert_entry(input, &entry);
+        assert_eq!(got, format!("{}{}\n", input, entry));
+        assert_eq!(upsert_entry(&got, &entry), got);
+    }
+
+    #[test]
+    fn upsert_entry_keeps_equivalent_and_replaces_different() {
+        let entry = mount().entry();
+        let same = "UUID=1234  /mnt/My\\040Data  ext4  defaults\n";
+        assert_eq!(upsert_entry(same, &entry), same);
+
+        let different = "UUID=1234 /mnt/My\\040Data ext4 noatime 0 0\n";
+        assert_eq!(upsert_entry(different, &entry), format!("{}\n", entry));
+    }
+
+    #[test]
+    fn parse_mountpoints_from_proc() {
+        let input = "proc /proc proc rw,nosuid 0 0\n/dev/sdb1 /mnt/My\\040Data ext4 rw 0 0\n";
+        let got = parse_mountpoints(input);
+        assert_eq!(
+            got,
+            vec![String::from("/proc"), String::from("/mnt/My\\040Data")]
+        );
+    }
+}
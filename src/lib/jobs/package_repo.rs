@@ -0,0 +1,361 @@
+use std::{fs, io, path::PathBuf};
+
+use mktemp::Temp;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, file, Status};
+
+const APT_KEYRINGS: &str = "/etc/apt/keyrings";
+const APT_SOURCES: &str = "/etc/apt/sources.list.d";
+const YUM_REPOS: &str = "/etc/yum.repos.d";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error(transparent)]
+    File {
+        #[from]
+        source: file::Error,
+    },
+    #[error("unable to detect kind of package repository, please set kind")]
+    KindNotDetected,
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("{:?} package repositories require source", kind)]
+    SourceRequired { kind: PackageRepoKind },
+    #[error("unable to create temporary file: {}", source)]
+    TempPath { source: io::Error },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageRepoKind {
+    Apt,
+    Copr,
+    Ppa,
+    Yum,
+}
+impl PackageRepoKind {
+    fn detect(repo: &str) -> Option<Self> {
+        if repo.starts_with("ppa:") {
+            Some(Self::Ppa)
+        } else if which("apt-get").is_ok() {
+            Some(Self::Apt)
+        } else if which("dnf").is_ok() || which("yum").is_ok() {
+            Some(Self::Yum)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageRepoState {
+    Absent,
+    #[default]
+    Present,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct PackageRepo {
+    pub key_url: Option<String>,
+    pub kind: Option<PackageRepoKind>,
+    pub repo: String,
+    pub source: Option<String>,
+    pub state: Option<PackageRepoState>,
+}
+impl PackageRepo {
+    pub fn execute(&self) -> Result {
+        let kind = self
+            .kind
+            .or_else(|| PackageRepoKind::detect(&self.repo))
+            .ok_or(Error::KindNotDetected)?;
+        let present = self.state.unwrap_or_default() == PackageRepoState::Present;
+
+        let changed = match kind {
+            PackageRepoKind::Apt | PackageRepoKind::Yum => {
+                let mut files = Vec::<(PathBuf, Vec<u8>)>::new();
+                if present {
+                    if let Some(url) = &self.key_url {
+                        if kind == PackageRepoKind::Apt {
+                            files.push((self.apt_key_path(), download(url)?));
+                        }
+                    }
+                    files.push((
+                        self.definition_path(kind),
+                        self.definition(kind)?.into_bytes(),
+                    ));
+                }
+                let changed = if present {
+                    sync_files(&files)?
+                } else {
+                    let mut paths = vec![self.definition_path(kind)];
+                    if kind == PackageRepoKind::Apt {
+                        paths.push(self.apt_key_path());
+                    }
+                    remove_files(&paths)?
+                };
+                // only refresh the package cache when the definition changed
+                if changed && !command::is_check() {
+                    refresh_cache(kind)?;
+                }
+                changed
+            }
+            PackageRepoKind::Copr => {
+                let project = self.repo.trim_start_matches("copr:");
+                let list = command::capture_success("dnf", &["copr", "list"])?.stdout_str();
+                let enabled = is_copr_enabled(list, project);
+                if enabled != present && !command::is_check() {
+                    let action = if present { "enable" } else { "remove" };
+                    command::capture_success_as_root(
+                        "dnf",
+                        &["copr", action, "--assumeyes", project],
+                    )?;
+                }
+                enabled != present
+            }
+            PackageRepoKind::Ppa => {
+                let enabled = is_ppa_enabled(&self.repo)?;
+                if enabled != present && !command::is_check() {
+                    let mut args = vec!["--yes"];
+                    if !present {
+                        args.push("--remove");
+                    }
+                    args.push(&self.repo);
+                    // add-apt-repository installs the key and refreshes the cache itself
+                    command::capture_success_as_root("add-apt-repository", &args)?;
+                }
+                enabled != present
+            }
+        };
+
+        let current = if present { "present" } else { "absent" };
+        let other = if present { "absent" } else { "present" };
+        if changed {
+            Ok(Status::Changed(
+                format!("{}: {}", self.repo, other),
+                format!("{}: {}", self.repo, current),
+            ))
+        } else {
+            Ok(Status::NoChange(format!("{}: {}", self.repo, current)))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let action = match self.state.unwrap_or_default() {
+            PackageRepoState::Absent => "remove",
+            PackageRepoState::Present => "add",
+        };
+        format!("package_repo {} {}", action, self.repo)
+    }
+
+    fn apt_key_path(&self) -> PathBuf {
+        let ext = match &self.key_url {
+            Some(u) if u.ends_with(".gpg") => "gpg",
+            _ => "asc",
+        };
+        PathBuf::from(APT_KEYRINGS).join(format!("{}.{}", self.repo, ext))
+    }
+
+    fn definition(&self, kind: PackageRepoKind) -> std::result::Result<String, Error> {
+        let source = self.source.as_ref().ok_or(Error::SourceRequired { kind })?;
+        if kind == PackageRepoKind::Yum {
+            return Ok(yum_repo(&self.repo, source, self.key_url.as_deref()));
+        }
+        let key_path = self.key_url.as_ref().map(|_| self.apt_key_path());
+        Ok(apt_source(source, key_path))
+    }
+
+    fn definition_path(&self, kind: PackageRepoKind) -> PathBuf {
+        match kind {
+            PackageRepoKind::Yum => PathBuf::from(YUM_REPOS).join(format!("{}.repo", self.repo)),
+            _ => PathBuf::from(APT_SOURCES).join(format!("{}.list", self.repo)),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// adds `[signed-by=...]` unless the source line already has options
+fn apt_source(source: &str, key_path: Option<PathBuf>) -> String {
+    let line = source.trim();
+    match (key_path, line.split_once(' ')) {
+        (Some(k), Some((deb, rest))) if !rest.trim_start().starts_with('[') => {
+            format!(
+                "{} [signed-by={}] {}\n",
+                deb,
+                k.display(),
+                rest.trim_start()
+            )
+        }
+        _ => format!("{}\n", line),
+    }
+}
+
+fn download(url: &str) -> std::result::Result<Vec<u8>, Error> {
+    let temp = Temp::new_file().map_err(|e| Error::TempPath { source: e })?;
+    command::capture_success("curl", &["-fsSL", "-o", &temp.to_string_lossy(), url])?;
+    fs::read(&temp).map_err(|e| Error::ReadPath {
+        path: temp.to_path_buf(),
+        source: e,
+    })
+}
+
+// e.g. "copr.fedorainfracloud.org/user/project", with a " (disabled)" suffix when disabled
+fn is_copr_enabled<S>(list: S, project: &str) -> bool
+where
+    S: AsRef<str>,
+{
+    list.as_ref().lines().any(|line| {
+        let l = line.trim();
+        !l.ends_with("(disabled)") && (l == project || l.ends_with(&format!("/{}", project)))
+    })
+}
+
+fn is_ppa_enabled(ppa: &str) -> std::result::Result<bool, Error> {
+    let needle = ppa.trim_start_matches("ppa:");
+    let entries = match fs::read_dir(APT_SOURCES) {
+        Ok(e) => e,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => {
+            return Err(Error::ReadPath {
+                path: PathBuf::from(APT_SOURCES),
+                source: e,
+            })
+        }
+    };
+    Ok(entries.flatten().any(|entry| {
+        fs::read_to_string(entry.path()).is_ok_and(|text| is_ppa_in_source(text, needle))
+    }))
+}
+
+fn is_ppa_in_source<S>(text: S, needle: &str) -> bool
+where
+    S: AsRef<str>,
+{
+    text.as_ref().lines().any(|line| {
+        let l = line.trim();
+        !l.starts_with('#')
+            && (l.contains(&format!("ppa.launchpad.net/{}/", needle))
+                || l.contains(&format!("ppa.launchpadcontent.net/{}/", needle)))
+    })
+}
+
+fn refresh_cache(kind: PackageRepoKind) -> std::result::Result<(), Error> {
+    match kind {
+        PackageRepoKind::Yum if which("dnf").is_ok() => {
+            command::capture_success_as_root("dnf", &["makecache"])?
+        }
+        PackageRepoKind::Yum => command::capture_success_as_root("yum", &["makecache"])?,
+        _ => command::capture_success_as_root("apt-get", &["update"])?,
+    };
+    Ok(())
+}
+
+// returns `true` if any file was removed
+fn remove_files(paths: &[PathBuf]) -> std::result::Result<bool, Error> {
+    let existing: Vec<String> = paths
+        .iter()
+        .filter(|p| p.exists())
+        .map(|p| p.display().to_string())
+        .collect();
+    if existing.is_empty() {
+        return Ok(false);
+    }
+    if command::is_check() {
+        return Ok(true);
+    }
+    let mut args = vec![String::from("-f")];
+    args.extend(existing);
+    command::capture_success_as_root("rm", &args)?;
+    Ok(true)
+}
+
+// returns `true` if any file needed to be written
+fn sync_files(files: &[(PathBuf, Vec<u8>)]) -> std::result::Result<bool, Error> {
+    let mut changed = false;
+    for (path, contents) in files {
+        if fs::read(path).ok().as_ref() == Some(contents) {
+            continue;
+        }
+        file::write_as_root(path, contents)?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+fn yum_repo(repo: &str, baseurl: &str, key_url: Option<&str>) -> String {
+    let mut lines = vec![
+        format!("[{}]", repo),
+        format!("name={}", repo),
+        format!("baseurl={}", baseurl),
+        String::from("enabled=1"),
+    ];
+    match key_url {
+        Some(k) => {
+            lines.push(String::from("gpgcheck=1"));
+            lines.push(format!("gpgkey={}", k));
+        }
+        None => lines.push(String::from("gpgcheck=0")),
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_when_present() {
+        let package_repo = PackageRepo {
+            repo: String::from("ppa:neovim-ppa/unstable"),
+            ..Default::default()
+        };
+        let got = package_repo.name();
+        let want = "package_repo add ppa:neovim-ppa/unstable";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn apt_source_adds_signed_by() {
+        let key = Some(PathBuf::from("/etc/apt/keyrings/docker.asc"));
+        assert_eq!(
+            apt_source("deb https://download.docker.com/linux/ubuntu noble stable", key.clone()),
+            "deb [signed-by=/etc/apt/keyrings/docker.asc] https://download.docker.com/linux/ubuntu noble stable\n"
+        );
+        assert_eq!(
+            apt_source("deb [arch=amd64] https://example.com stable main", key),
+            "deb [arch=amd64] https://example.com stable main\n"
+        );
+    }
+
+    #[test]
+    fn yum_repo_with_key() {
+        let got = yum_repo(
+            "vscode",
+            "https://packages.microsoft.com/yumrepos/vscode",
+            Some("https://packages.microsoft.com/keys/microsoft.asc"),
+        );
+        let want = "[vscode]\nname=vscode\nbaseurl=https://packages.microsoft.com/yumrepos/vscode\nenabled=1\ngpgcheck=1\ngpgkey=https://packages.microsoft.com/keys/microsoft.asc\n";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn detect_enabled_ppa_and_copr() {
+        let source = "# deb-src http://ppa.launchpad.net/other/ppa/ubuntu noble main\ndeb https://ppa.launchpadcontent.net/neovim-ppa/unstable/ubuntu/ noble main\n";
+        assert!(is_ppa_in_source(source, "neovim-ppa/unstable"));
+        assert!(!is_ppa_in_source(source, "other/ppa"));
+
+        let list = "copr.fedorainfracloud.org/atim/starship\ncopr.fedorainfracloud.org/user/old (disabled)\n";
+        assert!(is_copr_enabled(list, "atim/starship"));
+        assert!(!is_copr_enabled(list, "user/old"));
+    }
+}
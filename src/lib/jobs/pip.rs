@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("unable to parse output from `{}`: {}", cmd, source)]
+    ParseJson {
+        cmd: String,
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipManager {
+    Pip,
+    Pipx,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Pip {
+    pub manager: Option<PipManager>,
+    pub package: String,
+    pub version: Option<String>,
+}
+impl Pip {
+    pub fn execute(&self) -> Result {
+        let manager = self.manager();
+        let previously = installed_version(manager, &self.package)?;
+        if let Some(p) = &previously {
+            if self.version.as_ref().is_none_or(|v| v == p) {
+                return Ok(Status::NoChange(format!("{}=={}", self.package, p)));
+            }
+        }
+        let previously = previously.map(|p| format!("{}=={}", self.package, p));
+        if command::is_check() {
+            return Ok(Status::Changed(
+                previously.unwrap_or_else(|| String::from("absent")),
+                self.spec(),
+            ));
+        }
+
+        let (cmd, mut args) = self.command(manager);
+        if previously.is_some() && manager == PipManager::Pipx {
+            // pipx refuses to replace an existing venv otherwise
+            args.push(String::from("--force"));
+        }
+        command::capture_success(&cmd, &args)?;
+
+        let version = installed_version(manager, &self.package)?.unwrap_or_default();
+        Ok(Status::Changed(
+            previously.unwrap_or_else(|| String::from("absent")),
+            format!("{}=={}", self.package, version),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        let (cmd, args) = self.command(self.manager());
+        let mut parts = vec![cmd];
+        parts.extend(args);
+        parts.join(" ")
+    }
+
+    fn command(&self, manager: PipManager) -> (String, Vec<String>) {
+        let spec = self.spec();
+        match manager {
+            PipManager::Pip => (
+                String::from("python3"),
+                vec![
+                    String::from("-m"),
+                    String::from("pip"),
+                    String::from("install"),
+                    String::from("--user"),
+                    spec,
+                ],
+            ),
+            PipManager::Pipx => (String::from("pipx"), vec![String::from("install"), spec]),
+        }
+    }
+
+    fn manager(&self) -> PipManager {
+        self.manager.unwrap_or_else(|| {
+            if which("pipx").is_ok() {
+                PipManager::Pipx
+            } else {
+                PipManager::Pip
+            }
+        })
+    }
+
+    // e.g. "black==24.1.0", or just "black" for the latest version
+    fn spec(&self) -> String {
+        match &self.version {
+            Some(v) => format!("{}=={}", self.package, v),
+            None => self.package.clone(),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn installed_version<S>(
+    manager: PipManager,
+    package: S,
+) -> std::result::Result<Option<String>, Error>
+where
+    S: AsRef<str>,
+{
+    let installed = match manager {
+        PipManager::Pip => {
+            let args = ["-m", "pip", "list", "--user", "--format=json"];
+            let output = command::capture_success("python3", &args)?.stdout_str();
+            parse_pip_list(output).map_err(|e| Error::ParseJson {
+                cmd: String::from("pip list"),
+                source: e,
+            })?
+        }
+        PipManager::Pipx => {
+            let output = command::capture_success("pipx", &["list", "--json"])?.stdout_str();
+            parse_pipx_list(output).map_err(|e| Error::ParseJson {
+                cmd: String::from("pipx list"),
+                source: e,
+            })?
+        }
+    };
+    Ok(installed.get(&normalize(package)).cloned())
+}
+
+// https://www.python.org/dev/peps/pep-0503/#normalized-names
+fn normalize<S>(package: S) -> String
+where
+    S: AsRef<str>,
+{
+    package.as_ref().to_lowercase().replace(['_', '.'], "-")
+}
+
+#[derive(Deserialize)]
+struct PipListEntry {
+    name: String,
+    version: String,
+}
+
+fn parse_pip_list<S>(output: S) -> serde_json::Result<HashMap<String, String>>
+where
+    S: AsRef<str>,
+{
+    let entries: Vec<PipListEntry> = serde_json::from_str(output.as_ref())?;
+    Ok(entries
+        .into_iter()
+        .map(|e| (normalize(e.name), e.version))
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct PipxList {
+    venvs: HashMap<String, PipxVenv>,
+}
+#[derive(Deserialize)]
+struct PipxVenv {
+    metadata: PipxMetadata,
+}
+#[derive(Deserialize)]
+struct PipxMetadata {
+    main_package: PipxPackage,
+}
+#[derive(Deserialize)]
+struct PipxPackage {
+    package: String,
+    package_version: String,
+}
+
+fn parse_pipx_list<S>(output: S) -> serde_json::Result<HashMap<String, String>>
+where
+    S: AsRef<str>,
+{
+    let list: PipxList = serde_json::from_str(output.as_ref())?;
+    Ok(list
+        .venvs
+        .into_values()
+        .map(|v| {
+            let p = v.metadata.main_package;
+            (normalize(p.package), p.package_version)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_pip() {
+        let pip = Pip {
+            manager: Some(PipManager::Pip),
+            package: String::from("black"),
+            version: Some(String::from("19.10b0")),
+        };
+        let got = pip.name();
+        let want = "python3 -m pip install --user black==19.10b0";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_pipx() {
+        let pip = Pip {
+            manager: Some(PipManager::Pipx),
+            package: String::from("black"),
+            ..Default::default()
+        };
+        let got = pip.name();
+        let want = "pipx install black";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_pip_list_with_packages() -> serde_json::Result<()> {
+        let input = r#"[{"name": "Jinja2", "version": "2.11.2"}, {"name": "ruamel.yaml", "version": "0.16.10"}]"#;
+        let got = parse_pip_list(input)?;
+        assert_eq!(got.get("jinja2"), Some(&String::from("2.11.2")));
+        assert_eq!(got.get("ruamel-yaml"), Some(&String::from("0.16.10")));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_pipx_list_with_venvs() -> serde_json::Result<()> {
+        let input = r#"{
+            "pipx_spec_version": "0.1",
+            "venvs": {
+                "black": {
+                    "metadata": {
+                        "main_package": {
+                            "package": "black",
+                            "package_version": "19.10b0"
+                        }
+                    }
+                }
+            }
+        }"#;
+        let got = parse_pipx_list(input)?;
+        assert_eq!(got.len(), 1);
+        assert_eq!(got.get("black"), Some(&String::from("19.10b0")));
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Command, Status};
+
+#[cfg(windows)]
+const DEFAULT_SHELL: &str = "powershell";
+#[cfg(not(windows))]
+const DEFAULT_SHELL: &str = "sh";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+}
+
+// runs like a `command` job, with the script passed to the shell,
+// so it shares the same `creates` / `removes` guards and output handling
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Script {
+    pub chdir: Option<PathBuf>,
+    pub creates: Option<PathBuf>,
+    pub removes: Option<PathBuf>,
+    pub script: String,
+    pub shell: Option<String>,
+}
+impl Script {
+    pub fn execute(&self) -> Result {
+        Ok(self.command().execute()?)
+    }
+
+    pub fn name(&self) -> String {
+        let first = self
+            .script
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or_default();
+        let more = if self.script.trim().lines().count() > 1 {
+            " ..."
+        } else {
+            ""
+        };
+        format!("{}: {}{}", self.shell(), first, more)
+    }
+
+    fn command(&self) -> Command {
+        let shell = self.shell();
+        Command {
+            argv: Some(vec![String::from(shell_flag(&shell)), self.script.clone()]),
+            chdir: self.chdir.clone(),
+            command: shell,
+            creates: self.creates.clone(),
+            removes: self.removes.clone(),
+        }
+    }
+
+    pub fn shell(&self) -> String {
+        self.shell
+            .clone()
+            .unwrap_or_else(|| String::from(DEFAULT_SHELL))
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// runs a quick check with the default shell, e.g. for `only_if` and `unless`
+pub fn succeeds(script: &str) -> std::result::Result<bool, command::Error> {
+    let args = [shell_flag(DEFAULT_SHELL), script];
+    Ok(command::capture(DEFAULT_SHELL, &args)?.success())
+}
+
+// powershell and sh-like shells both accept the script as a single argument
+fn shell_flag(shell: &str) -> &'static str {
+    if shell.ends_with("powershell") || shell.ends_with("pwsh") {
+        "-Command"
+    } else {
+        "-c"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_multiple_lines() {
+        let script = Script {
+            script: String::from("\n  cd ~/src\n  make install\n"),
+            shell: Some(String::from("bash")),
+            ..Default::default()
+        };
+        assert_eq!(script.name(), "bash: cd ~/src ...");
+    }
+
+    #[test]
+    fn command_passes_script_to_shell() {
+        let script = Script {
+            creates: Some(PathBuf::from("/usr/local/bin/thing")),
+            script: String::from("echo one\necho two\n"),
+            shell: Some(String::from("pwsh")),
+            ..Default::default()
+        };
+        let got = script.command();
+        assert_eq!(got.command, "pwsh");
+        assert_eq!(
+            got.argv,
+            Some(vec![
+                String::from("-Command"),
+                String::from("echo one\necho two\n")
+            ])
+        );
+        assert_eq!(got.creates, script.creates);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn execute_respects_creates() -> std::result::Result<(), Error> {
+        let script = Script {
+            creates: Some(std::env::temp_dir()),
+            script: String::from("exit 1"),
+            ..Default::default()
+        };
+        assert!(matches!(script.execute()?, Status::NoChange(_)));
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn succeeds_with_exit_code() -> std::result::Result<(), command::Error> {
+        assert!(succeeds("test 1 -eq 1")?);
+        assert!(!succeeds("test 1 -eq 2")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+const INDENT: &str = "  ";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to find home_dir")]
+    HomeDir,
+    #[error("unable to create {}: {}", path.display(), source)]
+    CreatePath { path: PathBuf, source: io::Error },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to set permissions for {}: {}", path.display(), source)]
+    SetPermissions { path: PathBuf, source: io::Error },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct SshConfig {
+    pub host: String,
+    pub options: BTreeMap<String, String>,
+    pub path: Option<PathBuf>,
+}
+impl SshConfig {
+    pub fn execute(&self) -> Result {
+        let path = self.path()?;
+        let text = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::ReadPath { path, source: e }),
+        };
+
+        let (previously, updated) = upsert_host(&text, &self.host, &self.options);
+        let host = format!("Host {}", self.host);
+        if updated == text {
+            return Ok(if fix_permissions(&path)? {
+                Status::Changed(
+                    format!("{}: {}", path.display(), host),
+                    format!("{}: mode 600", path.display()),
+                )
+            } else {
+                Status::NoChange(format!("{}: {}", path.display(), host))
+            });
+        }
+
+        file::show_diff(&path, text.as_bytes(), updated.as_bytes());
+        if command::is_check() {
+            return Ok(Status::Changed(
+                previously.unwrap_or_else(|| String::from("absent")),
+                format!("{}: {}", path.display(), host),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::CreatePath {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+        fs::write(&path, &updated).map_err(|e| Error::WritePath {
+            path: path.clone(),
+            source: e,
+        })?;
+        fix_permissions(&path)?;
+
+        Ok(Status::Changed(
+            previously.unwrap_or_else(|| String::from("absent")),
+            format!("{}: {}", path.display(), host),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "ssh_config {}: Host {}",
+            self.path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("~/.ssh/config"))
+                .display(),
+            self.host
+        )
+    }
+
+    pub fn targets(&self) -> Vec<PathBuf> {
+        self.path().into_iter().collect()
+    }
+
+    fn path(&self) -> std::result::Result<PathBuf, Error> {
+        match &self.path {
+            Some(p) => Ok(p.clone()),
+            None => Ok(dirs::home_dir()
+                .ok_or(Error::HomeDir)?
+                .join(".ssh")
+                .join("config")),
+        }
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// returns `true` if permissions needed to be fixed
+#[cfg(not(windows))]
+fn fix_permissions<P>(path: P) -> std::result::Result<bool, Error>
+where
+    P: AsRef<Path>,
+{
+    use std::os::unix::fs::PermissionsExt;
+
+    let p = path.as_ref();
+    let attr = fs::metadata(p).map_err(|e| Error::ReadPath {
+        path: p.to_path_buf(),
+        source: e,
+    })?;
+    if attr.permissions().mode() & 0o777 == 0o600 {
+        return Ok(false);
+    }
+    if command::is_check() {
+        return Ok(true);
+    }
+    fs::set_permissions(p, fs::Permissions::from_mode(0o600)).map_err(|e| {
+        Error::SetPermissions {
+            path: p.to_path_buf(),
+            source: e,
+        }
+    })?;
+    Ok(true)
+}
+
+#[cfg(windows)]
+fn fix_permissions<P>(_path: P) -> std::result::Result<bool, Error>
+where
+    P: AsRef<Path>,
+{
+    Ok(false)
+}
+
+fn is_block_start<S>(line: S) -> bool
+where
+    S: AsRef<str>,
+{
+    let keyword = line
+        .as_ref()
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .find(|w| !w.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    keyword == "host" || keyword == "match"
+}
+
+fn is_host_line<S, H>(line: S, host: H) -> bool
+where
+    S: AsRef<str>,
+    H: AsRef<str>,
+{
+    let l = line.as_ref().trim();
+    is_block_start(l)
+        && l.len() > 4
+        && l[..4].eq_ignore_ascii_case("host")
+        && l[4..].trim_start_matches(|c: char| c.is_whitespace() || c == '=') == host.as_ref()
+}
+
+fn render_block<S>(host: S, options: &BTreeMap<String, String>) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    let mut lines = vec![format!("Host {}", host.as_ref())];
+    lines.extend(
+        options
+            .iter()
+            .map(|(k, v)| format!("{}{} {}", INDENT, k, v)),
+    );
+    lines
+}
+
+// returns the previous block (if any) and the updated text
+fn upsert_host<S, H>(
+    text: S,
+    host: H,
+    options: &BTreeMap<String, String>,
+) -> (Option<String>, String)
+where
+    S: AsRef<str>,
+    H: AsRef<str>,
+{
+    let mut lines: Vec<String> = text.as_ref().lines().map(String::from).collect();
+    let block = render_block(&host, options);
+
+    let previously = match lines.iter().position(|l| is_host_line(l, &host)) {
+        Some(start) => {
+            let mut end = lines[start + 1..]
+                .iter()
+                .position(is_block_start)
+                .map_or(lines.len(), |i| start + 1 + i);
+            // leave trailing blank lines and comments with the next block
+            while end > start + 1 && {
+                let l = lines[end - 1].trim();
+                l.is_empty() || l.starts_with('#')
+            } {
+                end -= 1;
+            }
+            let previous: Vec<String> = lines.splice(start..end, block).collect();
+            Some(previous.join("\n"))
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.extend(block);
+            None
+        }
+    };
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    if updated.trim().is_empty() {
+        updated = String::new();
+    }
+    // avoid reporting changes that are only due to a missing final newline
+    if text.as_ref().trim_end() == updated.trim_end() {
+        return (previously, String::from(text.as_ref()));
+    }
+    (previously, updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> BTreeMap<String, String> {
+        let mut options = BTreeMap::<String, String>::new();
+        options.insert(String::from("HostName"), String::from("github.com"));
+        options.insert(String::from("User"), String::from("git"));
+        options
+    }
+
+    #[test]
+    fn name_with_default_path() {
+        let ssh_config = SshConfig {
+            host: String::from("github"),
+            ..Default::default()
+        };
+        let got = ssh_config.name();
+        let want = "ssh_config ~/.ssh/config: Host github";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn upsert_host_appends_new_block() {
+        let input = "Host foo\n  User bar\n";
+        let (previously, got) = upsert_host(input, "github", &options());
+        assert_eq!(previously, None);
+        assert_eq!(
+            got,
+            "Host foo\n  User bar\n\nHost github\n  HostName github.com\n  User git\n"
+        );
+    }
+
+    #[test]
+    fn upsert_host_replaces_existing_block_and_preserves_others() {
+        let input = "Host github\n  User nobody\n\n# comment\nHost foo\n  User bar\n";
+        let (previously, got) = upsert_host(input, "github", &options());
+        assert_eq!(previously, Some(String::from("Host github\n  User nobody")));
+        assert_eq!(
+            got,
+            "Host github\n  HostName github.com\n  User git\n\n# comment\nHost foo\n  User bar\n"
+        );
+    }
+
+    #[test]
+    fn upsert_host_makes_no_change_when_block_matches() {
+        let input = "Host github\n  HostName github.com\n  User git\n\nHost foo\n  User bar\n";
+        let (_, got) = upsert_host(input, "github", &options());
+        assert_eq!(got, input);
+    }
+
+    #[test]
+    fn is_host_line_matches_exact_host() {
+        assert!(is_host_line("Host github", "github"));
+        assert!(is_host_line("host=github", "github"));
+        assert!(!is_host_line("Host github.com", "github"));
+        assert!(!is_host_line("  HostName github", "github"));
+    }
+}
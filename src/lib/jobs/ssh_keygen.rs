@@ -0,0 +1,207 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("unable to create {}: {}", path.display(), source)]
+    CreatePath { path: PathBuf, source: io::Error },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct SshKeygen {
+    pub bits: Option<u32>,
+    pub comment: Option<String>,
+    #[serde(default = "default_key_type_value")]
+    pub key_type: String,
+    pub path: PathBuf,
+    pub print: Option<bool>,
+}
+impl Default for SshKeygen {
+    fn default() -> Self {
+        Self {
+            bits: None,
+            comment: None,
+            key_type: default_key_type_value(),
+            path: PathBuf::new(),
+            print: None,
+        }
+    }
+}
+impl SshKeygen {
+    pub fn execute(&self) -> Result {
+        // never overwrite an existing key
+        if self.path.exists() {
+            return Ok(Status::NoChange(self.describe()?));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(
+                String::from("absent"),
+                format!("{}", self.path.display()),
+            ));
+        }
+
+        if let Some(parent) = self.path.parent() {
+            create_private_dir(parent)?;
+        }
+        command::capture_success("ssh-keygen", &self.args())?;
+
+        Ok(Status::Changed(String::from("absent"), self.describe()?))
+    }
+
+    pub fn name(&self) -> String {
+        let mut parts = vec![String::from("ssh-keygen")];
+        parts.extend(self.args());
+        parts.join(" ")
+    }
+
+    // the private and public key files
+    pub fn targets(&self) -> Vec<PathBuf> {
+        vec![self.path.clone(), public_key_path(&self.path)]
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("-q"),
+            String::from("-t"),
+            self.key_type.clone(),
+        ];
+        if let Some(b) = self.bits {
+            args.push(String::from("-b"));
+            args.push(format!("{}", b));
+        }
+        if let Some(c) = &self.comment {
+            args.push(String::from("-C"));
+            args.push(c.clone());
+        }
+        args.push(String::from("-N"));
+        args.push(String::new());
+        args.push(String::from("-f"));
+        args.push(format!("{}", self.path.display()));
+        args
+    }
+
+    fn describe(&self) -> std::result::Result<String, Error> {
+        if !self.print.unwrap_or(false) {
+            return Ok(format!("{}", self.path.display()));
+        }
+        let public = public_key_path(&self.path);
+        fs::read_to_string(&public)
+            .map(|s| String::from(s.trim()))
+            .map_err(|e| Error::ReadPath {
+                path: public,
+                source: e,
+            })
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn default_key_type_value() -> String {
+    String::from("ed25519")
+}
+
+#[cfg(not(windows))]
+fn create_private_dir<P>(path: P) -> std::result::Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    use std::os::unix::fs::DirBuilderExt;
+
+    let p = path.as_ref();
+    if p.is_dir() {
+        return Ok(());
+    }
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(p)
+        .map_err(|e| Error::CreatePath {
+            path: p.to_path_buf(),
+            source: e,
+        })
+}
+
+#[cfg(windows)]
+fn create_private_dir<P>(path: P) -> std::result::Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let p = path.as_ref();
+    fs::create_dir_all(p).map_err(|e| Error::CreatePath {
+        path: p.to_path_buf(),
+        source: e,
+    })
+}
+
+fn public_key_path<P>(path: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut s = path.as_ref().as_os_str().to_os_string();
+    s.push(".pub");
+    PathBuf::from(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_defaults() {
+        let keygen = SshKeygen {
+            path: PathBuf::from("id_ed25519"),
+            ..Default::default()
+        };
+        let got = keygen.name();
+        let want = "ssh-keygen -q -t ed25519 -N  -f id_ed25519";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn name_with_bits_and_comment() {
+        let keygen = SshKeygen {
+            bits: Some(4096),
+            comment: Some(String::from("me@example.com")),
+            key_type: String::from("rsa"),
+            path: PathBuf::from("id_rsa"),
+            ..Default::default()
+        };
+        let got = keygen.name();
+        let want = "ssh-keygen -q -t rsa -b 4096 -C me@example.com -N  -f id_rsa";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn public_key_path_appends_pub() {
+        assert_eq!(
+            public_key_path("/home/me/.ssh/id_ed25519"),
+            PathBuf::from("/home/me/.ssh/id_ed25519.pub")
+        );
+    }
+
+    #[test]
+    fn nochange_when_key_already_exists() -> std::result::Result<(), Error> {
+        let keygen = SshKeygen {
+            path: PathBuf::from("Cargo.toml"),
+            ..Default::default()
+        };
+        let got = keygen.execute()?;
+        assert_eq!(got, Status::NoChange(String::from("Cargo.toml")));
+        Ok(())
+    }
+}
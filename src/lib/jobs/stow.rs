@@ -0,0 +1,236 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    File {
+        #[from]
+        source: file::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to remove {}: {}", path.display(), source)]
+    RemovePath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Stow {
+    pub dest: PathBuf,
+    pub force: Option<bool>,
+    pub prune: Option<bool>,
+    pub src: PathBuf,
+}
+impl Stow {
+    pub fn execute(&self) -> Result {
+        let mut files = Vec::<PathBuf>::new();
+        let mut dirs = vec![PathBuf::new()];
+        walk(&self.src, Path::new(""), &mut files, &mut dirs)?;
+
+        let mut linked = Vec::<String>::new();
+        for rel in &files {
+            let status = file::execute_link(
+                self.src.join(rel),
+                self.dest.join(rel),
+                self.force.unwrap_or(false),
+            )?;
+            if let Status::Changed(_, _) = status {
+                linked.push(rel.display().to_string());
+            }
+        }
+
+        let mut pruned = Vec::<String>::new();
+        if self.prune.unwrap_or(false) {
+            for rel in &dirs {
+                for link in stale_links(&self.src, &self.dest.join(rel))? {
+                    if !command::is_check() {
+                        fs::remove_file(&link).map_err(|e| Error::RemovePath {
+                            path: link.clone(),
+                            source: e,
+                        })?;
+                    }
+                    pruned.push(link.display().to_string());
+                }
+            }
+        }
+
+        let current = format!(
+            "{} -> {}: {} links",
+            self.src.display(),
+            self.dest.display(),
+            files.len()
+        );
+        if linked.is_empty() && pruned.is_empty() {
+            return Ok(Status::NoChange(current));
+        }
+        let mut previously = Vec::<String>::new();
+        if !linked.is_empty() {
+            previously.push(format!("unlinked: {}", linked.join(", ")));
+        }
+        if !pruned.is_empty() {
+            previously.push(format!("stale: {}", pruned.join(", ")));
+        }
+        Ok(Status::Changed(previously.join("; "), current))
+    }
+
+    pub fn name(&self) -> String {
+        format!("stow {} -> {}", self.src.display(), self.dest.display())
+    }
+
+    // the links that it manages, one for each file in src
+    pub fn targets(&self) -> Vec<PathBuf> {
+        let mut files = Vec::<PathBuf>::new();
+        let mut dirs = Vec::<PathBuf>::new();
+        if walk(&self.src, Path::new(""), &mut files, &mut dirs).is_err() {
+            return Vec::new();
+        }
+        files.iter().map(|rel| self.dest.join(rel)).collect()
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// symlinks in dir that point into src, but whose target no longer exists
+fn stale_links(src: &Path, dir: &Path) -> std::result::Result<Vec<PathBuf>, Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(Error::ReadPath {
+                path: dir.to_path_buf(),
+                source: e,
+            })
+        }
+    };
+    let mut stale: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_link(path).is_ok_and(|target| target.starts_with(src) && !target.exists())
+        })
+        .collect();
+    stale.sort();
+    Ok(stale)
+}
+
+// collects paths relative to root, sorted for predictable output;
+// symlinked directories are not followed, so they get linked as a whole
+fn walk(
+    root: &Path,
+    rel: &Path,
+    files: &mut Vec<PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+) -> std::result::Result<(), Error> {
+    let dir = root.join(rel);
+    let mut entries: Vec<fs::DirEntry> = fs::read_dir(&dir)
+        .map_err(|e| Error::ReadPath {
+            path: dir.clone(),
+            source: e,
+        })?
+        .flatten()
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let child = rel.join(entry.file_name());
+        let file_type = entry.file_type().map_err(|e| Error::ReadPath {
+            path: entry.path(),
+            source: e,
+        })?;
+        if file_type.is_dir() {
+            dirs.push(child.clone());
+            walk(root, &child, files, dirs)?;
+        } else {
+            files.push(child);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn name_with_src_and_dest() {
+        let stow = Stow {
+            dest: PathBuf::from("/home/me"),
+            src: PathBuf::from("/home/me/.dotfiles/zsh"),
+            ..Default::default()
+        };
+        let got = stow.name();
+        let want = "stow /home/me/.dotfiles/zsh -> /home/me";
+        assert_eq!(got, want);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn execute_links_nested_files_and_prunes() -> std::result::Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let src = temp.join("src");
+        let dest = temp.join("dest");
+        fs::create_dir_all(src.join(".config").join("nvim")).expect("mkdir");
+        fs::write(src.join(".zshrc"), "").expect("write");
+        fs::write(src.join(".config").join("nvim").join("init.lua"), "").expect("write");
+
+        let stow = Stow {
+            dest: dest.clone(),
+            prune: Some(true),
+            src: src.clone(),
+            ..Default::default()
+        };
+
+        let got = stow.execute()?;
+        assert!(matches!(got, Status::Changed(_, _)));
+        assert_eq!(
+            fs::read_link(dest.join(".config").join("nvim").join("init.lua")).ok(),
+            Some(src.join(".config").join("nvim").join("init.lua"))
+        );
+        assert!(matches!(stow.execute()?, Status::NoChange(_)));
+
+        fs::remove_file(src.join(".zshrc")).expect("remove");
+        command::set_check(true);
+        let got = stow.execute();
+        command::set_check(false);
+        assert!(matches!(got?, Status::Changed(_, _)));
+        assert!(fs::symlink_metadata(dest.join(".zshrc")).is_ok());
+
+        let got = stow.execute()?;
+        assert!(matches!(got, Status::Changed(_, _)));
+        assert!(fs::symlink_metadata(dest.join(".zshrc")).is_err());
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn execute_links_symlinked_dirs_without_following_them() -> std::result::Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let src = temp.join("src");
+        let dest = temp.join("dest");
+        fs::create_dir_all(src.join("dir")).expect("mkdir");
+        std::os::unix::fs::symlink(&src, src.join("dir").join("loop")).expect("symlink");
+
+        let stow = Stow {
+            dest: dest.clone(),
+            src: src.clone(),
+            ..Default::default()
+        };
+
+        let got = stow.execute()?;
+        assert!(matches!(got, Status::Changed(_, _)));
+        assert_eq!(
+            fs::read_link(dest.join("dir").join("loop")).ok(),
+            Some(src.join("dir").join("loop"))
+        );
+        Ok(())
+    }
+}
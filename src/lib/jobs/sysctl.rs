@@ -0,0 +1,166 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+const SYSCTL_CONF: &str = "/etc/sysctl.d/99-tuning.conf";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error(transparent)]
+    File {
+        #[from]
+        source: file::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Sysctl {
+    pub key: String,
+    pub path: Option<PathBuf>,
+    pub persist: Option<bool>,
+    pub value: String,
+}
+impl Sysctl {
+    pub fn execute(&self) -> Result {
+        let value = normalize(&self.value);
+        let previously =
+            normalize(command::capture_success("sysctl", &["-n", &self.key])?.stdout_str());
+        let mut changed = false;
+
+        if previously != value {
+            if !command::is_check() {
+                command::capture_success_as_root(
+                    "sysctl",
+                    &["-w", &format!("{}={}", self.key, value)],
+                )?;
+            }
+            changed = true;
+        }
+
+        if self.persist.unwrap_or(true) {
+            let path = self.path();
+            let text = match fs::read_to_string(&path) {
+                Ok(s) => s,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(Error::ReadPath { path, source: e }),
+            };
+            let updated = upsert_line(&text, &self.key, &value);
+            if updated != text {
+                file::write_as_root(&path, updated)?;
+                changed = true;
+            }
+        }
+
+        let now = format!("{} = {}", self.key, value);
+        if changed {
+            Ok(Status::Changed(
+                format!("{} = {}", self.key, previously),
+                now,
+            ))
+        } else {
+            Ok(Status::NoChange(now))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("sysctl -w {}={}", self.key, normalize(&self.value))
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(SYSCTL_CONF))
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// multi-value parameters are tab-separated by `sysctl -n`
+fn normalize<S>(value: S) -> String
+where
+    S: AsRef<str>,
+{
+    value
+        .as_ref()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn upsert_line<S>(text: S, key: &str, value: &str) -> String
+where
+    S: AsRef<str>,
+{
+    let line = format!("{} = {}", key, value);
+    let mut found = false;
+    let mut lines: Vec<String> = text
+        .as_ref()
+        .lines()
+        .map(|l| {
+            let mut parts = l.splitn(2, '=');
+            let k = parts.next().unwrap_or_default().trim();
+            if k == key && parts.next().is_some() {
+                found = true;
+                line.clone()
+            } else {
+                String::from(l)
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(line);
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_key_and_value() {
+        let sysctl = Sysctl {
+            key: String::from("vm.swappiness"),
+            value: String::from("10"),
+            ..Default::default()
+        };
+        let got = sysctl.name();
+        let want = "sysctl -w vm.swappiness=10";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn normalize_multiple_values() {
+        assert_eq!(normalize("4096\t87380\t6291456\n"), "4096 87380 6291456");
+    }
+
+    #[test]
+    fn upsert_line_replaces_existing_key() {
+        let input = "# comment\nvm.swappiness=60\nnet.ipv4.ip_forward = 1\n";
+        let got = upsert_line(input, "vm.swappiness", "10");
+        assert_eq!(
+            got,
+            "# comment\nvm.swappiness = 10\nnet.ipv4.ip_forward = 1\n"
+        );
+    }
+
+    #[test]
+    fn upsert_line_appends_new_key() {
+        let got = upsert_line("", "vm.swappiness", "10");
+        assert_eq!(got, "vm.swappiness = 10\n");
+        assert_eq!(upsert_line(&got, "vm.swappiness", "10"), got);
+    }
+}
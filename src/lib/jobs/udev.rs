@@ -0,0 +1,146 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, file, Status};
+
+const UDEV_RULES: &str = "/etc/udev/rules.d";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error(transparent)]
+    File {
+        #[from]
+        source: file::Error,
+    },
+    #[error("udev requires filename when rules are inline")]
+    FilenameRequired,
+    #[error("udev only reads files ending with .rules, not {}", filename)]
+    InvalidFilename { filename: String },
+    #[error("udev requires src or rules")]
+    NothingToDo,
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Udev {
+    pub filename: Option<String>,
+    pub rules: Option<String>,
+    pub src: Option<PathBuf>,
+}
+impl Udev {
+    pub fn execute(&self) -> Result {
+        let contents = match (&self.rules, &self.src) {
+            (Some(r), _) => r.clone(),
+            (None, Some(s)) => fs::read_to_string(s).map_err(|e| Error::ReadPath {
+                path: s.clone(),
+                source: e,
+            })?,
+            (None, None) => return Err(Error::NothingToDo),
+        };
+        let filename = self.filename().ok_or(Error::FilenameRequired)?;
+        if !filename.ends_with(".rules") {
+            return Err(Error::InvalidFilename { filename });
+        }
+        let path = rules_path(&filename);
+
+        let previously = match fs::read_to_string(&path) {
+            Ok(s) => Some(s),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(Error::ReadPath { path, source: e }),
+        };
+        if previously.as_ref() == Some(&contents) {
+            return Ok(Status::NoChange(path.display().to_string()));
+        }
+
+        let from = if previously.is_some() {
+            "different"
+        } else {
+            "absent"
+        };
+        let changed = Status::Changed(
+            format!("{}: {}", path.display(), from),
+            format!("{}: installed", path.display()),
+        );
+        file::write_as_root(&path, &contents)?;
+        if command::is_check() {
+            return Ok(changed);
+        }
+        // rules only apply to devices that are (re-)added after a reload,
+        // so also re-trigger events for devices that are already plugged in
+        command::capture_success_as_root("udevadm", &["control", "--reload"])?;
+        command::capture_success_as_root("udevadm", &["trigger"])?;
+        Ok(changed)
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "udev {}",
+            self.filename()
+                .unwrap_or_else(|| String::from("<missing filename>"))
+        )
+    }
+
+    pub fn targets(&self) -> Vec<PathBuf> {
+        self.filename()
+            .map(|f| rules_path(&f))
+            .into_iter()
+            .collect()
+    }
+
+    // defaults to the file name of src
+    fn filename(&self) -> Option<String> {
+        self.filename.clone().or_else(|| {
+            self.src
+                .as_ref()
+                .and_then(|s| s.file_name())
+                .map(|f| f.to_string_lossy().to_string())
+        })
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn rules_path(filename: &str) -> PathBuf {
+    PathBuf::from(UDEV_RULES).join(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_from_src() {
+        let udev = Udev {
+            src: Some(PathBuf::from("/home/me/.dotfiles/70-u2f.rules")),
+            ..Default::default()
+        };
+        assert_eq!(udev.name(), "udev 70-u2f.rules");
+    }
+
+    #[test]
+    fn execute_validates_before_writing() {
+        let udev = Udev {
+            rules: Some(String::from("# nothing\n")),
+            ..Default::default()
+        };
+        assert!(matches!(udev.execute(), Err(Error::FilenameRequired)));
+
+        let udev = Udev {
+            filename: Some(String::from("70-u2f.conf")),
+            rules: Some(String::from("# nothing\n")),
+            ..Default::default()
+        };
+        assert!(matches!(udev.execute(), Err(Error::InvalidFilename { .. })));
+
+        assert!(matches!(Udev::default().execute(), Err(Error::NothingToDo)));
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+const COMMANDS: &[&str] = &["code", "codium"];
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct Vscode {
+    pub command: Option<String>,
+    pub extensions: Vec<String>,
+}
+impl Vscode {
+    pub fn execute(&self) -> Result {
+        let cmd = self.command();
+        let output = command::capture_success(&cmd, &["--list-extensions"])?.stdout_str();
+        let missing = missing_extensions(&self.extensions, output);
+        if missing.is_empty() {
+            return Ok(Status::NoChange(self.extensions.join(", ")));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(String::from("absent"), missing.join(", ")));
+        }
+
+        for extension in &missing {
+            command::capture_success(&cmd, &["--install-extension", extension])?;
+        }
+
+        Ok(Status::Changed(String::from("absent"), missing.join(", ")))
+    }
+
+    pub fn name(&self) -> String {
+        let cmd = self.command();
+        self.extensions
+            .iter()
+            .map(|e| format!("{} --install-extension {}", cmd, e))
+            .collect::<Vec<String>>()
+            .join(" && ")
+    }
+
+    fn command(&self) -> String {
+        self.command.clone().unwrap_or_else(|| {
+            String::from(
+                *COMMANDS
+                    .iter()
+                    .find(|c| which(c).is_ok())
+                    .unwrap_or(&COMMANDS[0]),
+            )
+        })
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// extension identifiers are case-insensitive
+fn missing_extensions<S>(wanted: &[String], installed: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    let installed: Vec<String> = installed
+        .as_ref()
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|line| !line.is_empty())
+        .collect();
+    wanted
+        .iter()
+        .filter(|w| !installed.contains(&w.to_lowercase()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_extensions() {
+        let vscode = Vscode {
+            command: Some(String::from("codium")),
+            extensions: vec![
+                String::from("rust-lang.rust"),
+                String::from("vscodevim.vim"),
+            ],
+        };
+        let got = vscode.name();
+        let want =
+            "codium --install-extension rust-lang.rust && codium --install-extension vscodevim.vim";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn missing_extensions_ignores_case() {
+        let wanted = vec![
+            String::from("Rust-Lang.rust"),
+            String::from("vscodevim.vim"),
+        ];
+        let got = missing_extensions(&wanted, "rust-lang.rust\nms-python.python\n");
+        assert_eq!(got, vec![String::from("vscodevim.vim")]);
+    }
+}
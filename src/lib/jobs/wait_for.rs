@@ -0,0 +1,174 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+const DEFAULT_MAX_SECONDS: u64 = 60;
+const DEFAULT_POLL_SECONDS: u64 = 1;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("wait_for requires address, command or path")]
+    NothingToDo,
+    #[error("{} still unavailable after {}s", what, seconds)]
+    TimedOut { seconds: u64, what: String },
+}
+
+// not `timeout` and `interval`, as those names are reserved for job metadata
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct WaitFor {
+    pub address: Option<String>,
+    pub argv: Option<Vec<String>>,
+    pub command: Option<String>,
+    pub max_seconds: Option<u64>,
+    pub path: Option<PathBuf>,
+    pub poll_seconds: Option<u64>,
+}
+impl WaitFor {
+    pub fn execute(&self) -> Result {
+        if self.address.is_none() && self.command.is_none() && self.path.is_none() {
+            return Err(Error::NothingToDo);
+        }
+        let max = Duration::from_secs(self.max_seconds.unwrap_or(DEFAULT_MAX_SECONDS));
+        let poll = Duration::from_secs(self.poll_seconds.unwrap_or(DEFAULT_POLL_SECONDS));
+        let started = Instant::now();
+        // a check looks once, rather than waiting for up to `max_seconds`
+        if command::is_check() && !self.is_available(poll)? {
+            return Ok(Status::Unchecked(format!(
+                "{} not yet available",
+                self.what()
+            )));
+        }
+
+        loop {
+            if self.is_available(poll)? {
+                return Ok(Status::NoChange(format!(
+                    "{} available after {}s",
+                    self.what(),
+                    started.elapsed().as_secs()
+                )));
+            }
+            if started.elapsed() >= max {
+                return Err(Error::TimedOut {
+                    seconds: max.as_secs(),
+                    what: self.what(),
+                });
+            }
+            thread::sleep(poll);
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "wait_for {} (max {}s)",
+            self.what(),
+            self.max_seconds.unwrap_or(DEFAULT_MAX_SECONDS)
+        )
+    }
+
+    fn is_available(&self, connect_timeout: Duration) -> std::result::Result<bool, Error> {
+        if let Some(p) = &self.path {
+            if !p.exists() {
+                return Ok(false);
+            }
+        }
+        if let Some(a) = &self.address {
+            if !is_listening(a, connect_timeout) {
+                return Ok(false);
+            }
+        }
+        if let Some(c) = &self.command {
+            let args = self.argv.clone().unwrap_or_default();
+            if !command::capture(c, &args)?.success() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn what(&self) -> String {
+        let mut parts = Vec::<String>::new();
+        if let Some(a) = &self.address {
+            parts.push(format!("tcp://{}", a));
+        }
+        if let Some(p) = &self.path {
+            parts.push(p.display().to_string());
+        }
+        if let Some(c) = &self.command {
+            let mut cmd = vec![c.clone()];
+            cmd.extend(self.argv.clone().unwrap_or_default());
+            parts.push(format!("`{}`", cmd.join(" ")));
+        }
+        parts.join(" and ")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// address is "host:port", and may resolve to several IPs
+fn is_listening(address: &str, timeout: Duration) -> bool {
+    match address.to_socket_addrs() {
+        Ok(addrs) => addrs
+            .into_iter()
+            .any(|a| TcpStream::connect_timeout(&a, timeout).is_ok()),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn name_with_address_and_path() {
+        let wait_for = WaitFor {
+            address: Some(String::from("localhost:5432")),
+            path: Some(PathBuf::from("/run/postgresql")),
+            ..Default::default()
+        };
+        let got = wait_for.name();
+        let want = "wait_for tcp://localhost:5432 and /run/postgresql (max 60s)";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn execute_with_listening_address() -> std::result::Result<(), Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let address = listener.local_addr().expect("local_addr").to_string();
+        let wait_for = WaitFor {
+            address: Some(address),
+            max_seconds: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(wait_for.execute()?, Status::NoChange(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn execute_times_out() {
+        let wait_for = WaitFor {
+            max_seconds: Some(0),
+            path: Some(PathBuf::from("/this/does/not/exist")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            wait_for.execute(),
+            Err(Error::TimedOut { seconds: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{env, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use which::which;
+
+use super::{command, Status};
+
+const CHOCOLATEY_INSTALL: &str = "C:\\ProgramData\\chocolatey";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("none of winget, scoop or choco found")]
+    ManagerNotFound,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowsManager {
+    Choco,
+    Scoop,
+    Winget,
+}
+impl WindowsManager {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Choco => "choco",
+            Self::Scoop => "scoop",
+            Self::Winget => "winget",
+        }
+    }
+
+    fn detect() -> Option<Self> {
+        [Self::Winget, Self::Scoop, Self::Choco]
+            .iter()
+            .copied()
+            .find(|m| which(m.command()).is_ok())
+    }
+
+    // scoop is a .cmd/.ps1 shim, so use the full path that `which` finds
+    fn resolve(self) -> std::result::Result<String, Error> {
+        which(self.command())
+            .map(|p| String::from(p.to_string_lossy()))
+            .map_err(|_| Error::ManagerNotFound)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct WindowsPackage {
+    pub manager: Option<WindowsManager>,
+    pub package: String,
+}
+impl WindowsPackage {
+    pub fn execute(&self) -> Result {
+        let manager = self
+            .manager
+            .or_else(WindowsManager::detect)
+            .ok_or(Error::ManagerNotFound)?;
+        let cmd = manager.resolve()?;
+
+        let installed = match manager {
+            WindowsManager::Choco => choco_lib_dir().join(&self.package).is_dir(),
+            WindowsManager::Scoop => {
+                let output = command::capture_success(&cmd, &["list"])?.stdout_str();
+                parse_scoop_list(output)
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(&self.package))
+            }
+            WindowsManager::Winget => command::capture(
+                &cmd,
+                &[
+                    "list",
+                    "--exact",
+                    "--id",
+                    &self.package,
+                    "--accept-source-agreements",
+                ],
+            )?
+            .success(),
+        };
+        if installed {
+            return Ok(Status::NoChange(self.package.clone()));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(
+                String::from("absent"),
+                self.package.clone(),
+            ));
+        }
+
+        match manager {
+            WindowsManager::Choco => {
+                command::capture_success_as_root(&cmd, &["install", &self.package, "--yes"])?
+            }
+            WindowsManager::Scoop => command::capture_success(&cmd, &["install", &self.package])?,
+            WindowsManager::Winget => command::capture_success(
+                &cmd,
+                &[
+                    "install",
+                    "--exact",
+                    "--id",
+                    &self.package,
+                    "--silent",
+                    "--accept-package-agreements",
+                    "--accept-source-agreements",
+                ],
+            )?,
+        };
+        Ok(Status::Changed(
+            String::from("absent"),
+            self.package.clone(),
+        ))
+    }
+
+    pub fn name(&self) -> String {
+        let manager = self
+            .manager
+            .or_else(WindowsManager::detect)
+            .unwrap_or(WindowsManager::Winget);
+        format!("{} install {}", manager.command(), self.package)
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+// `choco list` changed meaning between v1 and v2,
+// but installed packages are always in lib/
+fn choco_lib_dir() -> PathBuf {
+    env::var_os("ChocolateyInstall")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(CHOCOLATEY_INSTALL))
+        .join("lib")
+}
+
+// e.g.
+// Installed apps:
+//
+// Name Version Source Updated            Info
+// ---- ------- ------ -------            ----
+// git  2.44.0  main   2024-03-01 10:00:00
+fn parse_scoop_list<S>(output: S) -> Vec<String>
+where
+    S: AsRef<str>,
+{
+    output
+        .as_ref()
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("----"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_with_manager() {
+        let windows_package = WindowsPackage {
+            manager: Some(WindowsManager::Scoop),
+            package: String::from("git"),
+        };
+        let got = windows_package.name();
+        let want = "scoop install git";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn parse_scoop_list_with_apps() {
+        let input = "Installed apps:\n\nName    Version Source Updated             Info\n----    ------- ------ -------             ----\n7zip    23.01   main   2024-01-02 03:04:05\ngit     2.44.0  main   2024-03-01 10:00:00\n\n";
+        let got = parse_scoop_list(input);
+        assert_eq!(got, vec![String::from("7zip"), String::from("git")]);
+    }
+}
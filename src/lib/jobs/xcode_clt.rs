@@ -0,0 +1,109 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::{command, Status};
+
+// the installer is a GUI dialog, so give the user plenty of time to click through it
+const DEFAULT_MAX_SECONDS: u64 = 1800;
+const POLL_SECONDS: u64 = 5;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(transparent)]
+    Command {
+        #[from]
+        source: command::Error,
+    },
+    #[error("Xcode Command Line Tools still not installed after {}s", seconds)]
+    TimedOut { seconds: u64 },
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub struct XcodeClt {
+    pub max_seconds: Option<u64>,
+}
+impl XcodeClt {
+    pub fn execute(&self) -> Result {
+        if let Some(path) = installed_path()? {
+            return Ok(Status::NoChange(path));
+        }
+        if command::is_check() {
+            return Ok(Status::Changed(
+                String::from("absent"),
+                String::from("installed"),
+            ));
+        }
+
+        // this only opens the installer dialog, and returns immediately
+        command::capture("xcode-select", &["--install"])?;
+
+        let max = Duration::from_secs(self.max_seconds.unwrap_or(DEFAULT_MAX_SECONDS));
+        let started = Instant::now();
+        loop {
+            if let Some(path) = installed_path()? {
+                return Ok(Status::Changed(String::from("absent"), path));
+            }
+            if started.elapsed() >= max {
+                return Err(Error::TimedOut {
+                    seconds: max.as_secs(),
+                });
+            }
+            thread::sleep(Duration::from_secs(POLL_SECONDS));
+        }
+    }
+
+    pub fn name(&self) -> String {
+        String::from("xcode-select --install")
+    }
+}
+
+pub type Result = std::result::Result<Status, Error>;
+
+fn installed_path() -> std::result::Result<Option<String>, Error> {
+    let data = command::capture("xcode-select", &["-p"])?;
+    Ok(parse_print_path(data.success(), data.stdout_str()))
+}
+
+// `xcode-select -p` may print a path even when it no longer exists
+fn parse_print_path<S>(success: bool, stdout: S) -> Option<String>
+where
+    S: AsRef<str>,
+{
+    let path = stdout.as_ref().trim();
+    if success && !path.is_empty() && std::path::Path::new(path).exists() {
+        Some(String::from(path))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_is_constant() {
+        assert_eq!(XcodeClt::default().name(), "xcode-select --install");
+    }
+
+    #[test]
+    fn parse_print_path_requires_existing_path() {
+        let temp = std::env::temp_dir();
+        let temp = temp.to_string_lossy();
+        assert_eq!(
+            parse_print_path(true, format!("{}\n", temp)),
+            Some(String::from(temp.as_ref()))
+        );
+        assert_eq!(parse_print_path(false, format!("{}\n", temp)), None);
+        assert_eq!(
+            parse_print_path(true, "/Library/Developer/DoesNotExist\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use super::jobs::Execute;
+
+// one line per job, in config order, with its description (if any) on the next line
+pub fn list(jobs: &[impl Execute]) -> String {
+    jobs.iter()
+        .map(|job| {
+            let mut line = summary(job);
+            if let Some(d) = job.description() {
+                line.push_str(&format!("\n    {}", d));
+            }
+            line
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// jobs under the jobs that they need, starting with jobs that need nothing,
+// where a job that was already shown (e.g. as it needs several jobs) is marked with "(*)"
+pub fn tree(jobs: &[impl Execute]) -> String {
+    let mut needed_by = HashMap::<String, Vec<&dyn Execute>>::new();
+    for job in jobs {
+        for need in job.needs() {
+            needed_by.entry(need).or_default().push(job);
+        }
+    }
+    let names: HashSet<String> = jobs.iter().map(|j| j.name()).collect();
+    let mut lines = Vec::new();
+    let mut shown = HashSet::<String>::new();
+    for job in jobs {
+        // jobs with unknown needs would otherwise never be shown
+        if job.needs().iter().all(|n| !names.contains(n)) {
+            tree_lines(job, 0, &needed_by, &mut shown, &mut lines);
+        }
+    }
+    // e.g. jobs in a cycle
+    for job in jobs {
+        if !shown.contains(&job.name()) {
+            tree_lines(job, 0, &needed_by, &mut shown, &mut lines);
+        }
+    }
+    lines.join("\n")
+}
+
+fn tree_lines(
+    job: &dyn Execute,
+    depth: usize,
+    needed_by: &HashMap<String, Vec<&dyn Execute>>,
+    shown: &mut HashSet<String>,
+    lines: &mut Vec<String>,
+) {
+    let indent = "    ".repeat(depth);
+    let name = job.name();
+    if !shown.insert(name.clone()) {
+        lines.push(format!("{}{} (*)", indent, name));
+        return;
+    }
+    lines.push(format!("{}{}", indent, summary(job)));
+    for j in needed_by.get(&name).into_iter().flatten() {
+        tree_lines(*j, depth + 1, needed_by, shown, lines);
+    }
+}
+
+// Graphviz, with an edge from each job to the jobs that need it
+pub fn dot(jobs: &[impl Execute]) -> String {
+    let mut lines = vec![String::from("digraph tuning {")];
+    for job in jobs {
+        lines.push(format!(
+            "    {:?} [label={:?}];",
+            job.name(),
+            format!("{}\n({})", job.name(), job.kind())
+        ));
+    }
+    for (need, job) in edges(jobs) {
+        lines.push(format!(
+            "    {:?} -> {:?};",
+            jobs[need].name(),
+            jobs[job].name()
+        ));
+    }
+    lines.push(String::from("}"));
+    lines.join("\n")
+}
+
+// like `dot()`, but for Markdown that renders Mermaid, e.g. on GitHub
+pub fn mermaid(jobs: &[impl Execute]) -> String {
+    let mut lines = vec![String::from("flowchart TD")];
+    // names can have characters that ids cannot
+    for (i, job) in jobs.iter().enumerate() {
+        lines.push(format!(
+            "    job{}[\"{} ({})\"]",
+            i,
+            job.name().replace('"', "#quot;"),
+            job.kind()
+        ));
+    }
+    for (need, job) in edges(jobs) {
+        lines.push(format!("    job{} --> job{}", need, job));
+    }
+    lines.join("\n")
+}
+
+// (need, job) indexes into `jobs`, skipping needs that are not jobs
+fn edges(jobs: &[impl Execute]) -> Vec<(usize, usize)> {
+    let indexes: HashMap<String, usize> = jobs
+        .iter()
+        .enumerate()
+        .map(|(i, j)| (j.name(), i))
+        .collect();
+    let mut edges = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        for need in job.needs() {
+            if let Some(n) = indexes.get(&need) {
+                edges.push((*n, i));
+            }
+        }
+    }
+    edges
+}
+
+// e.g. "link vimrc (file) tags: vim needs: clone dotfiles"
+fn summary(job: &(impl Execute + ?Sized)) -> String {
+    let mut s = format!("{} ({})", job.name(), job.kind());
+    let tags = job.tags();
+    if !tags.is_empty() {
+        s.push_str(&format!(" tags: {}", tags.join(", ")));
+    }
+    let needs = job.needs();
+    if !needs.is_empty() {
+        s.push_str(&format!(" needs: {}", needs.join(", ")));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    use crate::lib::jobs::{Error, Main};
+
+    const INPUT: &str = r#"
+        [[jobs]]
+        name = "vimrc"
+        type = "command"
+        command = "true"
+        description = "link ~/.vimrc"
+        needs = ["dotfiles", "vim"]
+        tags = ["vim"]
+
+        [[jobs]]
+        name = "dotfiles"
+        type = "command"
+        command = "true"
+
+        [[jobs]]
+        name = "vim"
+        type = "command"
+        command = "true"
+        tags = ["vim", "slow"]
+        "#;
+
+    #[test]
+    fn list_in_config_order() -> std::result::Result<(), Error> {
+        let m = Main::try_from(INPUT)?;
+        assert_eq!(
+            list(&m.jobs),
+            "vimrc (command) tags: vim needs: dotfiles, vim\n    link ~/.vimrc\ndotfiles (command)\nvim (command) tags: vim, slow"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dot_and_mermaid_edges_from_needs() -> std::result::Result<(), Error> {
+        let m = Main::try_from(INPUT)?;
+        let got = dot(&m.jobs);
+        assert!(got.starts_with("digraph tuning {\n"));
+        assert!(got.contains("    \"vimrc\" [label=\"vimrc\\n(command)\"];\n"));
+        assert!(got.contains("    \"dotfiles\" -> \"vimrc\";\n    \"vim\" -> \"vimrc\";\n}"));
+
+        let got = mermaid(&m.jobs);
+        assert!(got.starts_with("flowchart TD\n    job0[\"vimrc (command)\"]\n"));
+        assert!(got.ends_with("    job1 --> job0\n    job2 --> job0"));
+        Ok(())
+    }
+
+    #[test]
+    fn tree_under_needs() -> std::result::Result<(), Error> {
+        let m = Main::try_from(INPUT)?;
+        assert_eq!(
+            tree(&m.jobs),
+            "dotfiles (command)\n    vimrc (command) tags: vim needs: dotfiles, vim\nvim (command) tags: vim, slow\n    vimrc (*)"
+        );
+        Ok(())
+    }
+}
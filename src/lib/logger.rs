@@ -0,0 +1,54 @@
+use std::io::{self, Write};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// writes log messages to stderr, so they do not mix with job output or events on stdout
+struct Logger {
+    level: LevelFilter,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // not messages from dependencies
+        metadata.level() <= self.level && metadata.target().starts_with(env!("CARGO_PKG_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(io::stderr(), "{}", line(record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+// only the first call has any effect
+pub fn init(level: LevelFilter) {
+    if log::set_boxed_logger(Box::new(Logger { level })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+// e.g. "warn: unable to read ...", but without a prefix for the usual messages
+fn line(level: Level, message: &std::fmt::Arguments) -> String {
+    match level {
+        Level::Info => message.to_string(),
+        _ => format!("{}: {}", level.as_str().to_lowercase(), message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_starts_with_level() {
+        assert_eq!(
+            line(Level::Warn, &format_args!("{} is broken", "a")),
+            "warn: a is broken"
+        );
+        assert_eq!(line(Level::Info, &format_args!("reading")), "reading");
+    }
+}
@@ -1,4 +1,15 @@
+pub mod add;
+pub mod diff;
+pub mod events;
+pub mod explain;
 pub mod facts;
+pub mod fmt;
+pub mod init;
 pub mod jobs;
+pub mod list;
+pub mod logger;
 pub mod runner;
+pub mod schedule;
+pub mod state;
 pub mod template;
+pub mod watch;
@@ -1,123 +1,399 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    env,
+    path::PathBuf,
+    sync::{mpsc, Arc, Condvar, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
+use colored::*;
+use tera::Context;
 use thiserror::Error as ThisError;
 
-use crate::jobs::{self, is_result_done, is_result_settled, Execute, Status};
+use crate::{
+    events::{self, Events},
+    jobs::{self, is_result_done, is_result_settled, Execute, Status},
+    state,
+};
 
-// TODO: detect number of CPUs
-const MAX_THREADS: usize = 2;
+// how long to wait for a timed-out job to stop, after its processes are killed
+const STOP_GRACE: Duration = Duration::from_millis(500);
 
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error("jobs need each other: {}", names.join(" -> "))]
+    Cycle { names: Vec<String> },
+    #[error("more than one job is named {:?}", name)]
+    DuplicateName { name: String },
+    #[error(transparent)]
+    Events {
+        #[from]
+        source: events::Error,
+    },
+    #[error("job {:?}: {}", job, problem)]
+    Invalid { job: String, problem: String },
     #[error(transparent)]
-    Job {
+    State {
         #[from]
-        source: jobs::Error,
+        source: state::Error,
+    },
+    #[error(
+        "job {:?} needs {:?}, which does not exist{}",
+        job,
+        need,
+        suggestion.as_ref().map(|s| format!(", did you mean {:?}?", s)).unwrap_or_default()
+    )]
+    UnknownNeed {
+        job: String,
+        need: String,
+        suggestion: Option<String>,
     },
 }
 
-// pub type Result = std::result::Result<(), Error>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    // only report what jobs would change, see `Execute::check()`
+    pub check: bool,
+    // ask before executing each destructive job, see `Execute::is_destructive()`,
+    // which is Skipped unless the answer is yes
+    pub confirm: bool,
+    // stop starting new jobs at this time, and time out jobs that are still running,
+    // e.g. for an unattended run
+    pub deadline: Option<Instant>,
+    // show how jobs change the contents of files
+    pub diff: bool,
+    // also append every job Status transition here as JSON lines, "-" for stdout
+    pub events: Option<PathBuf>,
+    // the most jobs with each tag or type that can run at the same time
+    pub limits: HashMap<String, usize>,
+    // where to read and record how long each job took,
+    // so that the jobs at the start of the longest chains of needs can start first
+    pub history: Option<PathBuf>,
+    // write each job's output and result to a file in this directory
+    pub logs: Option<PathBuf>,
+    // stop starting new jobs once any job fails, letting InProgress jobs finish
+    pub fail_fast: bool,
+    // if not empty, only jobs with these names (and their needs) run
+    pub only: Vec<String>,
+    // print nothing for people on stdout, as the caller prints a JSON report there
+    pub json_report: bool,
+    // dispatch jobs in the order they are configured, rather than as soon as they can run
+    pub ordered: bool,
+    // only print jobs that did something, or that need attention,
+    // and count the rest
+    pub quiet: bool,
+    // jobs configured before the job with this name are assumed to be done, e.g. to resume
+    pub start_at: Option<String>,
+    // jobs with these names are Skipped, even if also targeted by `only` or `tags`
+    pub skip: Vec<String>,
+    // jobs with any of these tags are Skipped, even if also targeted by `tags`
+    pub skip_tags: Vec<String>,
+    // jobs that succeeded in an earlier run, so do not need to run again
+    pub succeeded: HashSet<String>,
+    // jobs that needed no change last time, and have not changed since,
+    // so do not need to run again, see `Job::fingerprint()`
+    pub unchanged: HashSet<String>,
+    // print job output as it arrives, prefixed by job name,
+    // rather than all at once when each job finishes
+    pub stream: bool,
+    // if not empty, only jobs with any of these tags (and their needs) run
+    pub tags: Vec<String>,
+    // how many jobs can run at the same time, defaults to the number of CPUs
+    pub threads: Option<usize>,
+}
+impl Options {
+    fn threads(&self) -> usize {
+        self.threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(2, |n| n.get()))
+            .max(1)
+    }
+}
+
+// the state that worker threads share, guarded by a single Mutex
+struct Scheduler<J> {
+    // whether to stop starting new jobs,
+    // after any job failed with `Options::fail_fast`, or after `Options::deadline`
+    stopped: bool,
+    // jobs that have not yet been picked up by a worker thread
+    jobs: Vec<J>,
+    // see `ranks()`
+    ranks: HashMap<String, Duration>,
+    results: HashMap<String, jobs::Result>,
+    slots: Slots,
+}
+
+// what InProgress jobs hold, so that some other jobs wait for them
+#[derive(Debug, Default)]
+struct Slots {
+    // the most jobs with each tag or type that can run at the same time
+    limits: HashMap<String, usize>,
+    // named locks, each held by one job at a time
+    locks: HashSet<String>,
+    // how many InProgress jobs have each tag or type in `limits`
+    running: HashMap<String, usize>,
+}
+impl Slots {
+    // a limit of 0 is treated as 1, as those jobs would otherwise never run
+    fn is_free(&self, job: &impl Execute) -> bool {
+        job.lock().is_none_or(|l| !self.locks.contains(&l))
+            && self
+                .limited(job)
+                .iter()
+                .all(|c| self.running.get(c).copied().unwrap_or_default() < self.limits[c].max(1))
+    }
+
+    // the tags and type of `job` that have a limit
+    fn limited(&self, job: &impl Execute) -> Vec<String> {
+        let mut classes = job.tags();
+        classes.push(String::from(job.kind()));
+        classes.sort();
+        classes.dedup();
+        classes.retain(|c| self.limits.contains_key(c));
+        classes
+    }
+
+    // returns what to `release()` once the job finishes
+    fn take(&mut self, job: &impl Execute) -> (Option<String>, Vec<String>) {
+        let lock = job.lock();
+        if let Some(l) = &lock {
+            self.locks.insert(l.clone());
+        }
+        let limited = self.limited(job);
+        for c in &limited {
+            *self.running.entry(c.clone()).or_default() += 1;
+        }
+        (lock, limited)
+    }
+
+    fn release(&mut self, (lock, limited): (Option<String>, Vec<String>)) {
+        if let Some(l) = &lock {
+            self.locks.remove(l);
+        }
+        for c in &limited {
+            if let Some(n) = self.running.get_mut(c) {
+                *n -= 1;
+            }
+        }
+    }
+}
 
 // TODO: consider extracting the concern of println!ing Status
-pub fn run(jobs: Vec<(impl Execute + Send + 'static)>) {
+// returns the final result for every job, keyed by job name
+fn run_with_events(
+    jobs: Vec<impl Execute + Send + 'static>,
+    mut context: Context,
+    options: &Options,
+    events: &Arc<Events>,
+    history: &HashMap<String, Duration>,
+) -> Result<HashMap<String, jobs::Result>> {
+    validate(&jobs)?;
+    let ranks = ranks(&jobs, history);
     let mut results = HashMap::<String, jobs::Result>::new();
-    // ensure every job has a registered Status
+    // ensure every job has a registered Status,
+    // and that every job has its "when" checked once its "needs" are Done
+    let targeted = targeted_names(&jobs, options);
+    let before: HashSet<String> = match &options.start_at {
+        Some(start) => jobs
+            .iter()
+            .map(|j| j.name())
+            .take_while(|n| n != start)
+            .collect(),
+        None => HashSet::new(),
+    };
     jobs.iter().for_each(|job| {
-        if job.needs().is_empty() {
-            results.insert(job.name(), Ok(Status::Pending));
+        let name = job.name();
+        let reason = if before.contains(&name) {
+            Some("before the starting job")
+        } else if options.succeeded.contains(&name) {
+            Some("succeeded last run")
+        } else if options.unchanged.contains(&name) {
+            Some("unchanged since last run")
+        } else {
+            None
+        };
+        if !targeted.contains(&name) {
+            log::debug!("job {:?}: skipped: not targeted by tags or names", &name);
+            let result = Ok(Status::Skipped);
+            events.job(&name, &result, None);
+            register(&mut context, job.register(), &result, "");
+            results.insert(name, result);
+        } else if let Some(r) = reason {
+            // Done, as far as jobs that need it are concerned
+            let result = Ok(Status::NoChange(String::from(r)));
+            events.job(&name, &result, None);
+            register(&mut context, job.register(), &result, "");
+            results.insert(name, result);
         } else {
-            results.insert(job.name(), Ok(Status::Blocked));
+            results.insert(name, Ok(Status::Blocked));
         }
     });
+    let jobs: Vec<_> = jobs
+        .into_iter()
+        .filter(|job| is_equal_status(results.get(&job.name()).unwrap(), &Status::Blocked))
+        .collect();
 
-    let jobs_arc = Arc::new(Mutex::new(jobs));
-    let results_arc = Arc::new(Mutex::new(results));
-    let mut handles = Vec::<thread::JoinHandle<_>>::with_capacity(MAX_THREADS);
-    for _ in 0..MAX_THREADS {
-        let my_jobs_arc = jobs_arc.clone();
-        let my_results_arc = results_arc.clone();
+    let context_arc = Arc::new(Mutex::new(context));
+    // workers sleep on the Condvar until another job settles,
+    // rather than spinning on the shared state
+    let shared_arc = Arc::new((
+        Mutex::new(Scheduler {
+            stopped: false,
+            jobs,
+            ranks,
+            results,
+            slots: Slots {
+                limits: options.limits.clone(),
+                ..Default::default()
+            },
+        }),
+        Condvar::new(),
+    ));
+    let check = options.check;
+    // a check does not change anything, so there is nothing to confirm
+    let confirm = options.confirm && !check;
+    let deadline = options.deadline;
+    let fail_fast = options.fail_fast;
+    let ordered = options.ordered;
+    let threads = options.threads();
+    let mut handles = Vec::<thread::JoinHandle<_>>::with_capacity(threads);
+    for _ in 0..threads {
+        let my_context_arc = context_arc.clone();
+        let my_events = events.clone();
+        let my_shared_arc = shared_arc.clone();
 
         let handle = thread::spawn(move || {
+            let (scheduler, settled) = &*my_shared_arc;
             loop {
                 let current_job;
+                let held;
                 {
-                    // acquire locks
-                    let mut my_jobs = my_jobs_arc.lock().unwrap();
-                    let mut my_results = my_results_arc.lock().unwrap();
-
-                    // move jobs with false "when" over to Skipped
-                    for job in my_jobs.iter() {
-                        let name = job.name();
-                        if !job.when() {
-                            my_results.insert(name.clone(), Ok(Status::Skipped));
+                    // acquire lock
+                    let mut my_scheduler = scheduler.lock().unwrap();
+                    let index = loop {
+                        let Scheduler {
+                            stopped: my_stopped,
+                            jobs: my_jobs,
+                            slots: my_slots,
+                            ranks: my_ranks,
+                            results: my_results,
+                        } = &mut *my_scheduler;
+
+                        if !*my_stopped && deadline.is_some_and(|d| Instant::now() >= d) {
+                            if my_events.is_human() {
+                                println!("timed out: not starting any more jobs");
+                            }
+                            *my_stopped = true;
+                            stop(my_results);
                         }
-                    }
 
-                    // move Blocked jobs with satifisfied needs over to Pending
-                    for job in my_jobs.iter() {
-                        let name = job.name();
-                        if is_equal_status(my_results.get(&name).unwrap(), &Status::Blocked)
-                            && job
-                                .needs()
-                                .iter()
-                                .all(|n| is_result_done(my_results.get(n).unwrap()))
-                        {
-                            my_results.insert(name, Ok(Status::Pending));
+                        // move Blocked jobs with satifisfied needs over to Pending,
+                        // or over to Skipped if "when" is false
+                        if !*my_stopped {
+                            let mut my_context = my_context_arc.lock().unwrap();
+                            let promoted = promote_blocked(
+                                my_jobs,
+                                my_results,
+                                &mut my_context,
+                                &my_events,
+                                true,
+                            );
+                            // jobs that are only waiting on "after" would otherwise wait forever
+                            if !promoted && !is_any_running(my_results) {
+                                promote_blocked(
+                                    my_jobs,
+                                    my_results,
+                                    &mut my_context,
+                                    &my_events,
+                                    false,
+                                );
+                            }
                         }
-                    }
 
-                    // check exit/terminate condition for thread
-                    if is_all_settled(&my_results) {
-                        return; // nothing left to do
-                    }
-                    // there must be at least one available job
+                        // check exit/terminate condition for thread
+                        if is_all_settled(my_results) {
+                            // wake any other idle workers so they can exit too
+                            settled.notify_all();
+                            return; // nothing left to do
+                        }
 
-                    // cherry-pick first available job
-                    let index = match my_jobs.iter().enumerate().find(|(_, job)| {
-                        let name = job.name();
-                        // this .unwrap() is fine, as all jobs have a registered Status
-                        is_equal_status(my_results.get(&name).unwrap(), &Status::Pending)
-                    }) {
-                        Some((i, _)) => i,
-                        None => {
-                            // the only remaining jobs must already be InProgress
-                            // nothing left to do
-                            return;
+                        // cherry-pick first available job, that does not need a held lock
+                        let next = if ordered {
+                            next_in_order(my_jobs, my_results, my_slots)
+                        } else {
+                            next_available(my_jobs, my_results, my_slots, my_ranks)
+                        };
+                        if let Some(i) = next {
+                            break i;
                         }
+
+                        // the only remaining jobs must already be InProgress,
+                        // or waiting for a lock that an InProgress job will release,
+                        // or waiting their turn in config order,
+                        // so sleep until one of those settles
+                        my_scheduler = settled.wait(my_scheduler).unwrap();
                     };
-                    current_job = my_jobs.remove(index);
+                    current_job = my_scheduler.jobs.remove(index);
+                    held = my_scheduler.slots.take(&current_job);
                     let name = current_job.name();
-                    my_results.insert(name.clone(), Ok(Status::InProgress));
-                    println!(
-                        "job: {}: {}",
-                        &name,
-                        jobs::result_display(my_results.get(&name).unwrap())
-                    );
+                    my_scheduler
+                        .results
+                        .insert(name.clone(), Ok(Status::InProgress));
+                    my_events.job(&name, &Ok(Status::InProgress), None);
+                    if let Some(d) = current_job.description().filter(|_| {
+                        my_events.is_human() && my_events.is_shown(&Ok(Status::InProgress))
+                    }) {
+                        println!("    {}", d.dimmed());
+                    }
 
-                    // release/drop locks
+                    // release/drop lock
                 }
 
                 // execute job
                 let name = current_job.name();
-                let result = current_job.execute();
+                let ignore_errors = current_job.ignore_errors();
+                let register_as = current_job.register();
+                let started = Instant::now();
+                let (result, stdout, output) =
+                    if confirm && current_job.is_destructive() && !my_events.confirm(&name) {
+                        (Ok(Status::Skipped), String::new(), Vec::new())
+                    } else {
+                        execute_with_timeout(
+                            current_job,
+                            register_as.is_some(),
+                            check,
+                            deadline,
+                            my_events.clone(),
+                        )
+                    };
+                let duration = started.elapsed();
+                let result = match result {
+                    // still reported, but no longer blocks jobs that need this one
+                    Err(e) if ignore_errors => Ok(Status::Ignored(e.to_string())),
+                    r => r,
+                };
 
                 // record result of job
                 {
                     // acquire locks
-                    let mut my_results = my_results_arc.lock().unwrap();
+                    let mut my_scheduler = scheduler.lock().unwrap();
+                    let mut my_context = my_context_arc.lock().unwrap();
 
-                    my_results.insert(name.clone(), result);
-                    println!(
-                        "job: {}: {}",
-                        &name,
-                        jobs::result_display(my_results.get(&name).unwrap())
-                    );
+                    my_scheduler.slots.release(held);
+                    register(&mut my_context, register_as, &result, &stdout);
+                    if fail_fast && result.is_err() {
+                        my_scheduler.stopped = true;
+                        stop(&mut my_scheduler.results);
+                    }
+                    my_events.finished(&name, &result, duration, &output);
+                    my_scheduler.results.insert(name.clone(), result);
                     // release/drop locks
                 }
+                // jobs that need this one, or its lock or slot, may now be available
+                settled.notify_all();
             }
         });
         handles.push(handle);
@@ -126,6 +402,548 @@ pub fn run(jobs: Vec<(impl Execute + Send + 'static)>) {
     for handle in handles {
         handle.join().expect("worker thread failed");
     }
+
+    // all worker threads have finished, so we hold the only reference
+    match Arc::try_unwrap(shared_arc) {
+        Ok((m, _)) => Ok(m.into_inner().unwrap().results),
+        Err(_) => unreachable!(),
+    }
+}
+
+// what happened in a run, for the caller to present, e.g. with `Events::summary()`
+#[derive(Debug, Default)]
+pub struct RunReport {
+    // how long each job that ran took
+    pub durations: HashMap<String, Duration>,
+    // what each job needs, to tell why a Blocked job never ran
+    pub needs: HashMap<String, Vec<String>>,
+    pub results: HashMap<String, jobs::Result>,
+}
+impl RunReport {
+    // failure outranks change, which outranks everything else
+    pub fn status(&self) -> RunStatus {
+        if self
+            .results
+            .iter()
+            .any(|(name, r)| r.is_err() || self.is_blocked_by_failure(name))
+        {
+            RunStatus::Failed
+        } else if self
+            .results
+            .values()
+            .any(|r| matches!(r, Ok(Status::Changed(_, _)) | Ok(Status::WouldChange(_, _))))
+        {
+            RunStatus::Changed
+        } else {
+            RunStatus::Unchanged
+        }
+    }
+
+    // whether the job never ran because a job it needs failed, or because the run stopped,
+    // rather than because a job it needs was skipped
+    fn is_blocked_by_failure(&self, name: &str) -> bool {
+        if !matches!(self.results.get(name), Some(Ok(Status::Blocked))) {
+            return false;
+        }
+        let needs = self.needs.get(name).map(Vec::as_slice).unwrap_or_default();
+        // `validate()` has already ruled out cycles, so this recursion ends
+        needs
+            .iter()
+            .all(|n| self.results.get(n).is_some_and(is_result_done))
+            || needs.iter().any(|n| {
+                matches!(self.results.get(n), Some(Err(_))) || self.is_blocked_by_failure(n)
+            })
+    }
+
+    // after a check, whether any job could not tell what it would change
+    pub fn has_unchecked(&self) -> bool {
+        self.results
+            .values()
+            .any(|r| matches!(r, Ok(Status::Unchecked(_))))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunStatus {
+    // any job changed something, or would change something
+    Changed,
+    // any job failed, or never ran because a job it needs failed
+    Failed,
+    Unchanged,
+}
+impl RunStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Changed => "changed",
+            Self::Failed => "failed",
+            Self::Unchanged => "unchanged",
+        }
+    }
+
+    // process exit code, like `terraform plan -detailed-exitcode`
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Changed => 2,
+            Self::Failed => 1,
+            Self::Unchanged => 0,
+        }
+    }
+}
+
+// runs `jobs`, then any `handlers` that a Changed job notifies,
+// that a failed job rescues with, or that a job that ran always runs,
+// each handler at most once, however many jobs notify it,
+// reporting each job to `events` as it goes
+pub fn run_with_handlers<J: Execute + Send + 'static>(
+    jobs: Vec<J>,
+    handlers: Vec<J>,
+    context: Context,
+    options: &Options,
+    events: &Arc<Events>,
+) -> Result<RunReport> {
+    let needs: HashMap<String, Vec<String>> = jobs
+        .iter()
+        .chain(handlers.iter())
+        .map(|j| (j.name(), j.needs()))
+        .collect();
+    // handlers for each job: always, notify, and rescue
+    let triggers: Vec<_> = jobs
+        .iter()
+        .map(|j| (j.name(), j.always(), j.notify(), j.rescue()))
+        .collect();
+    let history = match &options.history {
+        Some(p) => state::durations(p)?,
+        None => HashMap::new(),
+    };
+    let mut results = run_with_events(jobs, context.clone(), options, events, &history)?;
+
+    let notified: HashSet<String> = triggers
+        .into_iter()
+        .flat_map(|(name, always, notify, rescue)| {
+            let result = results.get(&name).unwrap();
+            let mut names = Vec::new();
+            if is_result_finished(result) && !is_equal_status(result, &Status::Skipped) {
+                names.extend(always);
+            }
+            match result {
+                Ok(Status::Changed(_, _)) | Ok(Status::WouldChange(_, _)) => names.extend(notify),
+                Ok(Status::Ignored(_)) | Err(_) => names.extend(rescue),
+                _ => {}
+            }
+            names
+        })
+        .collect();
+    let handlers: Vec<J> = handlers
+        .into_iter()
+        .filter(|h| notified.contains(&h.name()))
+        .collect();
+    if !handlers.is_empty() {
+        // handlers were chosen by the jobs that notified them, so no tags apply
+        let handler_options = Options {
+            check: options.check,
+            confirm: options.confirm,
+            deadline: options.deadline,
+            diff: options.diff,
+            events: options.events.clone(),
+            fail_fast: options.fail_fast,
+            history: options.history.clone(),
+            limits: options.limits.clone(),
+            logs: options.logs.clone(),
+            ordered: options.ordered,
+            quiet: options.quiet,
+            stream: options.stream,
+            threads: options.threads,
+            ..Default::default()
+        };
+        results.extend(run_with_events(
+            handlers,
+            context,
+            &handler_options,
+            events,
+            &history,
+        )?);
+    }
+    let durations = events.durations();
+    // a check is much quicker than a real run
+    if let (Some(p), false) = (&options.history, options.check) {
+        state::record_durations(p, &durations)?;
+    }
+    Ok(RunReport {
+        durations: durations.into_iter().collect(),
+        needs,
+        results,
+    })
+}
+
+// the job runs on its own thread, so that we can stop waiting for it;
+// processes it starts are killed at the same deadline, so we give it a moment to stop,
+// but a job that is stuck elsewhere is abandoned to finish in the background
+fn execute_with_timeout(
+    job: impl Execute + Send + 'static,
+    capture: bool,
+    check: bool,
+    deadline: Option<Instant>,
+    events: Arc<Events>,
+) -> (jobs::Result, String, Vec<u8>) {
+    // the job's own timeout, or what is left of the run, whichever is sooner
+    let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+    let timeout = match (job.timeout(), remaining) {
+        (Some(t), Some(r)) => t.min(r),
+        (Some(t), None) | (None, Some(t)) => t,
+        (None, None) => return execute_with_retries(&job, capture, check, &events),
+    };
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        jobs::set_deadline(Some(Instant::now() + timeout));
+        tx.send(execute_with_retries(&job, capture, check, &events))
+            .ok();
+    });
+    rx.recv_timeout(timeout).unwrap_or_else(|_| {
+        let (stdout, output) = rx
+            .recv_timeout(STOP_GRACE)
+            .map(|(_, stdout, output)| (stdout, output))
+            .unwrap_or_default();
+        (Err(jobs::Error::Timeout { timeout }), stdout, output)
+    })
+}
+
+// also returns stdout of the last attempt, if capturing,
+// and everything that every attempt wrote to stdout and stderr
+fn execute_with_retries(
+    job: &impl Execute,
+    capture: bool,
+    check: bool,
+    events: &Events,
+) -> (jobs::Result, String, Vec<u8>) {
+    let attempts = job.retries() + 1;
+    let mut attempt = 1;
+    match events.prefix(&job.name()) {
+        // with events on stdout, job output goes to stderr
+        Some(p) => jobs::start_streaming(p, !events.is_human()),
+        None => jobs::start_output(),
+    }
+    jobs::set_diff(events.is_diff());
+    loop {
+        if capture {
+            jobs::start_capture();
+        }
+        let result = if check { job.check() } else { job.execute() };
+        let stdout = jobs::finish_capture();
+        match result {
+            Err(e) if attempt < attempts && !jobs::is_past_deadline() => {
+                if events.is_human() {
+                    println!(
+                        "job: {}: {} (attempt {} of {}, retrying)",
+                        job.name(),
+                        jobs::result_display(&Err(e)),
+                        attempt,
+                        attempts
+                    );
+                }
+                thread::sleep(job.retry_delay());
+                attempt += 1;
+            }
+            result => return (result, stdout, jobs::finish_output()),
+        }
+    }
+}
+
+fn register(context: &mut Context, name: Option<String>, result: &jobs::Result, stdout: &str) {
+    if let Some(n) = name {
+        context.insert(n, &jobs::result_facts(result, stdout));
+    }
+}
+
+// jobs with a matching name and tag, plus everything they (indirectly) need
+fn targeted_names(jobs: &[impl Execute], options: &Options) -> HashSet<String> {
+    let is_skipped = |job: &dyn Execute| {
+        options.skip.contains(&job.name())
+            || job.tags().iter().any(|t| options.skip_tags.contains(t))
+    };
+    let mut targeted: HashSet<String> = jobs
+        .iter()
+        .filter(|job| options.only.is_empty() || options.only.contains(&job.name()))
+        .filter(|job| {
+            options.tags.is_empty() || job.tags().iter().any(|t| options.tags.contains(t))
+        })
+        .filter(|job| !is_skipped(*job))
+        .map(|job| job.name())
+        .collect();
+    if options.only.is_empty() && options.tags.is_empty() {
+        return targeted;
+    }
+
+    let mut queue: Vec<String> = targeted.iter().cloned().collect();
+    while let Some(name) = queue.pop() {
+        for job in jobs.iter().filter(|job| job.name() == name) {
+            for need in job.needs() {
+                let is_need_skipped = jobs.iter().any(|j| j.name() == need && is_skipped(j));
+                if !is_need_skipped && targeted.insert(need.clone()) {
+                    queue.push(need);
+                }
+            }
+        }
+    }
+    targeted
+}
+
+// jobs that need missing jobs or each other would otherwise stay Blocked forever
+fn validate(jobs: &[impl Execute]) -> Result<()> {
+    match problems(jobs).into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// everything that would stop `jobs` from running, not just the first problem
+pub fn problems(jobs: &[impl Execute]) -> Vec<Error> {
+    let mut problems = Vec::new();
+    let mut names = HashSet::<String>::new();
+    for job in jobs {
+        let name = job.name();
+        if !names.insert(name.clone()) {
+            problems.push(Error::DuplicateName { name });
+        }
+    }
+    let needs: HashMap<String, Vec<String>> = jobs.iter().map(|j| (j.name(), j.needs())).collect();
+    for job in jobs {
+        for need in job.needs() {
+            if !needs.contains_key(&need) {
+                problems.push(Error::UnknownNeed {
+                    job: job.name(),
+                    suggestion: closest(&need, needs.keys()),
+                    need,
+                });
+            }
+        }
+    }
+    for job in jobs {
+        for problem in job.problems() {
+            problems.push(Error::Invalid {
+                job: job.name(),
+                problem,
+            });
+        }
+    }
+    // a cycle is only reported once, starting from the earliest job in it
+    let mut visited = HashSet::<String>::new();
+    for job in jobs {
+        let mut path = Vec::<String>::new();
+        if let Err(e) = find_cycle(&job.name(), &needs, &mut visited, &mut path) {
+            problems.push(e);
+        }
+    }
+    problems
+}
+
+// the most similar name, if any are similar enough, e.g. to suggest a fix for a typo
+fn closest<'a>(name: &str, names: impl Iterator<Item = &'a String>) -> Option<String> {
+    names
+        .map(|n| (strsim::jaro_winkler(name, n), n))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, n)| n.clone())
+}
+
+// depth-first, where `path` holds the jobs between the starting job and `name`
+fn find_cycle(
+    name: &str,
+    needs: &HashMap<String, Vec<String>>,
+    visited: &mut HashSet<String>,
+    path: &mut Vec<String>,
+) -> Result<()> {
+    if let Some(i) = path.iter().position(|p| p == name) {
+        let mut names = path[i..].to_vec();
+        names.push(String::from(name));
+        return Err(Error::Cycle { names });
+    }
+    if !visited.insert(String::from(name)) {
+        return Ok(()); // already checked, and no cycle there
+    }
+    path.push(String::from(name));
+    for need in needs.get(name).into_iter().flatten() {
+        find_cycle(need, needs, visited, path)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+// jobs that have not started yet will never start
+fn stop(results: &mut HashMap<String, jobs::Result>) {
+    results.values_mut().for_each(|r| {
+        if is_equal_status(r, &Status::Pending) {
+            *r = Ok(Status::Blocked);
+        }
+    });
+}
+
+// returns true if any job moved out of Blocked
+fn promote_blocked(
+    jobs: &[impl Execute],
+    results: &mut HashMap<String, jobs::Result>,
+    context: &mut Context,
+    events: &Events,
+    wait_for_after: bool,
+) -> bool {
+    let mut promoted = false;
+    for job in jobs.iter() {
+        let name = job.name();
+        if !is_equal_status(results.get(&name).unwrap(), &Status::Blocked)
+            || !job
+                .needs()
+                .iter()
+                .all(|n| is_result_done(results.get(n).unwrap()))
+        {
+            continue;
+        }
+        // unlike "needs", any outcome will do, and unknown jobs are ignored
+        if wait_for_after
+            && job
+                .after()
+                .iter()
+                .any(|a| results.get(a).is_some_and(|r| !is_result_finished(r)))
+        {
+            continue;
+        }
+        let result = if !jobs::is_os_supported(&job.os()) {
+            if events.is_human() {
+                println!(
+                    "job: {}: {} (not for {})",
+                    &name,
+                    Status::Skipped,
+                    env::consts::OS
+                );
+            }
+            Ok(Status::Skipped)
+        } else {
+            match job.when(context) {
+                Ok(true) => Ok(Status::Pending),
+                Ok(false) => {
+                    log::debug!("job {:?}: skipped: \"when\" is false", &name);
+                    Ok(Status::Skipped)
+                }
+                Err(e) => Err(e),
+            }
+        };
+        if result.is_err() {
+            events.job(&name, &result, None);
+        } else {
+            events.emit(&name, &result, None);
+        }
+        if !is_equal_status(&result, &Status::Pending) {
+            register(context, job.register(), &result, "");
+        }
+        results.insert(name, result);
+        promoted = true;
+    }
+    promoted
+}
+
+// the Pending job with the highest rank, that does not need a held lock or slot,
+// or the first of them, if they have the same rank
+fn next_available(
+    jobs: &[impl Execute],
+    results: &HashMap<String, jobs::Result>,
+    slots: &Slots,
+    ranks: &HashMap<String, Duration>,
+) -> Option<usize> {
+    jobs.iter()
+        .enumerate()
+        .filter(|(_, job)| is_available(*job, results, slots))
+        .max_by_key(|(i, job)| (ranks.get(&job.name()).copied(), Reverse(*i)))
+        .map(|(i, _)| i)
+}
+
+// how long the longest chain of needs takes from each job, by `history`,
+// i.e. how long the job took last time, plus the highest rank of the jobs that need it
+fn ranks(jobs: &[impl Execute], history: &HashMap<String, Duration>) -> HashMap<String, Duration> {
+    let mut needed_by = HashMap::<String, Vec<String>>::new();
+    for job in jobs {
+        for need in job.needs() {
+            needed_by.entry(need).or_default().push(job.name());
+        }
+    }
+    let mut ranks = HashMap::new();
+    for job in jobs {
+        rank(&job.name(), &needed_by, history, &mut ranks);
+    }
+    ranks
+}
+
+// `validate()` has already ruled out cycles, so this recursion ends
+fn rank(
+    name: &str,
+    needed_by: &HashMap<String, Vec<String>>,
+    history: &HashMap<String, Duration>,
+    ranks: &mut HashMap<String, Duration>,
+) -> Duration {
+    if let Some(r) = ranks.get(name) {
+        return *r;
+    }
+    let longest = needed_by
+        .get(name)
+        .into_iter()
+        .flatten()
+        .map(|n| rank(n, needed_by, history, ranks))
+        .max()
+        .unwrap_or_default();
+    let r = history.get(name).copied().unwrap_or_default() + longest;
+    ranks.insert(String::from(name), r);
+    r
+}
+
+// like `next_available()`, but never past an earlier job,
+// unless nothing is InProgress that could make the earlier job available
+fn next_in_order(
+    jobs: &[impl Execute],
+    results: &HashMap<String, jobs::Result>,
+    slots: &Slots,
+) -> Option<usize> {
+    if !results
+        .values()
+        .any(|r| is_equal_status(r, &Status::InProgress))
+    {
+        // e.g. an earlier job needs a later one
+        return next_available(jobs, results, slots, &HashMap::new());
+    }
+    jobs.iter()
+        .position(|job| is_available(job, results, slots) || is_waiting(job, results))
+        .filter(|i| is_available(&jobs[*i], results, slots))
+}
+
+fn is_available(
+    job: &impl Execute,
+    results: &HashMap<String, jobs::Result>,
+    slots: &Slots,
+) -> bool {
+    // this .unwrap() is fine, as all jobs have a registered Status
+    is_equal_status(results.get(&job.name()).unwrap(), &Status::Pending) && slots.is_free(job)
+}
+
+// whether the job may yet become available, once other jobs finish
+fn is_waiting(job: &impl Execute, results: &HashMap<String, jobs::Result>) -> bool {
+    match results.get(&job.name()) {
+        Some(Ok(Status::Pending)) => true,
+        Some(Ok(Status::Blocked)) => job.needs().iter().all(|n| {
+            let r = results.get(n).unwrap();
+            is_result_done(r) || !is_result_finished(r)
+        }),
+        _ => false,
+    }
+}
+
+fn is_any_running(results: &HashMap<String, jobs::Result>) -> bool {
+    results
+        .values()
+        .any(|r| is_equal_status(r, &Status::Pending) || is_equal_status(r, &Status::InProgress))
+}
+
+// whether the job has an outcome, successful or not
+fn is_result_finished(result: &jobs::Result) -> bool {
+    !matches!(
+        result,
+        Ok(Status::Blocked) | Ok(Status::InProgress) | Ok(Status::Pending)
+    )
 }
 
 fn is_all_settled(results: &HashMap<String, jobs::Result>) -> bool {
@@ -145,26 +963,61 @@ mod tests {
 
     use super::*;
 
+    // like `run_with_handlers()`, but without handlers
+    fn run(
+        jobs: Vec<impl Execute + Send + 'static>,
+        context: Context,
+        options: &Options,
+    ) -> Result<HashMap<String, jobs::Result>> {
+        let events = Arc::new(Events::open(options)?);
+        run_with_events(jobs, context, options, &events, &HashMap::new())
+    }
+
     struct FakeJob {
+        after: Vec<String>,
+        always: Vec<String>,
+        // fail this many times before returning result
+        failures: usize,
+        ignore_errors: bool,
+        lock: Option<String>,
         name: String,
         needs: Vec<String>,
+        notify: Vec<String>,
+        os: Vec<String>,
+        register: Option<String>,
+        rescue: Vec<String>,
         result: jobs::Result,
+        retries: u32,
         sleep: Duration,
         spy_arc: Arc<Mutex<FakeJobSpy>>,
-        when: bool,
+        tags: Vec<String>,
+        timeout: Option<Duration>,
+        when: jobs::When,
     }
     impl Default for FakeJob {
         fn default() -> Self {
             Self {
+                after: Vec::<String>::new(),
+                always: Vec::<String>::new(),
+                failures: 0,
+                ignore_errors: false,
+                lock: None,
                 name: String::new(),
                 needs: Vec::<String>::new(),
+                notify: Vec::<String>::new(),
+                os: Vec::<String>::new(),
+                register: None,
+                rescue: Vec::<String>::new(),
                 result: Ok(jobs::Status::Done),
+                retries: 0,
                 sleep: Duration::from_millis(0),
                 spy_arc: Arc::new(Mutex::new(FakeJobSpy {
                     calls: 0,
                     time: None,
                 })),
-                when: true,
+                tags: Vec::<String>::new(),
+                timeout: None,
+                when: jobs::When::Bool(true),
             }
         }
     }
@@ -184,21 +1037,66 @@ mod tests {
         }
     }
     impl Execute for FakeJob {
+        fn check(&self) -> jobs::Result {
+            Ok(jobs::Status::WouldChange(
+                String::from("checked"),
+                self.name.clone(),
+            ))
+        }
+        fn after(&self) -> Vec<String> {
+            self.after.clone()
+        }
+        fn always(&self) -> Vec<String> {
+            self.always.clone()
+        }
         fn execute(&self) -> jobs::Result {
             thread::sleep(self.sleep);
             let mut my_spy = self.spy_arc.lock().unwrap();
             my_spy.calls += 1;
             my_spy.time = Some(Instant::now());
+            if my_spy.calls <= self.failures {
+                return Err(jobs::Error::SomethingBad);
+            }
             result_clone(&self.result)
         }
+        fn ignore_errors(&self) -> bool {
+            self.ignore_errors
+        }
+        fn kind(&self) -> &'static str {
+            "fake"
+        }
+        fn lock(&self) -> Option<String> {
+            self.lock.clone()
+        }
         fn name(&self) -> String {
             self.name.clone()
         }
         fn needs(&self) -> Vec<String> {
             self.needs.clone()
         }
-        fn when(&self) -> bool {
-            self.when
+        fn notify(&self) -> Vec<String> {
+            self.notify.clone()
+        }
+        fn os(&self) -> Vec<String> {
+            self.os.clone()
+        }
+        fn register(&self) -> Option<String> {
+            self.register.clone()
+        }
+        fn rescue(&self) -> Vec<String> {
+            self.rescue.clone()
+        }
+        fn retries(&self) -> u32 {
+            self.retries
+        }
+        fn tags(&self) -> Vec<String> {
+            self.tags.clone()
+        }
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+        fn when(&self, context: &Context) -> std::result::Result<bool, jobs::Error> {
+            self.when.evaluate(context)
         }
     }
 
@@ -221,12 +1119,12 @@ mod tests {
     #[test]
     fn run_does_not_execute_job_with_false_when_or_needs_job_with_false_when() {
         let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
-        a.when = false;
+        a.when = jobs::When::Bool(false);
         let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
         b.needs.push(String::from("a"));
 
         let jobs = vec![a, b];
-        run(jobs);
+        run(jobs, Context::new(), &Options::default()).unwrap();
 
         let my_a_spy = a_spy.lock().unwrap();
         my_a_spy.assert_never_called();
@@ -251,7 +1149,7 @@ mod tests {
             spy_arcs.push(spy_arc);
         }
 
-        run(jobs);
+        run(jobs, Context::new(), &Options::default()).unwrap();
 
         for spy_arc in spy_arcs {
             let spy = spy_arc.lock().unwrap();
@@ -267,7 +1165,11 @@ mod tests {
         b.sleep = Duration::from_millis(500);
 
         let jobs = vec![a, b];
-        run(jobs);
+        let options = Options {
+            threads: Some(2),
+            ..Default::default()
+        };
+        run(jobs, Context::new(), &options).unwrap();
 
         let my_a_spy = a_spy.lock().unwrap();
         let my_b_spy = b_spy.lock().unwrap();
@@ -279,6 +1181,54 @@ mod tests {
         assert!(my_b_spy.time.expect("b").elapsed() < Duration::from_millis(100));
     }
 
+    #[test]
+    fn run_wakes_idle_threads_when_needs_are_done() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(200);
+        b.needs = vec![String::from("a")];
+        b.sleep = Duration::from_millis(500);
+        c.needs = vec![String::from("a")];
+        c.sleep = Duration::from_millis(500);
+
+        let jobs = vec![a, b, c];
+        let options = Options {
+            threads: Some(2),
+            ..Default::default()
+        };
+        run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        let my_b_spy = b_spy.lock().unwrap();
+        let my_c_spy = c_spy.lock().unwrap();
+        my_b_spy.assert_called_once();
+        my_c_spy.assert_called_once();
+        // the thread that was idle while "a" ran must have picked up "b" or "c"
+        assert!(my_b_spy.time.expect("b").elapsed() < Duration::from_millis(100));
+        assert!(my_c_spy.time.expect("c").elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn run_executes_one_job_at_a_time_with_one_thread() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(200);
+        b.sleep = Duration::from_millis(200);
+
+        let jobs = vec![a, b];
+        let options = Options {
+            threads: Some(1),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
     #[test]
     fn run_executes_jobs_with_complex_needs() {
         const MAX_COUNT: usize = 100;
@@ -311,7 +1261,7 @@ mod tests {
             spy_arcs.push(spy_arc);
         }
 
-        run(jobs);
+        run(jobs, Context::new(), &Options::default()).unwrap();
 
         for i in 0..MAX_COUNT {
             let spy_arc = &spy_arcs[i];
@@ -354,7 +1304,7 @@ mod tests {
         a.needs.push(String::from("b"));
 
         let jobs = vec![a, b];
-        run(jobs);
+        run(jobs, Context::new(), &Options::default()).unwrap();
 
         let my_a_spy = a_spy.lock().unwrap();
         let my_b_spy = b_spy.lock().unwrap();
@@ -371,12 +1321,37 @@ mod tests {
         a.needs.push(String::from("b"));
 
         let jobs = vec![a, b];
-        run(jobs);
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
 
         let my_a_spy = a_spy.lock().unwrap();
         let my_b_spy = b_spy.lock().unwrap();
         my_a_spy.assert_never_called();
         my_b_spy.assert_called_once();
+        assert!(matches!(results.get("a"), Some(Ok(Status::Blocked))));
+        assert!(results.get("b").expect("b").is_err());
+    }
+
+    #[test]
+    fn run_with_fail_fast_stops_starting_jobs_after_a_failure() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, b_spy) = FakeJob::new("b", Err(jobs::Error::SomethingBad));
+        let (c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(300);
+
+        let jobs = vec![a, b, c];
+        let options = Options {
+            fail_fast: true,
+            threads: Some(2),
+            ..Default::default()
+        };
+        let results = run(jobs, Context::new(), &options).unwrap();
+
+        // "a" was already InProgress, so it is allowed to finish
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("a"), Some(Ok(Status::Done))));
+        assert!(matches!(results.get("c"), Some(Ok(Status::Blocked))));
     }
 
     #[test]
@@ -389,7 +1364,7 @@ mod tests {
         b.needs.push(String::from("c"));
 
         let jobs = vec![a, b, c];
-        run(jobs);
+        run(jobs, Context::new(), &Options::default()).unwrap();
 
         let my_a_spy = a_spy.lock().unwrap();
         let my_b_spy = b_spy.lock().unwrap();
@@ -399,10 +1374,666 @@ mod tests {
         my_c_spy.assert_called_once();
     }
 
+    #[test]
+    fn run_executes_ordered_job_when_needs_ignore_errors() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Err(jobs::Error::SomethingBad));
+        a.needs.push(String::from("b"));
+        b.ignore_errors = true;
+
+        let jobs = vec![a, b];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        assert!(matches!(results.get("b"), Some(Ok(Status::Ignored(_)))));
+    }
+
+    #[test]
+    fn run_retries_failed_jobs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.failures = 2;
+        a.retries = 2;
+        b.failures = 2;
+        b.retries = 1;
+
+        let jobs = vec![a, b];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        assert_eq!(a_spy.lock().unwrap().calls, 3);
+        assert_eq!(b_spy.lock().unwrap().calls, 2);
+        assert!(matches!(results.get("a"), Some(Ok(Status::Done))));
+        assert!(results.get("b").expect("b").is_err());
+    }
+
+    #[test]
+    fn run_stops_starting_jobs_after_deadline() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_secs(5);
+        b.needs.push(String::from("a"));
+
+        let started = Instant::now();
+        let options = Options {
+            deadline: Some(started + Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let results = run(vec![a, b], Context::new(), &options).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        b_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(
+            results.get("a"),
+            Some(Err(jobs::Error::Timeout { .. }))
+        ));
+        assert!(matches!(results.get("b"), Some(Ok(Status::Blocked))));
+    }
+
+    #[test]
+    fn run_stops_waiting_for_job_after_timeout() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(100);
+        a.timeout = Some(Duration::from_secs(5));
+        b.sleep = Duration::from_secs(5);
+        b.timeout = Some(Duration::from_millis(100));
+
+        let started = Instant::now();
+        let jobs = vec![a, b];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        a_spy.lock().unwrap().assert_called_once();
+        assert!(matches!(results.get("a"), Some(Ok(Status::Done))));
+        assert!(matches!(
+            results.get("b"),
+            Some(Err(jobs::Error::Timeout { .. }))
+        ));
+    }
+
+    #[test]
+    fn run_executes_after_jobs_once_they_finish_or_cannot_run() {
+        let (mut a, a_spy) = FakeJob::new("a", Err(jobs::Error::SomethingBad));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (mut d, d_spy) = FakeJob::new("d", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(100);
+        b.after.push(String::from("a"));
+        b.after.push(String::from("does not exist"));
+        // c is never unblocked, as a fails
+        c.needs.push(String::from("a"));
+        d.after.push(String::from("c"));
+
+        let jobs = vec![d, c, b, a];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        let a_time = a_spy.lock().unwrap().time.unwrap();
+        let b_time = b_spy.lock().unwrap().time.unwrap();
+        assert!(b_time >= a_time);
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        d_spy.lock().unwrap().assert_called_once();
+        assert!(matches!(results.get("b"), Some(Ok(Status::Done))));
+        assert!(matches!(results.get("c"), Some(Ok(Status::Blocked))));
+    }
+
+    #[test]
+    fn run_errs_on_cycle_in_needs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (d, _) = FakeJob::new("d", Ok(jobs::Status::Done));
+        a.needs.push(String::from("d"));
+        a.needs.push(String::from("b"));
+        b.needs.push(String::from("c"));
+        c.needs.push(String::from("a"));
+
+        let jobs = vec![a, b, c, d];
+        match run(jobs, Context::new(), &Options::default()) {
+            Err(e) => assert_eq!(e.to_string(), "jobs need each other: a -> b -> c -> a"),
+            Ok(_) => panic!("expected an error"),
+        }
+        a_spy.lock().unwrap().assert_never_called();
+    }
+
+    #[test]
+    fn run_errs_on_unknown_needs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, _) = FakeJob::new("install rustup", Ok(jobs::Status::Done));
+        a.needs.push(String::from("instal rustup"));
+
+        let jobs = vec![a, b];
+        match run(jobs, Context::new(), &Options::default()) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                r#"job "a" needs "instal rustup", which does not exist, did you mean "install rustup"?"#
+            ),
+            Ok(_) => panic!("expected an error"),
+        }
+        a_spy.lock().unwrap().assert_never_called();
+    }
+
+    #[test]
+    fn problems_lists_every_problem() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (c2, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        a.needs.push(String::from("b"));
+        b.needs.push(String::from("a"));
+        b.needs.push(String::from("x"));
+        c.needs.push(String::from("y"));
+
+        let got: Vec<String> = problems(&[a, b, c, c2])
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                r#"more than one job is named "c""#,
+                r#"job "b" needs "x", which does not exist"#,
+                r#"job "c" needs "y", which does not exist"#,
+                "jobs need each other: a -> b -> a",
+            ]
+        );
+    }
+
+    #[test]
+    fn closest_names() {
+        let names = [String::from("install git"), String::from("link vimrc")];
+        assert_eq!(
+            closest("instal git", names.iter()),
+            Some(String::from("install git"))
+        );
+        assert_eq!(closest("something else", names.iter()), None);
+    }
+
+    #[test]
+    fn run_checks_instead_of_executing() {
+        let (a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        b.needs.push(String::from("a"));
+
+        let jobs = vec![a, b];
+        let options = Options {
+            check: true,
+            ..Default::default()
+        };
+        let results = run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_never_called();
+        b_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(
+            results.get("b"),
+            Some(Ok(Status::WouldChange(_, _)))
+        ));
+    }
+
+    #[test]
+    fn run_executes_jobs_with_the_same_lock_one_at_a_time() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.lock = Some(String::from("apt"));
+        a.sleep = Duration::from_millis(200);
+        b.lock = Some(String::from("apt"));
+        b.sleep = Duration::from_millis(200);
+
+        let jobs = vec![a, b];
+        let start = Instant::now();
+        run(jobs, Context::new(), &Options::default()).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn run_limits_jobs_with_the_same_tag_or_type() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (mut d, _) = FakeJob::new("d", Ok(jobs::Status::Done));
+        let (mut e, _) = FakeJob::new("e", Ok(jobs::Status::Done));
+        for job in [&mut a, &mut b, &mut c, &mut d, &mut e].iter_mut() {
+            job.sleep = Duration::from_millis(200);
+        }
+        a.tags.push(String::from("network"));
+        b.tags.push(String::from("network"));
+
+        let mut options = Options {
+            threads: Some(4),
+            ..Default::default()
+        };
+        options.limits.insert(String::from("network"), 1);
+        let start = Instant::now();
+        run(vec![a, b, c], Context::new(), &options).unwrap();
+
+        // "c" filled a free thread while "b" waited for "a", so finished first
+        assert!(c_spy.lock().unwrap().time.expect("c") < start + Duration::from_millis(400));
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        // every job has the "fake" type
+        options.limits.insert(String::from("fake"), 1);
+        let start = Instant::now();
+        run(vec![d, e], Context::new(), &options).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn run_skips_jobs_for_other_os() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        a.os.push(String::from(env::consts::OS));
+        b.os.push(String::from("plan9"));
+
+        let jobs = vec![a, b];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("b"), Some(Ok(Status::Skipped))));
+    }
+
+    #[test]
+    fn run_with_handlers_runs_notified_handlers_once() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::Changed(String::new(), String::new())));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Changed(String::new(), String::new())));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::NoChange(String::new())));
+        let (x, x_spy) = FakeJob::new("x", Ok(jobs::Status::Done));
+        let (y, y_spy) = FakeJob::new("y", Ok(jobs::Status::Done));
+        a.notify.push(String::from("x"));
+        b.notify.push(String::from("x"));
+        c.notify.push(String::from("y"));
+
+        let events = Arc::new(Events::open(&Options::default()).unwrap());
+        let report = run_with_handlers(
+            vec![a, b, c],
+            vec![x, y],
+            Context::new(),
+            &Options::default(),
+            &events,
+        )
+        .unwrap();
+        let results = report.results;
+
+        x_spy.lock().unwrap().assert_called_once();
+        y_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("x"), Some(Ok(Status::Done))));
+        assert!(!results.contains_key("y"));
+    }
+
+    #[test]
+    fn run_with_handlers_runs_rescue_and_always_handlers() {
+        let (mut a, _) = FakeJob::new("a", Err(jobs::Error::SomethingBad));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (x, x_spy) = FakeJob::new("x", Ok(jobs::Status::Done));
+        let (y, y_spy) = FakeJob::new("y", Ok(jobs::Status::Done));
+        let (z, z_spy) = FakeJob::new("z", Ok(jobs::Status::Done));
+        a.rescue.push(String::from("x"));
+        a.always.push(String::from("y"));
+        b.rescue.push(String::from("z"));
+        // "c" is Blocked, so never runs
+        c.needs.push(String::from("a"));
+        c.always.push(String::from("z"));
+
+        let events = Arc::new(Events::open(&Options::default()).unwrap());
+        run_with_handlers(
+            vec![a, b, c],
+            vec![x, y, z],
+            Context::new(),
+            &Options::default(),
+            &events,
+        )
+        .unwrap();
+
+        x_spy.lock().unwrap().assert_called_once();
+        y_spy.lock().unwrap().assert_called_once();
+        z_spy.lock().unwrap().assert_never_called();
+    }
+
+    #[test]
+    fn run_registers_results_for_later_when() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::NoChange(String::from("v1"))));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        a.register = Some(String::from("a_result"));
+        b.needs.push(String::from("a"));
+        b.when = jobs::When::Expression(String::from("a_result.message == 'v1'"));
+        c.needs.push(String::from("a"));
+        c.when = jobs::When::Expression(String::from("a_result.changed"));
+
+        let jobs = vec![a, b, c];
+        let results = run(jobs, Context::new(), &Options::default()).unwrap();
+
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("c"), Some(Ok(Status::Skipped))));
+    }
+
+    #[test]
+    fn run_executes_only_targeted_tags_and_their_needs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (mut d, d_spy) = FakeJob::new("d", Ok(jobs::Status::Done));
+        a.tags.push(String::from("shell"));
+        a.needs.push(String::from("b"));
+        c.tags.push(String::from("gui"));
+        d.tags.push(String::from("shell"));
+        d.tags.push(String::from("slow"));
+
+        let jobs = vec![a, b, c, d];
+        let options = Options {
+            skip_tags: vec![String::from("slow")],
+            tags: vec![String::from("shell")],
+            ..Default::default()
+        };
+        let results = run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        d_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("c"), Some(Ok(Status::Skipped))));
+        assert!(matches!(results.get("d"), Some(Ok(Status::Skipped))));
+    }
+
+    #[test]
+    fn run_assumes_jobs_before_start_at_are_done() {
+        let (a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        b.needs.push(String::from("a"));
+        c.needs.push(String::from("a"));
+
+        let jobs = vec![a, b, c];
+        let options = Options {
+            start_at: Some(String::from("b")),
+            ..Default::default()
+        };
+        let results = run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_never_called();
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_called_once();
+        assert!(matches!(results.get("a"), Some(Ok(Status::NoChange(_)))));
+    }
+
+    #[test]
+    fn run_executes_only_named_jobs_and_their_needs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        let (d, d_spy) = FakeJob::new("d", Ok(jobs::Status::Done));
+        a.needs.push(String::from("b"));
+
+        let jobs = vec![a, b, c, d];
+        let options = Options {
+            only: vec![String::from("a"), String::from("c")],
+            skip: vec![String::from("c")],
+            ..Default::default()
+        };
+        let results = run(jobs, Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_called_once();
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        d_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(results.get("c"), Some(Ok(Status::Skipped))));
+        assert!(matches!(results.get("d"), Some(Ok(Status::Skipped))));
+    }
+
     fn result_clone(result: &jobs::Result) -> jobs::Result {
         match result {
             Ok(s) => Ok(s.clone()),
             Err(_) => Err(jobs::Error::SomethingBad),
         }
     }
+    #[test]
+    fn status_prefers_failure_over_change() {
+        let mut report = RunReport::default();
+        report
+            .results
+            .insert(String::from("a"), Ok(Status::NoChange(String::from("a"))));
+        assert_eq!(report.status(), RunStatus::Unchanged);
+        assert_eq!(report.status().exit_code(), 0);
+
+        // not knowing is not the same as a change
+        report.results.insert(
+            String::from("u"),
+            Ok(Status::Unchecked(String::from("not checked"))),
+        );
+        assert_eq!(report.status(), RunStatus::Unchanged);
+        assert!(report.has_unchecked());
+
+        report.results.insert(
+            String::from("b"),
+            Ok(Status::WouldChange(String::from("b"), String::from("c"))),
+        );
+        assert_eq!(report.status(), RunStatus::Changed);
+        assert_eq!(report.status().exit_code(), 2);
+
+        report
+            .results
+            .insert(String::from("c"), Err(jobs::Error::SomethingBad));
+        assert_eq!(report.status(), RunStatus::Failed);
+        assert_eq!(report.status().exit_code(), 1);
+    }
+    #[test]
+    fn status_only_fails_jobs_blocked_by_failure() {
+        let (mut a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+        a.when = jobs::When::Bool(false);
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        b.needs.push(String::from("a"));
+
+        let events = Arc::new(Events::open(&Options::default()).unwrap());
+        let report = run_with_handlers(
+            vec![a, b],
+            Vec::new(),
+            Context::new(),
+            &Options::default(),
+            &events,
+        )
+        .unwrap();
+
+        b_spy.lock().unwrap().assert_never_called();
+        assert!(matches!(report.results.get("b"), Some(Ok(Status::Blocked))));
+        assert_eq!(report.status(), RunStatus::Unchanged);
+
+        let (c, _) = FakeJob::new("c", Err(jobs::Error::SomethingBad));
+        let (mut d, _) = FakeJob::new("d", Ok(jobs::Status::Done));
+        d.needs.push(String::from("c"));
+        let (mut e, _) = FakeJob::new("e", Ok(jobs::Status::Done));
+        e.needs.push(String::from("d"));
+
+        let report = run_with_handlers(
+            vec![c, d, e],
+            Vec::new(),
+            Context::new(),
+            &Options::default(),
+            &events,
+        )
+        .unwrap();
+
+        assert_eq!(report.status(), RunStatus::Failed);
+        assert!(report.is_blocked_by_failure("d"));
+        // "e" never ran because "d" never ran
+        assert!(report.is_blocked_by_failure("e"));
+    }
+    #[test]
+    fn run_appends_events_as_json_lines() {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        let path = temp.join("events.jsonl");
+        let (a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+
+        let options = Options {
+            events: Some(path.clone()),
+            ..Default::default()
+        };
+        run(vec![a], Context::new(), &options).unwrap();
+
+        let got: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .expect("events file")
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("JSON line"))
+            .collect();
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0]["status"], "pending");
+        assert_eq!(got[1]["status"], "inprogress");
+        assert_eq!(got[2]["status"], "done");
+        assert!(got[2]["duration_ms"].is_u64());
+    }
+    #[test]
+    fn run_calls_hooks_for_each_transition() {
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+        impl events::Hook for Recorder {
+            fn status(&self, name: &str, result: &jobs::Result, _: Option<Duration>) {
+                let (status, _) = jobs::result_status(result);
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("status: {}: {}", name, status));
+            }
+            fn started(&self, name: &str) {
+                self.0.lock().unwrap().push(format!("started: {}", name));
+            }
+            fn finished(&self, name: &str, _: &jobs::Result, _: Duration, output: &[u8]) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("finished: {}: {} bytes", name, output.len()));
+            }
+        }
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut events = Events::open(&Options::default()).unwrap();
+        events.add_hook(Box::new(Recorder(seen.clone())));
+        let (a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+
+        run_with_events(
+            vec![a],
+            Context::new(),
+            &Options::default(),
+            &Arc::new(events),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "status: a: pending",
+                "started: a",
+                "status: a: inprogress",
+                "finished: a: 0 bytes",
+                "status: a: done",
+            ]
+        );
+    }
+    #[test]
+    fn run_with_ordered_does_not_start_jobs_ahead_of_earlier_jobs() {
+        let (mut a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+        a.sleep = Duration::from_millis(200);
+        b.needs = vec![String::from("a")];
+
+        let jobs = vec![a, b, c];
+        let options = Options {
+            ordered: true,
+            threads: Some(2),
+            ..Default::default()
+        };
+        run(jobs, Context::new(), &options).unwrap();
+
+        let a_time = a_spy.lock().unwrap().time.expect("a");
+        let b_time = b_spy.lock().unwrap().time.expect("b");
+        let c_time = c_spy.lock().unwrap().time.expect("c");
+        // "c" could have run alongside "a", but "b" comes first
+        assert!(a_time < b_time);
+        assert!(a_time < c_time);
+    }
+    #[test]
+    fn run_does_not_execute_jobs_that_succeeded_last_run() {
+        let (a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        b.needs = vec![String::from("a")];
+
+        let options = Options {
+            succeeded: vec![String::from("a")].into_iter().collect(),
+            ..Default::default()
+        };
+        let results = run(vec![a, b], Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_never_called();
+        b_spy.lock().unwrap().assert_called_once();
+        assert!(matches!(results.get("a"), Some(Ok(Status::NoChange(_)))));
+        assert!(matches!(results.get("b"), Some(Ok(Status::Done))));
+    }
+    #[test]
+    fn run_does_not_execute_unchanged_jobs() {
+        let (a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (c, c_spy) = FakeJob::new("c", Ok(jobs::Status::Done));
+
+        let options = Options {
+            only: vec![String::from("a"), String::from("b")],
+            unchanged: vec![String::from("a"), String::from("c")]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let results = run(vec![a, b, c], Context::new(), &options).unwrap();
+
+        a_spy.lock().unwrap().assert_never_called();
+        b_spy.lock().unwrap().assert_called_once();
+        c_spy.lock().unwrap().assert_never_called();
+        assert!(
+            matches!(results.get("a"), Some(Ok(Status::NoChange(m))) if m == "unchanged since last run")
+        );
+        // not targeted, so skipped, whether or not it changed
+        assert!(matches!(results.get("c"), Some(Ok(Status::Skipped))));
+    }
+    #[test]
+    fn run_starts_the_longest_chain_first() {
+        let (a, a_spy) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (b, b_spy) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        c.needs = vec![String::from("b")];
+        let mut history = HashMap::new();
+        history.insert(String::from("a"), Duration::from_secs(2));
+        history.insert(String::from("b"), Duration::from_secs(1));
+        history.insert(String::from("c"), Duration::from_secs(3));
+
+        let options = Options {
+            threads: Some(1),
+            ..Default::default()
+        };
+        let events = Arc::new(Events::open(&options).unwrap());
+        run_with_events(vec![a, b, c], Context::new(), &options, &events, &history).unwrap();
+
+        // "b" and "c" take 4s together, which is longer than "a"
+        let a_time = a_spy.lock().unwrap().time.expect("a");
+        let b_time = b_spy.lock().unwrap().time.expect("b");
+        assert!(b_time < a_time);
+    }
+
+    #[test]
+    fn ranks_include_jobs_that_need_them() {
+        let (a, _) = FakeJob::new("a", Ok(jobs::Status::Done));
+        let (mut b, _) = FakeJob::new("b", Ok(jobs::Status::Done));
+        let (mut c, _) = FakeJob::new("c", Ok(jobs::Status::Done));
+        b.needs = vec![String::from("a")];
+        c.needs = vec![String::from("a")];
+        let mut history = HashMap::new();
+        history.insert(String::from("a"), Duration::from_secs(1));
+        history.insert(String::from("b"), Duration::from_secs(5));
+
+        let got = ranks(&[a, b, c], &history);
+        assert_eq!(got.get("a"), Some(&Duration::from_secs(6)));
+        assert_eq!(got.get("b"), Some(&Duration::from_secs(5)));
+        assert_eq!(got.get("c"), Some(&Duration::from_secs(0)));
+    }
 }
@@ -0,0 +1,309 @@
+use std::{
+    env::consts::OS,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use thiserror::Error as ThisError;
+
+use super::facts::Facts;
+
+// for launchd, and as the name of the systemd units and the Windows task
+const LABEL: &str = "com.github.jokeyrhyme.tuning";
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("`{}` exited with non-zero status code", cmd)]
+    NonZeroExitStatus { cmd: String },
+    #[error("unable to remove {}: {}", path.display(), source)]
+    RemovePath { path: PathBuf, source: io::Error },
+    #[error("`{}` could not begin: {}", cmd, source)]
+    Run { cmd: String, source: io::Error },
+    #[error("scheduling is not supported on {}", os)]
+    UnsupportedOs { os: String },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+// what to run, and how often
+pub struct Schedule {
+    pub args: Vec<String>,
+    pub every: Duration,
+    pub program: PathBuf,
+}
+
+// replaces any earlier schedule, returning what was installed, for people
+pub fn install(facts: &Facts, schedule: &Schedule) -> Result<Vec<String>> {
+    match OS {
+        "linux" => {
+            let dir = systemd_dir(facts);
+            let service = dir.join(format!("{}.service", NAME));
+            let timer = dir.join(format!("{}.timer", NAME));
+            write(&service, &systemd_service(schedule))?;
+            write(&timer, &systemd_timer(schedule))?;
+            run("systemctl", &["--user", "daemon-reload"])?;
+            run(
+                "systemctl",
+                &["--user", "enable", "--now", &format!("{}.timer", NAME)],
+            )?;
+            Ok(vec![path_string(&service), path_string(&timer)])
+        }
+        "macos" => {
+            let plist = launch_agent_path(facts);
+            let log = facts
+                .home_dir
+                .join("Library/Logs")
+                .join(format!("{}.log", NAME));
+            if plist.exists() {
+                // launchd only reads the plist at load time
+                run("launchctl", &["unload", &path_string(&plist)]).ok();
+            }
+            write(&plist, &launch_agent(schedule, &log))?;
+            run("launchctl", &["load", "-w", &path_string(&plist)])?;
+            Ok(vec![path_string(&plist)])
+        }
+        "windows" => {
+            let mut args = vec!["/Create", "/F", "/TN", NAME];
+            let trigger = schtasks_trigger(schedule.every);
+            args.extend(trigger.iter().map(String::as_str));
+            let command = windows_command(schedule);
+            args.extend(["/TR", &command].iter());
+            run("schtasks", &args)?;
+            Ok(vec![format!("scheduled task {:?}", NAME)])
+        }
+        os => Err(Error::UnsupportedOs {
+            os: String::from(os),
+        }),
+    }
+}
+
+// returns what was removed, which is nothing if there was no schedule
+pub fn remove(facts: &Facts) -> Result<Vec<String>> {
+    match OS {
+        "linux" => {
+            let dir = systemd_dir(facts);
+            let timer = dir.join(format!("{}.timer", NAME));
+            if !timer.exists() {
+                return Ok(Vec::new());
+            }
+            // still remove the files, e.g. without a systemd session
+            run(
+                "systemctl",
+                &["--user", "disable", "--now", &format!("{}.timer", NAME)],
+            )
+            .ok();
+            let service = dir.join(format!("{}.service", NAME));
+            remove_file(&timer)?;
+            remove_file(&service)?;
+            run("systemctl", &["--user", "daemon-reload"]).ok();
+            Ok(vec![path_string(&service), path_string(&timer)])
+        }
+        "macos" => {
+            let plist = launch_agent_path(facts);
+            if !plist.exists() {
+                return Ok(Vec::new());
+            }
+            run("launchctl", &["unload", "-w", &path_string(&plist)]).ok();
+            remove_file(&plist)?;
+            Ok(vec![path_string(&plist)])
+        }
+        "windows" => {
+            if run("schtasks", &["/Query", "/TN", NAME]).is_err() {
+                return Ok(Vec::new());
+            }
+            run("schtasks", &["/Delete", "/F", "/TN", NAME])?;
+            Ok(vec![format!("scheduled task {:?}", NAME)])
+        }
+        os => Err(Error::UnsupportedOs {
+            os: String::from(os),
+        }),
+    }
+}
+
+fn systemd_dir(facts: &Facts) -> PathBuf {
+    facts.config_dir.join("systemd").join("user")
+}
+
+fn systemd_service(schedule: &Schedule) -> String {
+    let argv: Vec<String> = std::iter::once(path_string(&schedule.program))
+        .chain(schedule.args.iter().cloned())
+        .map(|a| format!("\"{}\"", a.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!(
+        "[Unit]\nDescription={}, to keep this machine configured\n\n\
+         [Service]\nType=oneshot\nExecStart={}\n",
+        NAME,
+        argv.join(" ")
+    )
+}
+
+fn systemd_timer(schedule: &Schedule) -> String {
+    let every = schedule.every.as_secs().max(1);
+    format!(
+        "[Unit]\nDescription=run {} every {}\n\n\
+         [Timer]\nOnBootSec=5min\nOnUnitActiveSec={}s\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        NAME,
+        humantime::format_duration(Duration::from_secs(every)),
+        every
+    )
+}
+
+fn launch_agent_path(facts: &Facts) -> PathBuf {
+    facts
+        .home_dir
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", LABEL))
+}
+
+// with output appended to `log`, as there is no journal
+fn launch_agent(schedule: &Schedule, log: &Path) -> String {
+    let argv: String = std::iter::once(path_string(&schedule.program))
+        .chain(schedule.args.iter().cloned())
+        .map(|a| format!("    <string>{}</string>\n", xml_escape(&a)))
+        .collect();
+    let log = xml_escape(&path_string(log));
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{}</string>
+  <key>ProgramArguments</key>
+  <array>
+{}  </array>
+  <key>StartInterval</key>
+  <integer>{}</integer>
+  <key>StandardOutPath</key>
+  <string>{}</string>
+  <key>StandardErrorPath</key>
+  <string>{}</string>
+</dict>
+</plist>
+"#,
+        LABEL,
+        argv,
+        schedule.every.as_secs().max(1),
+        log,
+        log
+    )
+}
+
+// in the largest unit that fits exactly, as minutes can be at most 1439
+fn schtasks_trigger(every: Duration) -> Vec<String> {
+    let minutes = (every.as_secs() / 60).max(1);
+    let (schedule, modifier) = if minutes.is_multiple_of(24 * 60) {
+        ("DAILY", minutes / (24 * 60))
+    } else if minutes.is_multiple_of(60) {
+        ("HOURLY", minutes / 60)
+    } else {
+        ("MINUTE", minutes.min(1439))
+    };
+    vec![
+        String::from("/SC"),
+        String::from(schedule),
+        String::from("/MO"),
+        modifier.to_string(),
+    ]
+}
+
+fn windows_command(schedule: &Schedule) -> String {
+    std::iter::once(format!("\"{}\"", path_string(&schedule.program)))
+        .chain(schedule.args.iter().map(|a| {
+            if a.contains(' ') {
+                format!("\"{}\"", a)
+            } else {
+                a.clone()
+            }
+        }))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn path_string(path: &Path) -> String {
+    path.display().to_string()
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    let write_path_error = |source| Error::WritePath {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(write_path_error)?;
+    }
+    fs::write(path, contents).map_err(write_path_error)
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    fs::remove_file(path).map_err(|source| Error::RemovePath {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// output is discarded, as people only need to know whether it worked
+fn run(cmd: &str, args: &[&str]) -> Result<()> {
+    let display = format!("{} {}", cmd, args.join(" "));
+    let output = Command::new(cmd)
+        .args(args)
+        .output()
+        .map_err(|source| Error::Run {
+            cmd: display.clone(),
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(Error::NonZeroExitStatus { cmd: display });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hourly() -> Schedule {
+        Schedule {
+            args: vec![String::from("--yes"), String::from("--quiet")],
+            every: Duration::from_secs(3600),
+            program: PathBuf::from("/usr/bin/tuning"),
+        }
+    }
+
+    #[test]
+    fn systemd_units() {
+        assert!(systemd_service(&hourly())
+            .contains("\nExecStart=\"/usr/bin/tuning\" \"--yes\" \"--quiet\"\n"));
+        let timer = systemd_timer(&hourly());
+        assert!(timer.contains("\nOnUnitActiveSec=3600s\n"));
+        assert!(timer.contains("\nDescription=run tuning every 1h\n"));
+    }
+
+    #[test]
+    fn launch_agent_runs_every_interval() {
+        let got = launch_agent(&hourly(), Path::new("/Users/me/Library/Logs/tuning.log"));
+        assert!(got.contains("    <string>/usr/bin/tuning</string>\n    <string>--yes</string>\n"));
+        assert!(got.contains("<key>StartInterval</key>\n  <integer>3600</integer>"));
+    }
+
+    #[test]
+    fn schtasks_trigger_uses_largest_exact_unit() {
+        let trigger = |secs| schtasks_trigger(Duration::from_secs(secs)).join(" ");
+        assert_eq!(trigger(3600), "/SC HOURLY /MO 1");
+        assert_eq!(trigger(2 * 86400), "/SC DAILY /MO 2");
+        assert_eq!(trigger(90 * 60), "/SC MINUTE /MO 90");
+        assert_eq!(trigger(10), "/SC MINUTE /MO 1");
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::jobs;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to parse {}: {}", path.display(), source)]
+    ParseState {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
+    #[error("unable to write {}: {}", path.display(), source)]
+    WritePath { path: PathBuf, source: io::Error },
+}
+
+// job names, and the status of each one at the end of the last run
+type State = BTreeMap<String, String>;
+// job names, and a fingerprint of each job that needed no change last time
+type Fingerprints = BTreeMap<String, String>;
+// job names, and how long each one took when it last ran, in milliseconds
+type Durations = BTreeMap<String, u64>;
+
+// how many runs are kept for `tuning history`, dropping the oldest
+const HISTORY_RUNS: usize = 100;
+
+// one run, for `tuning history`
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Run {
+    #[serde(with = "humantime_serde")]
+    pub finished: SystemTime,
+    pub jobs: State,
+}
+impl Run {
+    pub fn new(finished: SystemTime, results: &HashMap<String, jobs::Result>) -> Self {
+        Run {
+            finished,
+            jobs: results
+                .iter()
+                .map(|(name, result)| (name.clone(), String::from(jobs::result_status(result).0)))
+                .collect(),
+        }
+    }
+
+    pub fn count(&self, status: &str) -> usize {
+        self.jobs.values().filter(|s| *s == status).count()
+    }
+
+    // nothing left to do, as every job already matched the config
+    pub fn is_converged(&self) -> bool {
+        self.jobs
+            .values()
+            .all(|s| !matches!(s.as_str(), "blocked" | "changed" | "failed"))
+    }
+
+    // e.g. "2026-01-02T03:04:05Z: 1 changed, 0 failed, of 3 jobs"
+    pub fn summary(&self) -> String {
+        let blocked = match self.count("blocked") {
+            0 => String::new(),
+            n => format!(", {} blocked", n),
+        };
+        format!(
+            "{}: {} changed, {} failed{}, of {} jobs{}",
+            humantime::format_rfc3339_seconds(self.finished),
+            self.count("changed"),
+            self.count("failed"),
+            blocked,
+            self.jobs.len(),
+            if self.is_converged() {
+                " (converged)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+// statuses that mean the job does not need to run again
+const SUCCEEDED: [&str; 4] = ["changed", "done", "ignored", "nochange"];
+
+pub fn path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(env!("CARGO_PKG_NAME")).join("state.json")
+}
+
+pub fn durations_path(cache_dir: &Path) -> PathBuf {
+    cache_dir
+        .join(env!("CARGO_PKG_NAME"))
+        .join("durations.json")
+}
+
+pub fn history_path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(env!("CARGO_PKG_NAME")).join("history.json")
+}
+
+pub fn fingerprints_path(cache_dir: &Path) -> PathBuf {
+    cache_dir
+        .join(env!("CARGO_PKG_NAME"))
+        .join("fingerprints.json")
+}
+
+// job names, and when each one last completed, see `interval`
+pub fn intervals_path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(env!("CARGO_PKG_NAME")).join("interval.json")
+}
+
+// instructions that the user has done, see the `manual` job
+pub fn acknowledged_path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(env!("CARGO_PKG_NAME")).join("manual.json")
+}
+
+// job names, and the status of each one at the end of the last run, e.g. "nochange"
+pub fn statuses(path: &Path) -> Result<BTreeMap<String, String>, Error> {
+    read_state(path)
+}
+
+// jobs that succeeded in the last run, none if there was no last run
+pub fn succeeded(path: &Path) -> Result<HashSet<String>, Error> {
+    let state: State = read_state(path)?;
+    Ok(state
+        .into_iter()
+        .filter(|(_, status)| SUCCEEDED.contains(&status.as_str()))
+        .map(|(name, _)| name)
+        .collect())
+}
+
+pub fn record(path: &Path, results: &HashMap<String, jobs::Result>) -> Result<(), Error> {
+    let state: State = results
+        .iter()
+        .map(|(name, result)| (name.clone(), String::from(jobs::result_status(result).0)))
+        .collect();
+    write_state(path, &state)
+}
+
+// earlier runs, oldest first, none if there were no earlier runs
+pub fn history(path: &Path) -> Result<Vec<Run>, Error> {
+    read_state(path)
+}
+
+pub fn record_history(
+    path: &Path,
+    finished: SystemTime,
+    results: &HashMap<String, jobs::Result>,
+) -> Result<(), Error> {
+    let mut runs = history(path)?;
+    runs.push(Run::new(finished, results));
+    let excess = runs.len().saturating_sub(HISTORY_RUNS);
+    runs.drain(..excess);
+    write_state(path, &runs)
+}
+
+// jobs with the same fingerprint as when they last needed no change
+pub fn unchanged(
+    path: &Path,
+    fingerprints: &HashMap<String, String>,
+) -> Result<HashSet<String>, Error> {
+    let state: Fingerprints = read_state(path)?;
+    Ok(state
+        .into_iter()
+        .filter(|(name, f)| fingerprints.get(name) == Some(f))
+        .map(|(name, _)| name)
+        .collect())
+}
+
+// jobs that were Skipped this time keep their earlier fingerprints,
+// but a run that did not converge forgets the rest, as they may depend on what changed or failed
+pub fn record_fingerprints(
+    path: &Path,
+    results: &HashMap<String, jobs::Result>,
+    fingerprints: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let mut state: Fingerprints = read_state(path)?;
+    let converged = Run::new(SystemTime::now(), results).is_converged();
+    for (name, result) in results {
+        match (result, fingerprints.get(name)) {
+            (Ok(jobs::Status::Skipped), _) => {}
+            (Ok(jobs::Status::NoChange(_)), Some(f)) if converged => {
+                state.insert(name.clone(), f.clone());
+            }
+            _ => {
+                state.remove(name);
+            }
+        }
+    }
+    write_state(path, &state)
+}
+
+pub fn durations(path: &Path) -> Result<HashMap<String, Duration>, Error> {
+    let durations: Durations = read_state(path)?;
+    Ok(durations
+        .into_iter()
+        .map(|(name, ms)| (name, Duration::from_millis(ms)))
+        .collect())
+}
+
+// jobs that did not run this time keep their earlier durations
+pub fn record_durations(path: &Path, durations: &[(String, Duration)]) -> Result<(), Error> {
+    let mut state: Durations = read_state(path)?;
+    state.extend(
+        durations
+            .iter()
+            .map(|(name, d)| (name.clone(), d.as_millis() as u64)),
+    );
+    write_state(path, &state)
+}
+
+pub fn read_state<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Error> {
+    match fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).map_err(|e| Error::ParseState {
+            path: path.to_path_buf(),
+            source: e,
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(Error::ReadPath {
+            path: path.to_path_buf(),
+            source: e,
+        }),
+    }
+}
+
+pub fn write_state<T: Serialize>(path: &Path, state: &T) -> Result<(), Error> {
+    let write_err = |e| Error::WritePath {
+        path: path.to_path_buf(),
+        source: e,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(write_err)?;
+    }
+    // serializing a map of strings to strings or numbers cannot fail
+    fs::write(path, serde_json::to_string_pretty(state).unwrap()).map_err(write_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn record_then_succeeded() -> Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = path(&temp);
+        assert!(succeeded(&path)?.is_empty());
+
+        let mut results = HashMap::<String, jobs::Result>::new();
+        results.insert(String::from("a"), Ok(jobs::Status::Done));
+        results.insert(String::from("b"), Err(jobs::Error::SomethingBad));
+        results.insert(String::from("c"), Ok(jobs::Status::Blocked));
+        results.insert(
+            String::from("d"),
+            Ok(jobs::Status::NoChange(String::from("d"))),
+        );
+        record(&path, &results)?;
+
+        let mut got: Vec<String> = succeeded(&path)?.into_iter().collect();
+        got.sort();
+        assert_eq!(got, vec![String::from("a"), String::from("d")]);
+        Ok(())
+    }
+
+    #[test]
+    fn record_history_keeps_recent_runs() -> Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = history_path(&temp);
+        assert!(history(&path)?.is_empty());
+
+        let mut results = HashMap::<String, jobs::Result>::new();
+        results.insert(
+            String::from("a"),
+            Ok(jobs::Status::Changed(String::new(), String::new())),
+        );
+        results.insert(String::from("b"), Err(jobs::Error::SomethingBad));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(86400);
+        for i in 0..(HISTORY_RUNS + 2) {
+            record_history(&path, start + Duration::from_secs(i as u64), &results)?;
+        }
+
+        let runs = history(&path)?;
+        assert_eq!(runs.len(), HISTORY_RUNS);
+        assert_eq!(
+            runs[0].summary(),
+            "1970-01-02T00:00:02Z: 1 changed, 1 failed, of 2 jobs"
+        );
+        assert!(!runs[0].is_converged());
+        Ok(())
+    }
+
+    #[test]
+    fn record_fingerprints_then_unchanged() -> Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = fingerprints_path(&temp);
+        let mut fingerprints = HashMap::<String, String>::new();
+        for name in ["a", "b", "c", "d"].iter() {
+            fingerprints.insert(String::from(*name), format!("{}1", name));
+        }
+        assert!(unchanged(&path, &fingerprints)?.is_empty());
+
+        let mut results = HashMap::<String, jobs::Result>::new();
+        results.insert(String::from("a"), Ok(jobs::Status::NoChange(String::new())));
+        results.insert(String::from("b"), Ok(jobs::Status::NoChange(String::new())));
+        results.insert(String::from("c"), Ok(jobs::Status::Done));
+        results.insert(String::from("d"), Ok(jobs::Status::NoChange(String::new())));
+        record_fingerprints(&path, &results, &fingerprints)?;
+
+        // "b" changed something this time, and "d" was skipped
+        results.insert(String::from("b"), Ok(jobs::Status::Done));
+        results.insert(String::from("d"), Ok(jobs::Status::Skipped));
+        record_fingerprints(&path, &results, &fingerprints)?;
+        assert_eq!(unchanged(&path, &fingerprints)?.len(), 2);
+
+        // "a" changed since
+        fingerprints.insert(String::from("a"), String::from("a2"));
+
+        let mut got: Vec<String> = unchanged(&path, &fingerprints)?.into_iter().collect();
+        got.sort();
+        assert_eq!(got, vec![String::from("d")]);
+
+        // "c" failed, so nothing else is trusted either
+        fingerprints.insert(String::from("a"), String::from("a1"));
+        results.insert(String::from("a"), Ok(jobs::Status::NoChange(String::new())));
+        results.insert(String::from("c"), Ok(jobs::Status::Blocked));
+        record_fingerprints(&path, &results, &fingerprints)?;
+        let got: Vec<String> = unchanged(&path, &fingerprints)?.into_iter().collect();
+        assert_eq!(got, vec![String::from("d")]);
+        Ok(())
+    }
+
+    #[test]
+    fn record_durations_keeps_earlier_durations() -> Result<(), Error> {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = durations_path(&temp);
+        record_durations(
+            &path,
+            &[
+                (String::from("a"), Duration::from_millis(100)),
+                (String::from("b"), Duration::from_millis(200)),
+            ],
+        )?;
+        record_durations(&path, &[(String::from("b"), Duration::from_millis(300))])?;
+
+        let got = durations(&path)?;
+        assert_eq!(got.get("a"), Some(&Duration::from_millis(100)));
+        assert_eq!(got.get("b"), Some(&Duration::from_millis(300)));
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashMap, convert::TryFrom};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -8,25 +13,37 @@ use which::which;
 
 use super::{
     facts::Facts,
-    jobs::{self, Main},
+    jobs::{self, Job, Main},
 };
 
 lazy_static! {
     static ref DIR_EXPRESSION_RE: Regex = Regex::new(r"_dir\s*\}\}").unwrap();
+    // left alone at first, to be rendered later for each of `with_items` or `matrix`,
+    // or for each instance of a template
+    static ref DEFERRED_EXPRESSION_RE: Regex =
+        Regex::new(r"\{\{-?\s*(?:args|item|matrix)\b.*?\}\}").unwrap();
 }
 
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error("{} includes itself", path.display())]
+    IncludeCycle { path: PathBuf },
+    #[error("unable to expand job: {}", source)]
+    Expand { source: serde_json::Error },
     #[error(transparent)]
     Job {
         #[from]
         source: jobs::Error,
     },
+    #[error("unable to read {}: {}", path.display(), source)]
+    ReadPath { path: PathBuf, source: io::Error },
     #[error("template error: {}", source)]
     Tera {
         #[from]
         source: tera::Error,
     },
+    #[error("no jobs in [templates.{}]", name)]
+    UnknownTemplate { name: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -35,12 +52,13 @@ pub fn render<S>(input: S, facts: &Facts) -> Result<String>
 where
     S: AsRef<str>,
 {
-    let context = Context::from_serialize(facts)?;
+    let context = context(facts)?;
 
     let mut t = Tera::new("template/**/*").expect("unable to prepare template system");
+    let input = DEFERRED_EXPRESSION_RE.replace_all(input.as_ref(), "{% raw %}${0}{% endraw %}");
     t.add_raw_template(
         "main.toml",
-        &DIR_EXPRESSION_RE.replace_all(input.as_ref(), "_dir | addslashes }}"),
+        &DIR_EXPRESSION_RE.replace_all(&input, "_dir | addslashes }}"),
     )?;
     t.register_function("has_executable", template_function_has_executable);
 
@@ -51,6 +69,145 @@ where
     Ok(output)
 }
 
+pub fn context(facts: &Facts) -> tera::Result<Context> {
+    Context::from_serialize(facts)
+}
+
+// evaluates a boolean expression at runtime, e.g. for `when`
+pub fn evaluate<S>(expression: S, context: &Context) -> tera::Result<bool>
+where
+    S: AsRef<str>,
+{
+    let mut t = Tera::default();
+    t.add_raw_template(
+        "expression",
+        &format!(
+            "{{% if {} %}}true{{% else %}}false{{% endif %}}",
+            expression.as_ref()
+        ),
+    )?;
+    t.register_function("has_executable", template_function_has_executable);
+    Ok(t.render("expression", context)? == "true")
+}
+
+// reads and renders a config file, splicing in any files that it includes
+pub fn render_file<P>(path: P, facts: &Facts) -> Result<Main>
+where
+    P: AsRef<Path>,
+{
+    render_file_included(path.as_ref(), facts, &mut Vec::new())
+}
+
+// like `render_file`, for a config that is not in a file (e.g. from stdin),
+// so any files that it includes are relative to `dir`
+pub fn render_config(text: String, dir: &Path, facts: &Facts) -> Result<Main> {
+    render_included(text, dir, facts, &mut Vec::new())
+}
+
+fn render_file_included(path: &Path, facts: &Facts, stack: &mut Vec<PathBuf>) -> Result<Main> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(Error::IncludeCycle {
+            path: path.to_path_buf(),
+        });
+    }
+    let text = fs::read_to_string(path).map_err(|e| Error::ReadPath {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    stack.push(canonical);
+    let main = render_included(text, dir, facts, stack);
+    stack.pop();
+    main
+}
+
+fn render_included(
+    text: String,
+    dir: &Path,
+    facts: &Facts,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Main> {
+    let context = context(facts)?;
+    let rendered = render(text, facts)?;
+    let raw = toml::from_str::<toml::Value>(&rendered)
+        .map_err(|e| jobs::Error::ParseToml { source: e })?;
+    let tables = raw
+        .get("jobs")
+        .and_then(|j| j.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let templates = raw.get("templates");
+    let main = Main::try_from(rendered.as_str())?
+        .expand_items(|index, variable, item| {
+            render_item(&tables[index], variable, item, &context)
+        })?
+        .splice_instances(|instance| {
+            let mut context = context.clone();
+            context.insert("args", &instance.args);
+            render_template(&instance.template, templates, &context)
+        })?;
+
+    main.splice_includes(|include| render_file_included(&include.resolve(dir), facts, stack))
+}
+
+// renders `{{ item }}` or `{{ matrix.* }}` expressions that `render` left alone in a job's table
+fn render_item(
+    table: &toml::Value,
+    variable: &str,
+    item: &Value,
+    context: &Context,
+) -> Result<Job> {
+    let mut context = context.clone();
+    context.insert(variable, item);
+    let mut value = serde_json::to_value(table).map_err(|e| Error::Expand { source: e })?;
+    if let Some(o) = value.as_object_mut() {
+        o.remove("matrix");
+        o.remove("with_items");
+    }
+    render_table(value, &context)
+}
+
+// renders `{{ args.* }}` expressions that `render` left alone in `[templates.<name>]`
+fn render_template(name: &str, templates: Option<&toml::Value>, context: &Context) -> Result<Main> {
+    let tables = templates
+        .and_then(|t| t.get(name))
+        .and_then(|t| t.get("jobs"))
+        .and_then(|j| j.as_array())
+        .ok_or_else(|| Error::UnknownTemplate {
+            name: String::from(name),
+        })?;
+    let jobs = tables
+        .iter()
+        .map(|t| {
+            let value = serde_json::to_value(t).map_err(|e| Error::Expand { source: e })?;
+            render_table(value, context)
+        })
+        .collect::<Result<Vec<Job>>>()?;
+    Ok(Main {
+        handlers: Vec::new(),
+        jobs,
+        settings: Default::default(),
+    })
+}
+
+fn render_table(mut value: Value, context: &Context) -> Result<Job> {
+    render_strings(&mut value, context)?;
+    serde_json::from_value(value).map_err(|e| Error::Expand { source: e })
+}
+
+fn render_strings(value: &mut Value, context: &Context) -> tera::Result<()> {
+    match value {
+        Value::Array(a) => a.iter_mut().try_for_each(|v| render_strings(v, context)),
+        Value::Object(o) => o.values_mut().try_for_each(|v| render_strings(v, context)),
+        Value::String(s) if s.contains("{{") => {
+            *s = Tera::one_off(s, context, false)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
 fn template_function_has_executable(args: &HashMap<String, Value>) -> tera::Result<Value> {
     match args.get("exe") {
         Some(val) => match from_value::<String>(val.clone()) {
@@ -65,10 +222,247 @@ fn template_function_has_executable(args: &HashMap<String, Value>) -> tera::Resu
 mod tests {
     use std::path::PathBuf;
 
-    use super::super::facts::Facts;
+    use super::super::{facts::Facts, jobs::Execute};
 
     use super::*;
 
+    #[test]
+    fn render_file_with_includes() -> Result<()> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::create_dir_all(temp.join("tools")).expect("mkdir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[jobs]]
+            type = "include"
+            path = "tools/git.toml"
+            "#,
+        )
+        .expect("write");
+        fs::write(
+            temp.join("tools").join("git.toml"),
+            r#"
+            [[jobs]]
+            name = "{{ home_dir }}"
+            type = "command"
+            command = "git"
+            "#,
+        )
+        .expect("write");
+        let facts = Facts {
+            home_dir: PathBuf::from("my_home_dir"),
+            ..Default::default()
+        };
+
+        let got = render_file(temp.join("main.toml"), &facts)?;
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got.jobs[0].name(), "my_home_dir");
+        Ok(())
+    }
+
+    #[test]
+    fn render_config_includes_relative_to_dir() -> Result<()> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("git.toml"),
+            r#"
+            [[jobs]]
+            name = "git"
+            type = "command"
+            command = "git"
+            "#,
+        )
+        .expect("write");
+        let text = String::from(
+            r#"
+            [[jobs]]
+            type = "include"
+            path = "git.toml"
+            "#,
+        );
+
+        let got = render_config(text, &temp, &Facts::default())?;
+        assert_eq!(got.jobs.len(), 1);
+        assert_eq!(got.jobs[0].name(), "git");
+        Ok(())
+    }
+
+    #[test]
+    fn render_file_with_items() -> Result<()> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[jobs]]
+            name = "link {{ item.src }}"
+            type = "file"
+            src = "{{ home_dir }}/dotfiles/{{ item.src }}"
+            path = "{{ home_dir }}/{{ item.path }}"
+            state = "link"
+            with_items = [
+                { src = "bashrc", path = ".bashrc" },
+                { src = "vimrc", path = ".vimrc" },
+            ]
+
+            [[jobs]]
+            name = "install"
+            type = "command"
+            command = "apt-get"
+            argv = ["install", "{{ item }}"]
+            with_items = ["git", "tmux"]
+
+            [[jobs]]
+            name = "afterwards"
+            type = "command"
+            command = "true"
+            needs = ["install"]
+            "#,
+        )
+        .expect("write");
+        let facts = Facts {
+            home_dir: PathBuf::from("my_home_dir"),
+            ..Default::default()
+        };
+
+        let got = render_file(temp.join("main.toml"), &facts)?;
+        let names: Vec<String> = got.jobs.iter().map(|j| j.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "link bashrc",
+                "link vimrc",
+                "install: git",
+                "install: tmux",
+                "afterwards"
+            ]
+        );
+        assert_eq!(got.jobs[4].needs(), vec!["install: git", "install: tmux"]);
+        let got = format!("{:?}", got.jobs[1]);
+        assert!(got.contains("my_home_dir/dotfiles/vimrc"));
+        assert!(got.contains("my_home_dir/.vimrc"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_file_with_matrix() -> Result<()> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[jobs]]
+            name = "pyenv install"
+            type = "command"
+            command = "pyenv"
+            argv = ["install", "{{ matrix.python }}"]
+            matrix = { python = ["3.11", "3.12"], arch = ["arm64", "x86_64"] }
+
+            [[jobs]]
+            name = "afterwards"
+            type = "command"
+            command = "true"
+            needs = ["pyenv install: arch=arm64, python=3.12"]
+            "#,
+        )
+        .expect("write");
+
+        let got = render_file(temp.join("main.toml"), &Facts::default())?;
+        let names: Vec<String> = got.jobs.iter().map(|j| j.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "pyenv install: arch=arm64, python=3.11",
+                "pyenv install: arch=arm64, python=3.12",
+                "pyenv install: arch=x86_64, python=3.11",
+                "pyenv install: arch=x86_64, python=3.12",
+                "afterwards",
+            ]
+        );
+        assert!(format!("{:?}", got.jobs[1]).contains("\"3.12\""));
+        Ok(())
+    }
+
+    #[test]
+    fn render_file_with_template_instances() -> Result<()> {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[templates.dotfile.jobs]]
+            name = "clone {{ args.name }}"
+            type = "command"
+            command = "git"
+            argv = ["clone", "{{ args.repo }}", "{{ home_dir }}/src/{{ args.name }}"]
+
+            [[templates.dotfile.jobs]]
+            name = "link {{ args.name }}"
+            type = "file"
+            src = "{{ home_dir }}/src/{{ args.name }}/config"
+            path = "{{ home_dir }}/.config/{{ args.name }}"
+            state = "link"
+            needs = ["clone {{ args.name }}"]
+
+            [[jobs]]
+            name = "nvim"
+            type = "instance"
+            template = "dotfile"
+            args = { name = "nvim", repo = "https://example.com/nvim.git" }
+
+            [[jobs]]
+            name = "afterwards"
+            type = "command"
+            command = "true"
+            needs = ["nvim"]
+            "#,
+        )
+        .expect("write");
+        let facts = Facts {
+            home_dir: PathBuf::from("my_home_dir"),
+            ..Default::default()
+        };
+
+        let got = render_file(temp.join("main.toml"), &facts)?;
+        let names: Vec<String> = got.jobs.iter().map(|j| j.name()).collect();
+        assert_eq!(names, vec!["clone nvim", "link nvim", "afterwards"]);
+        assert_eq!(got.jobs[1].needs(), vec!["clone nvim"]);
+        assert_eq!(got.jobs[2].needs(), vec!["clone nvim", "link nvim"]);
+        let got = format!("{:?}", got.jobs[0]);
+        assert!(got.contains("https://example.com/nvim.git"));
+        assert!(got.contains("my_home_dir/src/nvim"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_file_errs_on_unknown_template() {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[jobs]]
+            type = "instance"
+            template = "nope"
+            "#,
+        )
+        .expect("write");
+        let got = render_file(temp.join("main.toml"), &Facts::default());
+        assert!(matches!(got, Err(Error::UnknownTemplate { .. })));
+    }
+
+    #[test]
+    fn render_file_errs_on_include_cycle() {
+        let temp = mktemp::Temp::new_dir().expect("temp dir");
+        fs::write(
+            temp.join("main.toml"),
+            r#"
+            [[jobs]]
+            type = "include"
+            path = "main.toml"
+            "#,
+        )
+        .expect("write");
+        let got = render_file(temp.join("main.toml"), &Facts::default());
+        assert!(matches!(got, Err(Error::IncludeCycle { .. })));
+    }
+
     #[test]
     fn render_errs_if_not_toml() {
         let input = r#"{"hello": "world"}"#;
@@ -0,0 +1,72 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use thiserror::Error as ThisError;
+
+// editors often write a file several times in quick succession
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("unable to watch {}: {}", path.display(), source)]
+    Watch {
+        path: PathBuf,
+        source: notify::Error,
+    },
+}
+
+// blocks until a file under `dir` is created, modified, or removed
+pub fn wait_for_change(dir: &Path) -> Result<(), Error> {
+    let watch_err = |e| Error::Watch {
+        path: dir.to_path_buf(),
+        source: e,
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|e| is_change(&e.kind)) {
+            tx.send(()).ok();
+        }
+    })
+    .map_err(watch_err)?;
+    watcher
+        .watch(dir, RecursiveMode::Recursive)
+        .map_err(watch_err)?;
+
+    rx.recv().ok();
+    // wait for the rest of the changes, so that we only run once
+    while rx.recv_timeout(DEBOUNCE).is_ok() {}
+    Ok(())
+}
+
+fn is_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+
+    use mktemp::Temp;
+
+    use super::*;
+
+    #[test]
+    fn returns_after_a_file_changes() {
+        let temp = Temp::new_dir().expect("temp dir");
+        let path = temp.join("main.toml");
+        let writer = thread::spawn(move || {
+            // give the watcher a moment to start
+            thread::sleep(Duration::from_millis(500));
+            fs::write(path, "jobs = []").expect("write");
+        });
+        wait_for_change(&temp).expect("watch");
+        writer.join().expect("writer");
+    }
+}
@@ -1,29 +1,69 @@
 #![deny(clippy::all)]
+#![allow(special_module_name)]
 
+mod cli;
 mod lib;
 
-use std::{convert::TryFrom, fs, io};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime},
+};
 
+use clap::Parser;
 use thiserror::Error as ThisError;
 
+use cli::{Cli, Command, Format, GraphFormat, NewJob, Output};
 use lib::{
+    add, diff,
+    events::{self, Events},
+    explain,
     facts::{self, Facts},
-    jobs::{self, Main},
-    runner, template,
+    fmt, init,
+    jobs::{self, is_result_done, Execute, Main},
+    list, logger,
+    runner::{self, RunReport, RunStatus},
+    schedule::{self, Schedule},
+    state, template, watch,
 };
 
 const MAIN_TOML_FILE: &str = "main.toml";
+// as `--config`, reads the config from stdin, e.g. `curl ... | tuning --config -`
+const STDIN: &str = "-";
 
 #[derive(Debug, ThisError)]
 enum Error {
     #[error("valid config file not found")]
     ConfigNotFound,
     #[error(transparent)]
+    Events {
+        #[from]
+        source: events::Error,
+    },
+    #[error(transparent)]
     Facts {
         #[from]
         source: facts::Error,
     },
     #[error(transparent)]
+    Fmt {
+        #[from]
+        source: fmt::Error,
+    },
+    #[error("there is already a job named {:?}", name)]
+    JobExists { name: String },
+    #[error("formatting {} would change its jobs, so it was left as it is", path.display())]
+    FmtChanged { path: PathBuf },
+    #[error(transparent)]
+    Init {
+        #[from]
+        source: init::Error,
+    },
+    #[error(transparent)]
     Io {
         #[from]
         source: io::Error,
@@ -34,58 +74,709 @@ enum Error {
         source: jobs::Error,
     },
     #[error(transparent)]
+    Runner {
+        #[from]
+        source: runner::Error,
+    },
+    #[error(transparent)]
+    Schedule {
+        #[from]
+        source: schedule::Error,
+    },
+    #[error("{} needs a config file, rather than stdin", command)]
+    StdinConfig { command: &'static str },
+    #[error(transparent)]
+    State {
+        #[from]
+        source: state::Error,
+    },
+    #[error(transparent)]
     Template {
         #[from]
         source: template::Error,
     },
+    #[error(transparent)]
+    Toml {
+        #[from]
+        source: toml::ser::Error,
+    },
+    #[error("no job named {:?}", name)]
+    UnknownJob { name: String },
+    #[error(transparent)]
+    Watch {
+        #[from]
+        source: watch::Error,
+    },
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
+// exits 1 if any job failed, 2 if any job changed (or would change), otherwise 0
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    colored::control::set_override(cli.is_colored());
+    logger::init(cli.log_level());
+    facts::set_overrides(cli.fact.iter().cloned().collect());
     let facts = Facts::gather()?;
-    let m = read_config(&facts)?;
-    runner::run(m.jobs);
-
-    Ok(())
-}
-
-fn read_config(facts: &Facts) -> Result<Main> {
-    let config_paths = [
-        facts
-            .config_dir
-            .join(env!("CARGO_PKG_NAME"))
-            .join(MAIN_TOML_FILE),
-        facts
-            .home_dir
-            .join(".dotfiles")
-            .join(env!("CARGO_PKG_NAME"))
-            .join(MAIN_TOML_FILE),
-    ];
-    for config_path in config_paths.iter() {
-        println!("reading: {}", &config_path.display());
-        let text = match fs::read_to_string(&config_path) {
-            Ok(s) => s,
-            Err(e) => {
-                println!("{:?}", e);
-                continue;
-            }
+    match &cli.command {
+        Some(Command::Add {
+            name,
+            needs,
+            tags,
+            job,
+        }) => return write_add(&cli, &facts, job, name.as_deref(), needs, tags),
+        Some(Command::Doctor) => return doctor(&cli, &facts),
+        Some(Command::Explain { job }) => return print_explain(&cli, &facts, job),
+        Some(Command::Facts { format }) => return print_facts(&facts, *format),
+        Some(Command::Graph { format }) => return print_graph(&cli, &facts, *format),
+        Some(Command::Fmt { check }) => return write_fmt(&cli, &facts, *check),
+        Some(Command::History { job }) => return print_history(&facts, job.as_deref()),
+        Some(Command::Init { force }) => return write_init(&cli, &facts, *force),
+        Some(Command::List { tree }) => return print_list(&cli, &facts, *tree),
+        Some(Command::Render { job }) => return print_render(&cli, &facts, job.as_deref()),
+        Some(Command::Schedule { every, remove }) => {
+            return write_schedule(&cli, &facts, *every, *remove)
+        }
+        Some(Command::Validate) => return validate(&cli, &facts),
+        None => {}
+    }
+    if cli.watch {
+        return watch(&cli, &facts);
+    }
+    let (_, mut m) = read_config(&facts, cli.config.as_deref(), cli.is_human())?;
+    if cli.is_ask(&m.settings) {
+        let plan = Cli {
+            check: true,
+            ..cli.clone()
         };
-        let rendered = match template::render(text, &facts) {
-            Ok(s) => s,
-            Err(e) => {
-                println!("{:?}", e);
-                continue;
+        let report = apply(&plan, &facts, m, HashSet::new())?;
+        let is_settled = report.status() == RunStatus::Unchanged && !report.has_unchecked();
+        if is_settled || !events::confirm_apply(cli.is_human()) {
+            return Ok(ExitCode::from(report.status().exit_code()));
+        }
+        // the check consumed the jobs
+        m = read_config(&facts, cli.config.as_deref(), false)?.1;
+    }
+    let report = apply(&cli, &facts, m, HashSet::new())?;
+    if cli.output == Output::Json {
+        // serializing strings and numbers cannot fail
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&events::Report::new(&report)).unwrap()
+        );
+    }
+
+    Ok(ExitCode::from(report.status().exit_code()))
+}
+
+// runs the jobs in `m`, except those that already `succeeded`
+fn apply(cli: &Cli, facts: &Facts, m: Main, succeeded: HashSet<String>) -> Result<RunReport> {
+    let context = template::context(facts).map_err(template::Error::from)?;
+    let mut options = cli.runner_options(&m.settings, facts);
+    let state_path = state::path(&facts.cache_dir);
+    options.succeeded = succeeded;
+    if cli.retry_failed {
+        options.succeeded.extend(state::succeeded(&state_path)?);
+    }
+    // a typo would otherwise quietly skip everything
+    if let Some(name) = cli
+        .only
+        .iter()
+        .chain(cli.skip.iter())
+        .chain(cli.start_at_job.iter())
+        .find(|n| !m.jobs.iter().any(|j| &j.name() == *n))
+    {
+        return Err(Error::UnknownJob { name: name.clone() });
+    }
+    let kinds: HashMap<String, &str> = m.jobs.iter().map(|j| (j.name(), j.kind())).collect();
+    let fingerprints: HashMap<String, String> = m
+        .jobs
+        .iter()
+        .filter_map(|j| j.fingerprint().map(|f| (j.name(), f)))
+        .collect();
+    let fingerprints_path = state::fingerprints_path(&facts.cache_dir);
+    if cli.skip_unchanged {
+        options.unchanged = state::unchanged(&fingerprints_path, &fingerprints)?;
+    }
+    let events = Arc::new(Events::open(&options)?);
+    let report = runner::run_with_handlers(m.jobs, m.handlers, context, &options, &events)?;
+    events.summary(&report);
+    if cli.drift && cli.is_human() {
+        println!("{}", events::drift(&report.results, &kinds));
+    }
+    // a check does not change anything, so the last real run still stands
+    if !options.check {
+        state::record(&state_path, &report.results)?;
+        state::record_fingerprints(&fingerprints_path, &report.results, &fingerprints)?;
+        state::record_history(
+            &state::history_path(&facts.cache_dir),
+            SystemTime::now(),
+            &report.results,
+        )?;
+    }
+    Ok(report)
+}
+
+// runs the jobs, then again whenever a file in the config directory changes,
+// but only the jobs that changed, or that did not succeed last time
+fn watch(cli: &Cli, facts: &Facts) -> Result<ExitCode> {
+    if is_stdin(cli.config.as_deref()) {
+        return Err(Error::StdinConfig { command: "--watch" });
+    }
+    // each job as it was last rendered, and whether it succeeded
+    let mut previous = HashMap::<String, (String, bool)>::new();
+    let mut dir = None;
+    loop {
+        match read_config(facts, cli.config.as_deref(), cli.is_human()) {
+            Ok((path, m)) => {
+                dir = path.parent().map(Path::to_path_buf);
+                let specs: HashMap<String, String> = m
+                    .jobs
+                    .iter()
+                    .map(|j| (j.name(), format!("{:?}", j)))
+                    .collect();
+                let unchanged = specs
+                    .iter()
+                    .filter(|(name, spec)| {
+                        previous
+                            .get(*name)
+                            .is_some_and(|(p, succeeded)| *succeeded && p == *spec)
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                match apply(cli, facts, m, unchanged) {
+                    Ok(report) => {
+                        previous = specs
+                            .into_iter()
+                            .map(|(name, spec)| {
+                                let succeeded =
+                                    report.results.get(&name).is_some_and(is_result_done);
+                                (name, (spec, succeeded))
+                            })
+                            .collect();
+                    }
+                    // keep watching, as the next change may fix it
+                    Err(e) => println!("{}", e),
+                }
             }
-        };
-        match Main::try_from(rendered.as_str()) {
+            Err(e) if dir.is_some() => println!("{}", e),
+            Err(e) => return Err(e),
+        }
+        // `dir` is always set by now
+        let dir = dir.as_deref().unwrap();
+        println!("watching: {}", dir.display());
+        watch::wait_for_change(dir)?;
+    }
+}
+
+fn print_facts(facts: &Facts, format: Format) -> Result<ExitCode> {
+    match format {
+        // serializing paths and booleans cannot fail
+        Format::Json => println!("{}", serde_json::to_string_pretty(facts).unwrap()),
+        Format::Toml => print!("{}", toml::to_string_pretty(facts)?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_graph(cli: &Cli, facts: &Facts, format: GraphFormat) -> Result<ExitCode> {
+    let (_, m) = read_config(facts, cli.config.as_deref(), false)?;
+    match format {
+        GraphFormat::Dot => println!("{}", list::dot(&m.jobs)),
+        GraphFormat::Mermaid => println!("{}", list::mermaid(&m.jobs)),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// where the config would be read from first
+fn write_init(cli: &Cli, facts: &Facts, force: bool) -> Result<ExitCode> {
+    if is_stdin(cli.config.as_deref()) {
+        return Err(Error::StdinConfig {
+            command: "tuning init",
+        });
+    }
+    let path = config_paths(facts, cli.config.as_deref())
+        .into_iter()
+        .next()
+        .ok_or(Error::ConfigNotFound)?;
+    init::write(&path, force)?;
+    println!("created: {}", path.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_list(cli: &Cli, facts: &Facts, tree: bool) -> Result<ExitCode> {
+    let (_, m) = read_config(facts, cli.config.as_deref(), false)?;
+    if tree {
+        println!("{}", list::tree(&m.jobs));
+    } else {
+        println!("{}", list::list(&m.jobs));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// to the config that would be read, or a new one where `tuning init` would write it
+fn write_add(
+    cli: &Cli,
+    facts: &Facts,
+    job: &NewJob,
+    name: Option<&str>,
+    needs: &[String],
+    tags: &[String],
+) -> Result<ExitCode> {
+    if is_stdin(cli.config.as_deref()) {
+        return Err(Error::StdinConfig {
+            command: "tuning add",
+        });
+    }
+    let path = match find_config(facts, cli.config.as_deref()) {
+        Some(p) => p,
+        None => config_paths(facts, cli.config.as_deref())
+            .into_iter()
+            .next()
+            .ok_or(Error::ConfigNotFound)?,
+    };
+    let (default_name, mut table) = new_job(job, facts)?;
+    let name = name.map_or(default_name, String::from);
+    let text = if path.exists() {
+        let m = render_config(&path, facts)?;
+        if m.jobs
+            .iter()
+            .chain(m.handlers.iter())
+            .any(|j| j.name() == name)
+        {
+            return Err(Error::JobExists { name });
+        }
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+
+    table.insert("name", toml_edit::value(name.as_str()));
+    if !needs.is_empty() {
+        table.insert(
+            "needs",
+            toml_edit::value(needs.iter().collect::<toml_edit::Array>()),
+        );
+    }
+    if !tags.is_empty() {
+        table.insert(
+            "tags",
+            toml_edit::value(tags.iter().collect::<toml_edit::Array>()),
+        );
+    }
+    let added = add::append(&text, table)?;
+    // e.g. a typo in `needs`, before it is written
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let m = template::render_config(added.clone(), dir, facts)?;
+    if let Some(e) = runner::problems(&m.jobs).into_iter().next() {
+        return Err(Error::Runner { source: e });
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, added)?;
+    println!("added: {:?} to {}", name, path.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+// a `[[jobs]]` table without metadata, and a name for it,
+// with paths that are relative to the current directory made absolute,
+// and paths in the home directory starting with `{{ home_dir }}`
+fn new_job(job: &NewJob, facts: &Facts) -> Result<(String, toml_edit::Table)> {
+    let cwd = env::current_dir()?;
+    let portable = |p: &Path| add::portable_path(&cwd.join(p), &facts.home_dir);
+    let mut table = toml_edit::Table::new();
+    let name = match job {
+        NewJob::Cargo { krate } => {
+            table.insert("type", toml_edit::value("cargo"));
+            table.insert("crate", toml_edit::value(krate.as_str()));
+            format!("cargo install {}", krate)
+        }
+        NewJob::Command {
+            argv,
+            command,
+            creates,
+        } => {
+            table.insert("type", toml_edit::value("command"));
+            table.insert("command", toml_edit::value(command.as_str()));
+            if !argv.is_empty() {
+                table.insert(
+                    "argv",
+                    toml_edit::value(argv.iter().collect::<toml_edit::Array>()),
+                );
+            }
+            if let Some(c) = creates {
+                table.insert("creates", toml_edit::value(portable(c)));
+            }
+            std::iter::once(command)
+                .chain(argv.iter())
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(" ")
+        }
+        NewJob::Link { force, path, src } => {
+            table.insert("type", toml_edit::value("file"));
+            table.insert("state", toml_edit::value("link"));
+            table.insert("src", toml_edit::value(portable(src)));
+            table.insert("path", toml_edit::value(portable(path)));
+            if *force {
+                table.insert("force", toml_edit::value(true));
+            }
+            let file_name = path.file_name().unwrap_or(path.as_os_str());
+            format!("link {}", file_name.to_string_lossy())
+        }
+        NewJob::Script { creates, script } => {
+            table.insert("type", toml_edit::value("script"));
+            table.insert("script", toml_edit::value(script.as_str()));
+            if let Some(c) = creates {
+                table.insert("creates", toml_edit::value(portable(c)));
+            }
+            String::from(script.lines().next().unwrap_or_default().trim())
+        }
+    };
+    Ok((name, table))
+}
+
+// in place, or to stdout for a config from stdin
+fn write_fmt(cli: &Cli, facts: &Facts, check: bool) -> Result<ExitCode> {
+    let path = find_config(facts, cli.config.as_deref()).ok_or(Error::ConfigNotFound)?;
+    let text = read_text(&path)?;
+    let formatted = fmt::format(&text)?;
+    // e.g. if a template renders differently with other quotes around it
+    if let Ok(before) = render_config(&path, facts) {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let after = template::render_config(formatted.clone(), dir, facts);
+        if after.ok().as_ref() != Some(&before) {
+            return Err(Error::FmtChanged { path });
+        }
+    }
+    if check {
+        if formatted == text {
+            return Ok(ExitCode::SUCCESS);
+        }
+        print!("{}", diff::unified(&path, &text, &formatted));
+        return Ok(ExitCode::FAILURE);
+    }
+    if is_stdin(Some(&path)) {
+        print!("{}", formatted);
+    } else if formatted == text {
+        println!("unchanged: {}", path.display());
+    } else {
+        fs::write(&path, formatted)?;
+        println!("formatted: {}", path.display());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// newest first, as usually only the last few runs matter
+fn print_history(facts: &Facts, job: Option<&str>) -> Result<ExitCode> {
+    let runs = state::history(&state::history_path(&facts.cache_dir))?;
+    if runs.is_empty() {
+        println!("no runs yet");
+        return Ok(ExitCode::SUCCESS);
+    }
+    match job {
+        Some(name) => {
+            for run in runs.iter().rev() {
+                let status = run.jobs.get(name).map_or("not run", String::as_str);
+                println!(
+                    "{}: {}",
+                    humantime::format_rfc3339_seconds(run.finished),
+                    status
+                );
+            }
+        }
+        None => {
+            match runs.iter().rev().find(|r| r.is_converged()) {
+                Some(run) => println!(
+                    "last converged: {}",
+                    humantime::format_rfc3339_seconds(run.finished)
+                ),
+                None => println!("last converged: never"),
+            }
+            for run in runs.iter().rev() {
+                println!("{}", run.summary());
+                // which jobs, as it is usually only a few
+                for (name, status) in run
+                    .jobs
+                    .iter()
+                    .filter(|(_, s)| matches!(s.as_str(), "blocked" | "changed" | "failed"))
+                {
+                    println!("    {}: {}", name, status);
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// with the last run from the state file, so it can be compared to what would happen now
+fn print_explain(cli: &Cli, facts: &Facts, name: &str) -> Result<ExitCode> {
+    let (_, m) = read_config(facts, cli.config.as_deref(), false)?;
+    let context = template::context(facts).map_err(template::Error::from)?;
+    let job = m
+        .jobs
+        .iter()
+        .chain(m.handlers.iter())
+        .find(|j| j.name() == name)
+        .ok_or_else(|| Error::UnknownJob {
+            name: String::from(name),
+        })?;
+    let is_handler = !m.jobs.iter().any(|j| j.name() == name);
+    let jobs = if is_handler { &m.handlers } else { &m.jobs };
+
+    println!("job: {} ({})", name, job.kind());
+    if let Some(d) = job.description() {
+        println!("    {}", d);
+    }
+    let chain = explain::chain(jobs, name);
+    if chain.is_empty() {
+        println!("needs: nothing");
+    } else {
+        println!("needs: {}", chain.join(" -> "));
+    }
+    match job.when(&context) {
+        Ok(when) => println!("when: {}", when),
+        Err(e) => println!("when: {}", e),
+    }
+    let statuses = state::statuses(&state::path(&facts.cache_dir))?;
+    match statuses.get(name) {
+        Some(status) => println!("last run: {}", status),
+        None => println!("last run: never"),
+    }
+    match job.fingerprint() {
+        Some(f) => {
+            let fingerprints = HashMap::from([(String::from(name), f)]);
+            let fingerprints_path = state::fingerprints_path(&facts.cache_dir);
+            if state::unchanged(&fingerprints_path, &fingerprints)?.contains(name) {
+                println!(
+                    "unchanged since it last needed no change, so `--skip-unchanged` skips it"
+                );
+            }
+        }
+        None => println!("manages more than files, so `--skip-unchanged` never skips it"),
+    }
+    match explain::verdict(jobs, name, &context) {
+        Ok(()) if is_handler => println!("would run: if a job notifies it"),
+        Ok(()) => println!("would run"),
+        Err(reason) => println!("would not run: {}", reason),
+    }
+    println!("\n{}", job.to_toml()?.trim_end());
+    Ok(ExitCode::SUCCESS)
+}
+
+// prints the whole rendered config file, before `include` jobs and so on are expanded,
+// or one job, after they are expanded
+fn print_render(cli: &Cli, facts: &Facts, job: Option<&str>) -> Result<ExitCode> {
+    match job {
+        Some(name) => {
+            let (_, m) = read_config(facts, cli.config.as_deref(), false)?;
+            let job =
+                m.jobs
+                    .iter()
+                    .find(|j| j.name() == name)
+                    .ok_or_else(|| Error::UnknownJob {
+                        name: String::from(name),
+                    })?;
+            print!("{}", job.to_toml()?);
+        }
+        None => {
+            let path = find_config(facts, cli.config.as_deref()).ok_or(Error::ConfigNotFound)?;
+            print!("{}", template::render(read_text(&path)?, facts)?);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// with the same config as now, if it was not found in the usual places
+fn write_schedule(cli: &Cli, facts: &Facts, every: Duration, remove: bool) -> Result<ExitCode> {
+    if remove {
+        let removed = schedule::remove(facts)?;
+        if removed.is_empty() {
+            println!("not scheduled");
+        }
+        for r in removed {
+            println!("removed: {}", r);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if is_stdin(cli.config.as_deref()) {
+        return Err(Error::StdinConfig {
+            command: "tuning schedule",
+        });
+    }
+    let mut args = vec![String::from("--yes"), String::from("--quiet")];
+    if let Some(c) = &cli.config {
+        args.push(String::from("--config"));
+        args.push(fs::canonicalize(c)?.display().to_string());
+    }
+    let s = Schedule {
+        args,
+        every,
+        program: env::current_exe()?,
+    };
+    for i in schedule::install(facts, &s)? {
+        println!("installed: {}", i);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// e.g. for a pre-commit hook
+fn validate(cli: &Cli, facts: &Facts) -> Result<ExitCode> {
+    let path = find_config(facts, cli.config.as_deref()).ok_or(Error::ConfigNotFound)?;
+    let mut problems = Vec::<String>::new();
+    match render_config(&path, facts) {
+        Ok(m) => {
+            let rendered = template::render(read_text(&path)?, facts)?;
+            problems.extend(jobs::unknown_fields(&rendered)?);
+            problems.extend(runner::problems(&m.jobs).iter().map(|e| e.to_string()));
+            problems.extend(runner::problems(&m.handlers).iter().map(|e| e.to_string()));
+            let handlers: HashSet<String> = m.handlers.iter().map(|h| h.name()).collect();
+            for job in &m.jobs {
+                for handler in [job.always(), job.notify(), job.rescue()].concat() {
+                    if !handlers.contains(&handler) {
+                        problems.push(format!(
+                            "job {:?} triggers {:?}, which is not a handler",
+                            job.name(),
+                            handler
+                        ));
+                    }
+                }
+            }
+        }
+        // nothing else can be checked
+        Err(e) => problems.push(e.to_string()),
+    }
+    if problems.is_empty() {
+        println!("valid: {}", path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("invalid: {}", path.display());
+    for problem in problems {
+        println!("    {}", problem);
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+// like `validate`, but for this machine, e.g. missing executables
+fn doctor(cli: &Cli, facts: &Facts) -> Result<ExitCode> {
+    let paths = config_paths(facts, cli.config.as_deref());
+    let mut warnings = Vec::<String>::new();
+    match find_config(facts, cli.config.as_deref()) {
+        Some(path) => {
+            println!("config: {}", path.display());
+            match render_config(&path, facts) {
+                Ok(m) => {
+                    for job in m.jobs.iter().chain(m.handlers.iter()) {
+                        for warning in job.warnings() {
+                            warnings.push(format!("job {:?}: {}", job.name(), warning));
+                        }
+                    }
+                }
+                Err(e) => warnings.push(format!("{}, see `tuning validate`", e)),
+            }
+        }
+        None => warnings.push(format!(
+            "no config in {}, see `tuning init`",
+            paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" or ")
+        )),
+    }
+    if warnings.is_empty() {
+        println!("no problems found");
+        return Ok(ExitCode::SUCCESS);
+    }
+    for warning in warnings {
+        println!("warning: {}", warning);
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+// returns the path of the config file that was read, too
+fn read_config(facts: &Facts, config: Option<&Path>, human: bool) -> Result<(PathBuf, Main)> {
+    for config_path in config_paths(facts, config).iter() {
+        if human {
+            log::info!("reading: {}", &config_path.display());
+        }
+        match render_config(config_path, facts) {
             Ok(m) => {
-                return Ok(m);
+                if log::log_enabled!(log::Level::Debug) {
+                    for job in &m.jobs {
+                        if let Ok(spec) = job.to_toml() {
+                            log::debug!("job {:?}:\n{}", job.name(), spec.trim_end());
+                        }
+                    }
+                }
+                return Ok((config_path.clone(), m));
+            }
+            // it is normal for some of these not to exist
+            Err(e) if !config_path.exists() && !is_stdin(Some(config_path)) => {
+                log::debug!("{}: {}", config_path.display(), e);
             }
             Err(e) => {
-                println!("{:?}", e);
+                log::warn!("{}: {}", config_path.display(), e);
             }
         }
     }
     Err(Error::ConfigNotFound)
 }
+
+// like `template::render_file`, but for stdin too
+fn render_config(path: &Path, facts: &Facts) -> Result<Main> {
+    if is_stdin(Some(path)) {
+        // so included files are relative to the current directory
+        return Ok(template::render_config(
+            read_text(path)?,
+            Path::new(""),
+            facts,
+        )?);
+    }
+    Ok(template::render_file(path, facts)?)
+}
+
+// stdin can only be read once, but e.g. `--ask` reads the config again
+fn read_text(path: &Path) -> Result<String> {
+    static TEXT: OnceLock<String> = OnceLock::new();
+    if !is_stdin(Some(path)) {
+        return Ok(fs::read_to_string(path)?);
+    }
+    if let Some(text) = TEXT.get() {
+        return Ok(text.clone());
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(TEXT.get_or_init(|| text).clone())
+}
+
+fn is_stdin(config: Option<&Path>) -> bool {
+    config == Some(Path::new(STDIN))
+}
+
+// the config file that would be read, if there is one
+fn find_config(facts: &Facts, config: Option<&Path>) -> Option<PathBuf> {
+    config_paths(facts, config)
+        .into_iter()
+        .find(|p| is_stdin(Some(p)) || p.is_file())
+}
+
+// where to look for the config file, in order,
+// which is `config` (or main.toml in it, if a directory) if given
+fn config_paths(facts: &Facts, config: Option<&Path>) -> Vec<PathBuf> {
+    match config {
+        Some(p) if p.is_dir() => vec![p.join(MAIN_TOML_FILE)],
+        Some(p) => vec![p.to_path_buf()],
+        None => vec![
+            facts
+                .config_dir
+                .join(env!("CARGO_PKG_NAME"))
+                .join(MAIN_TOML_FILE),
+            facts
+                .home_dir
+                .join(".dotfiles")
+                .join(env!("CARGO_PKG_NAME"))
+                .join(MAIN_TOML_FILE),
+        ],
+    }
+}